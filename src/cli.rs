@@ -1,8 +1,80 @@
 use clap::{Parser, ValueEnum};
+use std::ops::RangeInclusive;
 
 use crate::domain::{
-    CompressionConfig, CropCoordinates, FlipOptions, OutputFormat, Preset, VideoTransforms,
+    AudioCodec, AudioFilters, AudioSettings, AutoCrop, BurnSubtitles, ColorAdjust,
+    CompressionConfig, CropCoordinates, Deinterlace, Denoise, FlipOptions, GifDither, HdrMode,
+    ImageFormat, MaxResolution, MetadataMode, OutputFormat, Overlay, OverlayPosition, Preset,
+    QualityTarget, RemuxMode, SplitMode, ThumbnailRequest, TrackSelection, TrimRange, VideoCodec,
+    VideoFilters, VideoTransforms,
 };
+use crate::error::CompressoError;
+
+/// Options that only make sense when compressing; thumbnail mode reads a
+/// single file and writes images, so they are refused there rather than
+/// ignored. Frames are always spread over the whole video, so this includes
+/// the trim options.
+const STILL_CONFLICTS: [&str; 59] = [
+    "dir",
+    "concat",
+    "quality",
+    "preset",
+    "target_size",
+    "split_size",
+    "split_duration",
+    "remux",
+    "two_pass",
+    "target_quality",
+    "measure_quality",
+    "format",
+    "gif_dither",
+    "codec",
+    "width",
+    "height",
+    "fps",
+    "max_resolution",
+    "allow_upscale",
+    "start",
+    "end",
+    "duration",
+    "speed",
+    "timelapse",
+    "mute",
+    "audio_codec",
+    "audio_bitrate",
+    "audio_channels",
+    "audio_tracks",
+    "subtitles",
+    "burn_subtitles",
+    "burn_subtitle_track",
+    "metadata",
+    "preserve_mtime",
+    "hdr",
+    "audio_rate",
+    "normalize_audio",
+    "volume",
+    "audio_fade_in",
+    "audio_fade_out",
+    "rotate",
+    "flip_h",
+    "flip_v",
+    "denoise",
+    "sharpen",
+    "deinterlace",
+    "brightness",
+    "contrast",
+    "saturation",
+    "gamma",
+    "lut",
+    "watermark",
+    "text_overlay",
+    "text_font",
+    "watermark_position",
+    "watermark_margin",
+    "watermark_opacity",
+    "crop",
+    "crop_aspect",
+];
 
 #[derive(Parser, Debug)]
 #[command(
@@ -26,22 +98,87 @@ pub struct Cli {
     #[arg(long, conflicts_with = "input")]
     pub dir: Option<String>,
 
+    /// Join the inputs, in the order given (or by name with --dir), into
+    /// one output instead of compressing each of them
+    #[arg(long, conflicts_with_all = ["target_quality", "measure_quality"])]
+    pub concat: bool,
+
     /// Output file path (only for single file, default: <input>_compressed.<ext>)
     #[arg(short, long)]
     pub output: Option<String>,
 
-    /// Compression quality (0-100, higher = better quality, larger file)
-    #[arg(short, long, default_value = "70", value_parser = clap::value_parser!(u8).range(0..=100))]
-    pub quality: u8,
+    /// Compression quality (0-100, higher = better quality, larger file) [default: 70]
+    #[arg(short, long, value_parser = clap::value_parser!(u8).range(0..=100))]
+    pub quality: Option<u8>,
 
     /// Compression preset
     #[arg(short, long, value_enum, default_value = "ironclad")]
     pub preset: PresetArg,
 
-    /// Output format (mp4, mov, webm, avi, mkv)
+    /// Target output size, e.g. 25MB, 8M, 500KB (1 MB = 1024 KB); computes a
+    /// bitrate from the video duration instead of using --quality
+    #[arg(long, value_parser = parse_size, conflicts_with = "quality")]
+    pub target_size: Option<u64>,
+
+    /// Cut the output into parts of at most this size, e.g. 25MB or 2GB,
+    /// named <output>_part01, _part02, ...
+    #[arg(
+        long,
+        value_name = "SIZE",
+        value_parser = parse_size,
+        conflicts_with_all = ["split_duration", "measure_quality"]
+    )]
+    pub split_size: Option<u64>,
+
+    /// Cut the output into parts of this length (HH:MM:SS or seconds),
+    /// named <output>_part01, _part02, ...
+    #[arg(
+        long,
+        value_name = "TIME",
+        value_parser = parse_split_duration,
+        conflicts_with = "measure_quality"
+    )]
+    pub split_duration: Option<f64>,
+
+    /// Copy the streams into the output container without re-encoding
+    /// (lossless and fast; also chosen automatically for a plain container
+    /// change when the source streams fit the new container)
+    #[arg(
+        long,
+        conflicts_with_all = [
+            "quality", "codec", "target_size", "target_quality", "two_pass",
+            "audio_codec", "audio_bitrate", "audio_channels", "audio_rate",
+        ]
+    )]
+    pub remux: bool,
+
+    /// Two-pass encoding: analyze the video first for better bitrate
+    /// distribution (h264/hevc need --target-size)
+    #[arg(long)]
+    pub two_pass: bool,
+
+    /// Target perceptual quality: a VMAF score (e.g. 93) or an SSIM score
+    /// (e.g. 0.98); picks the CRF by encoding short samples
+    #[arg(long, conflicts_with_all = ["quality", "target_size"])]
+    pub target_quality: Option<QualityTarget>,
+
+    /// Measure SSIM/PSNR (and VMAF if available) of the output against the source
+    #[arg(long)]
+    pub measure_quality: bool,
+
+    /// Output format (mp4, mov, webm, avi, mkv, or gif/webp for an
+    /// animated preview without sound)
     #[arg(short, long)]
     pub format: Option<FormatArg>,
 
+    /// Dithering for GIF output
+    #[arg(long, value_enum, value_name = "MODE", default_value = "sierra")]
+    pub gif_dither: GifDitherArg,
+
+    /// Video codec (default: vp9 for webm, h264 for everything else)
+    #[arg(long, value_enum)]
+    pub codec: Option<CodecArg>,
+
     /// Output video width
     #[arg(long)]
     pub width: Option<u32>,
@@ -54,10 +191,110 @@ pub struct Cli {
     #[arg(long)]
     pub fps: Option<u32>,
 
+    /// Fit the video into a box such as 1280x720 or 720p, applied to the
+    /// long edge so vertical videos are handled too
+    #[arg(long, visible_alias = "fit", conflicts_with_all = ["width", "height"])]
+    pub max_resolution: Option<MaxResolution>,
+
+    /// Allow --width/--height/--max-resolution to enlarge the video
+    #[arg(long)]
+    pub allow_upscale: bool,
+
+    /// Start of the section to keep (HH:MM:SS.mmm or seconds)
+    #[arg(long, value_parser = parse_timestamp)]
+    pub start: Option<f64>,
+
+    /// End of the section to keep (HH:MM:SS.mmm or seconds)
+    #[arg(long, value_parser = parse_timestamp, conflicts_with = "duration")]
+    pub end: Option<f64>,
+
+    /// Length of the section to keep, counted from --start (HH:MM:SS.mmm or seconds)
+    #[arg(long, value_parser = parse_timestamp)]
+    pub duration: Option<f64>,
+
+    /// Playback speed factor, e.g. 2 (twice as fast) or 0.5 (half speed);
+    /// audio keeps its pitch
+    #[arg(long, value_name = "FACTOR", value_parser = parse_speed, conflicts_with = "remux")]
+    pub speed: Option<f64>,
+
+    /// Drop the audio of a --speed change
+    #[arg(long, requires = "speed")]
+    pub timelapse: bool,
+
     /// Remove audio from video
     #[arg(long)]
     pub mute: bool,
 
+    /// Audio codec (default: opus for webm, mp3 for avi/wmv, aac for everything else)
+    #[arg(long, value_enum, conflicts_with = "mute")]
+    pub audio_codec: Option<AudioCodecArg>,
+
+    /// Audio bitrate, e.g. 96k or 128 (kbit/s)
+    #[arg(long, value_parser = parse_audio_bitrate, conflicts_with = "mute")]
+    pub audio_bitrate: Option<u32>,
+
+    /// Audio channels (1 = downmix to mono)
+    #[arg(long, value_parser = clap::value_parser!(u8).range(1..=8), conflicts_with = "mute")]
+    pub audio_channels: Option<u8>,
+
+    /// Audio tracks to keep: `all`, track numbers such as `1,3`, or
+    /// languages such as `lang:eng,rus` (default: the main track)
+    #[arg(long, value_parser = parse_audio_tracks, conflicts_with = "mute")]
+    pub audio_tracks: Option<TrackSelection>,
+
+    /// Subtitles: `keep`, `drop` or languages such as `lang:rus`; converted to
+    /// mov_text for mp4/mov and webvtt for webm (default: drop)
+    #[arg(long, value_parser = parse_subtitles)]
+    pub subtitles: Option<TrackSelection>,
+
+    /// Render a subtitle file (.srt, .ass, .ssa, .vtt) into the picture
+    #[arg(long, value_name = "FILE", conflicts_with = "remux")]
+    pub burn_subtitles: Option<String>,
+
+    /// Render a subtitle track of the input (counted from 1) into the picture
+    #[arg(
+        long,
+        value_name = "N",
+        value_parser = clap::value_parser!(u32).range(1..),
+        conflicts_with_all = ["burn_subtitles", "remux"]
+    )]
+    pub burn_subtitle_track: Option<u32>,
+
+    /// Metadata (creation date, GPS, camera model): keep all, strip all, or
+    /// keep only the creation date and track languages
+    #[arg(long, value_enum, default_value = "keep")]
+    pub metadata: MetadataArg,
+
+    /// Give the output the same modification time as the input
+    #[arg(long)]
+    pub preserve_mtime: bool,
+
+    /// HDR video: keep it as 10-bit HDR or tone-map it to SDR (default: keep
+    /// for hevc, vp9 and av1, tonemap for h264)
+    #[arg(long, value_enum)]
+    pub hdr: Option<HdrArg>,
+
+    /// Audio sample rate in Hz (e.g. 44100, 48000)
+    #[arg(long, value_parser = clap::value_parser!(u32).range(8000..=192000), conflicts_with = "mute")]
+    pub audio_rate: Option<u32>,
+
+    /// Normalize the loudness to -16 LUFS (EBU R128); measures the audio in
+    /// an extra pass first
+    #[arg(long, conflicts_with = "remux")]
+    pub normalize_audio: bool,
+
+    /// Change the volume in dB, e.g. -3 or +6 (after --normalize-audio)
+    #[arg(long, value_name = "DB", value_parser = parse_volume, allow_hyphen_values = true, conflicts_with = "remux")]
+    pub volume: Option<f32>,
+
+    /// Fade the audio in over this many seconds
+    #[arg(long, value_name = "SECONDS", value_parser = parse_fade, conflicts_with = "remux")]
+    pub audio_fade_in: Option<f64>,
+
+    /// Fade the audio out over this many seconds at the end
+    #[arg(long, value_name = "SECONDS", value_parser = parse_fade, conflicts_with = "remux")]
+    pub audio_fade_out: Option<f64>,
+
     /// Rotate video (90, 180, 270, -90, -180, -270)
     #[arg(long, value_parser = parse_rotation)]
     pub rotate: Option<i32>,
@@ -70,9 +307,72 @@ pub struct Cli {
     #[arg(long)]
     pub flip_v: bool,
 
-    /// Crop video (format: WxH:X:Y, e.g., 1920x1080:0:0)
-    #[arg(long, value_parser = parse_crop)]
-    pub crop: Option<CropCoordinates>,
+    /// Reduce noise and grain (strong is much slower)
+    #[arg(long, value_enum, value_name = "LEVEL", conflicts_with = "remux")]
+    pub denoise: Option<DenoiseArg>,
+
+    /// Sharpen the picture after scaling
+    #[arg(long, conflicts_with = "remux")]
+    pub sharpen: bool,
+
+    /// Deinterlace: always, never, or when a sample of the video looks
+    /// interlaced
+    #[arg(long, value_enum, value_name = "MODE", default_value = "off")]
+    pub deinterlace: DeinterlaceArg,
+
+    /// Brightness, -1.0 to 1.0 (0 = unchanged)
+    #[arg(long, value_name = "VALUE", value_parser = parse_brightness, allow_hyphen_values = true, conflicts_with = "remux")]
+    pub brightness: Option<f32>,
+
+    /// Contrast, 0.0 to 3.0 (1 = unchanged)
+    #[arg(long, value_name = "VALUE", value_parser = parse_contrast, conflicts_with = "remux")]
+    pub contrast: Option<f32>,
+
+    /// Saturation, 0.0 to 3.0 (1 = unchanged, 0 = greyscale)
+    #[arg(long, value_name = "VALUE", value_parser = parse_saturation, conflicts_with = "remux")]
+    pub saturation: Option<f32>,
+
+    /// Gamma, 0.1 to 10.0 (1 = unchanged)
+    #[arg(long, value_name = "VALUE", value_parser = parse_gamma, conflicts_with = "remux")]
+    pub gamma: Option<f32>,
+
+    /// Apply a 3D LUT (.cube, .3dl, .dat, .m3d or .csp), e.g. to convert log
+    /// footage
+    #[arg(long, value_name = "FILE", conflicts_with = "remux")]
+    pub lut: Option<String>,
+
+    /// Image (e.g. a PNG logo) to draw over the video
+    #[arg(long, value_name = "IMAGE", conflicts_with = "remux")]
+    pub watermark: Option<String>,
+
+    /// Text to draw over the video
+    #[arg(long, value_name = "TEXT", conflicts_with = "remux")]
+    pub text_overlay: Option<String>,
+
+    /// Font file for --text-overlay (default: the system font via fontconfig)
+    #[arg(long, value_name = "FILE", requires = "text_overlay")]
+    pub text_font: Option<String>,
+
+    /// Where the watermark and text go
+    #[arg(long, value_enum, value_name = "POS", default_value = "br")]
+    pub watermark_position: OverlayPositionArg,
+
+    /// Distance of the watermark and text from the frame edges, in pixels
+    #[arg(long, value_name = "PX", default_value_t = 20)]
+    pub watermark_margin: u32,
+
+    /// Opacity of the watermark and text, 0.0 to 1.0
+    #[arg(long, value_name = "VALUE", value_parser = parse_opacity, default_value = "1.0")]
+    pub watermark_opacity: f32,
+
+    /// Crop video (format: WxH:X:Y, e.g., 1920x1080:0:0), or `auto` to
+    /// remove black bars
+    #[arg(long, value_parser = parse_crop_arg)]
+    pub crop: Option<CropArg>,
+
+    /// Centred crop to an aspect ratio such as 1:1 or 9:16
+    #[arg(long, value_parser = parse_aspect_ratio, conflicts_with = "crop")]
+    pub crop_aspect: Option<(u32, u32)>,
 
     /// Overwrite output file without asking
     #[arg(short = 'y', long)]
@@ -90,6 +390,47 @@ pub struct Cli {
     #[arg(long)]
     pub json: bool,
 
+    /// Extract N evenly spaced thumbnails instead of compressing
+    #[arg(
+        long,
+        value_name = "N",
+        value_parser = clap::value_parser!(u32).range(1..=1000),
+        conflicts_with_all = STILL_CONFLICTS
+    )]
+    pub thumbnails: Option<u32>,
+
+    /// Also tile the thumbnails into a sprite with a WebVTT track for web
+    /// players
+    #[arg(long, requires = "thumbnails")]
+    pub sprite: bool,
+
+    /// Save a full-size poster frame at this time (seconds or HH:MM:SS)
+    #[arg(
+        long,
+        value_name = "TIME",
+        value_parser = parse_timestamp,
+        conflicts_with_all = STILL_CONFLICTS
+    )]
+    pub poster: Option<f64>,
+
+    /// Save a contact sheet of evenly spaced, timestamped frames (e.g. 4x4)
+    #[arg(
+        long,
+        value_name = "COLSxROWS",
+        value_parser = parse_grid,
+        conflicts_with_all = STILL_CONFLICTS
+    )]
+    pub contact_sheet: Option<(u32, u32)>,
+
+    /// Image format for thumbnails, posters and contact sheets
+    #[arg(long, value_enum, value_name = "FORMAT", default_value = "jpg")]
+    pub image_format: ImageFormatArg,
+
+    /// Width of thumbnails and contact sheet tiles
+    #[arg(long, value_name = "PIXELS", default_value = "320",
+          value_parser = clap::value_parser!(u32).range(16..=3840))]
+    pub thumbnail_width: u32,
+
     /// Language for the application interface
     #[arg(long, value_enum, default_value = "english")]
     pub language: LanguageArg,
@@ -119,6 +460,8 @@ pub enum FormatArg {
     Webm,
     Avi,
     Mkv,
+    Gif,
+    Webp,
 }
 
 impl From<FormatArg> for OutputFormat {
@@ -129,6 +472,196 @@ impl From<FormatArg> for OutputFormat {
             FormatArg::Webm => OutputFormat::Webm,
             FormatArg::Avi => OutputFormat::Avi,
             FormatArg::Mkv => OutputFormat::Mkv,
+            FormatArg::Gif => OutputFormat::Gif,
+            FormatArg::Webp => OutputFormat::Webp,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub enum CodecArg {
+    /// H.264 / AVC (libx264), plays everywhere
+    H264,
+    /// H.265 / HEVC (libx265), ~30-40% smaller than H.264 at similar quality
+    #[value(alias = "h265")]
+    Hevc,
+    /// VP9 (libvpx-vp9)
+    Vp9,
+    /// AV1 (SVT-AV1, or libaom when SVT-AV1 is unavailable); mkv, mp4, webm only
+    Av1,
+}
+
+impl From<CodecArg> for VideoCodec {
+    fn from(arg: CodecArg) -> Self {
+        match arg {
+            CodecArg::H264 => VideoCodec::H264,
+            CodecArg::Hevc => VideoCodec::Hevc,
+            CodecArg::Vp9 => VideoCodec::Vp9,
+            CodecArg::Av1 => VideoCodec::Av1,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub enum AudioCodecArg {
+    /// AAC; mp4, mov, mkv, avi, flv
+    Aac,
+    /// Opus; webm, mkv, mp4
+    Opus,
+    /// MP3; everything except webm
+    Mp3,
+    /// Keep the source audio without re-encoding
+    Copy,
+}
+
+impl From<AudioCodecArg> for AudioCodec {
+    fn from(arg: AudioCodecArg) -> Self {
+        match arg {
+            AudioCodecArg::Aac => AudioCodec::Aac,
+            AudioCodecArg::Opus => AudioCodec::Opus,
+            AudioCodecArg::Mp3 => AudioCodec::Mp3,
+            AudioCodecArg::Copy => AudioCodec::Copy,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub enum MetadataArg {
+    /// Copy all metadata, including GPS location
+    Keep,
+    /// Remove all metadata
+    Strip,
+    /// Keep only the creation date and track languages
+    Minimal,
+}
+
+impl From<MetadataArg> for MetadataMode {
+    fn from(arg: MetadataArg) -> Self {
+        match arg {
+            MetadataArg::Keep => MetadataMode::Keep,
+            MetadataArg::Strip => MetadataMode::Strip,
+            MetadataArg::Minimal => MetadataMode::Minimal,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub enum HdrArg {
+    /// 10-bit HDR output; hevc, vp9 and av1 only
+    Keep,
+    /// Convert to SDR (BT.709)
+    Tonemap,
+}
+
+impl From<HdrArg> for HdrMode {
+    fn from(arg: HdrArg) -> Self {
+        match arg {
+            HdrArg::Keep => HdrMode::Keep,
+            HdrArg::Tonemap => HdrMode::Tonemap,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub enum DenoiseArg {
+    /// Light hqdn3d
+    Light,
+    /// Default hqdn3d
+    Medium,
+    /// Non-local means
+    Strong,
+}
+
+impl From<DenoiseArg> for Denoise {
+    fn from(arg: DenoiseArg) -> Self {
+        match arg {
+            DenoiseArg::Light => Denoise::Light,
+            DenoiseArg::Medium => Denoise::Medium,
+            DenoiseArg::Strong => Denoise::Strong,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub enum DeinterlaceArg {
+    /// Detect interlacing from a sample of the video
+    Auto,
+    /// Always deinterlace
+    On,
+    /// Never deinterlace
+    Off,
+}
+
+impl From<DeinterlaceArg> for Deinterlace {
+    fn from(arg: DeinterlaceArg) -> Self {
+        match arg {
+            DeinterlaceArg::Auto => Deinterlace::Auto,
+            DeinterlaceArg::On => Deinterlace::On,
+            DeinterlaceArg::Off => Deinterlace::Off,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub enum GifDitherArg {
+    /// Ordered pattern; compresses best
+    Bayer,
+    /// Error diffusion; smooth gradients, larger files
+    FloydSteinberg,
+    /// Lighter error diffusion
+    Sierra,
+    /// No dithering; visible colour bands
+    None,
+}
+
+impl From<GifDitherArg> for GifDither {
+    fn from(arg: GifDitherArg) -> Self {
+        match arg {
+            GifDitherArg::Bayer => GifDither::Bayer,
+            GifDitherArg::FloydSteinberg => GifDither::FloydSteinberg,
+            GifDitherArg::Sierra => GifDither::Sierra,
+            GifDitherArg::None => GifDither::None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub enum ImageFormatArg {
+    Jpg,
+    Webp,
+}
+
+impl From<ImageFormatArg> for ImageFormat {
+    fn from(arg: ImageFormatArg) -> Self {
+        match arg {
+            ImageFormatArg::Jpg => ImageFormat::Jpeg,
+            ImageFormatArg::Webp => ImageFormat::Webp,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub enum OverlayPositionArg {
+    /// Top left corner
+    Tl,
+    /// Top right corner
+    Tr,
+    /// Bottom left corner
+    Bl,
+    /// Bottom right corner
+    Br,
+    /// Centre of the frame
+    Center,
+}
+
+impl From<OverlayPositionArg> for OverlayPosition {
+    fn from(arg: OverlayPositionArg) -> Self {
+        match arg {
+            OverlayPositionArg::Tl => OverlayPosition::TopLeft,
+            OverlayPositionArg::Tr => OverlayPosition::TopRight,
+            OverlayPositionArg::Bl => OverlayPosition::BottomLeft,
+            OverlayPositionArg::Br => OverlayPosition::BottomRight,
+            OverlayPositionArg::Center => OverlayPosition::Center,
         }
     }
 }
@@ -158,6 +691,262 @@ fn parse_rotation(s: &str) -> Result<i32, String> {
     }
 }
 
+/// Parse a human-readable size such as `25MB`, `8M`, `500k` or `1.5GB`.
+///
+/// Units are binary (1 KB = 1024 bytes) to match how sizes are reported;
+/// a bare number is a byte count.
+pub fn parse_size(s: &str) -> Result<u64, String> {
+    let s = s.trim();
+    let split = s
+        .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+        .unwrap_or(s.len());
+    let (number, unit) = s.split_at(split);
+    let number: f64 = number
+        .parse()
+        .map_err(|_| format!("Invalid size: {} (e.g. 25MB, 8M, 500KB)", s))?;
+
+    let multiplier: u64 = match unit.trim().to_lowercase().as_str() {
+        "" | "b" => 1,
+        "k" | "kb" => 1024,
+        "m" | "mb" => 1024 * 1024,
+        "g" | "gb" => 1024 * 1024 * 1024,
+        _ => return Err(format!("Unknown size unit: {} (use KB, MB or GB)", unit)),
+    };
+
+    let bytes = (number * multiplier as f64).round() as u64;
+    if bytes == 0 {
+        return Err("Size must be greater than zero".to_string());
+    }
+    Ok(bytes)
+}
+
+/// Parse a timestamp: `HH:MM:SS.mmm`, `MM:SS` or plain seconds (`90.5`).
+///
+/// Shared with the interactive wizard.
+pub fn parse_timestamp(s: &str) -> Result<f64, String> {
+    let invalid = || format!("Invalid time: {} (e.g. 01:30, 00:01:30.500, 90.5)", s);
+    let parts: Vec<&str> = s.trim().split(':').collect();
+    if parts.len() > 3 {
+        return Err(invalid());
+    }
+
+    let mut seconds = 0.0;
+    for (i, part) in parts.iter().enumerate() {
+        let value: f64 = part.parse().map_err(|_| invalid())?;
+        // Only the last field may carry a fraction, and only the leading one
+        // may exceed 59.
+        let is_last = i == parts.len() - 1;
+        if !value.is_finite() || value < 0.0 || (!is_last && value.fract() != 0.0) {
+            return Err(invalid());
+        }
+        if i > 0 && value >= 60.0 {
+            return Err(invalid());
+        }
+        seconds = seconds * 60.0 + value;
+    }
+    Ok(seconds)
+}
+
+/// Parse a `lang:eng,rus` list; None when `s` has no `lang:` prefix.
+fn parse_languages(s: &str) -> Option<Result<TrackSelection, String>> {
+    let list = s.strip_prefix("lang:")?;
+    let languages: Vec<String> = list
+        .split(',')
+        .map(|l| l.trim().to_lowercase())
+        .filter(|l| !l.is_empty())
+        .collect();
+    Some(
+        if languages.is_empty()
+            || languages
+                .iter()
+                .any(|l| !l.chars().all(|c| c.is_ascii_alphabetic()))
+        {
+            Err(format!(
+                "Invalid language list: {} (e.g. lang:eng or lang:eng,rus)",
+                s
+            ))
+        } else {
+            Ok(TrackSelection::Languages(languages))
+        },
+    )
+}
+
+/// Parse `--audio-tracks`: `all`, `1,3` or `lang:eng,rus`.
+pub fn parse_audio_tracks(s: &str) -> Result<TrackSelection, String> {
+    let s = s.trim().to_lowercase();
+    if s == "all" {
+        return Ok(TrackSelection::All);
+    }
+    if let Some(languages) = parse_languages(&s) {
+        return languages;
+    }
+    let numbers = s
+        .split(',')
+        .map(|n| n.trim().parse::<u32>().ok().filter(|n| *n >= 1))
+        .collect::<Option<Vec<u32>>>()
+        .ok_or_else(|| format!("Invalid audio tracks: {} (use all, 1,3 or lang:eng)", s))?;
+    Ok(TrackSelection::Numbers(numbers))
+}
+
+/// Parse `--subtitles`: `keep`, `drop` or `lang:rus`.
+pub fn parse_subtitles(s: &str) -> Result<TrackSelection, String> {
+    let s = s.trim().to_lowercase();
+    match s.as_str() {
+        "keep" => Ok(TrackSelection::All),
+        "drop" => Ok(TrackSelection::None),
+        _ => parse_languages(&s).unwrap_or_else(|| {
+            Err(format!(
+                "Invalid subtitles mode: {} (use keep, drop or lang:rus)",
+                s
+            ))
+        }),
+    }
+}
+
+/// Parse an audio bitrate in kbit/s: `96k`, `128K` or a bare `128`.
+///
+/// Shared with the interactive wizard.
+pub fn parse_audio_bitrate(s: &str) -> Result<u32, String> {
+    let digits = s.trim().trim_end_matches(['k', 'K']);
+    let kbps: u32 = digits
+        .parse()
+        .map_err(|_| format!("Invalid audio bitrate: {} (e.g. 96k, 128k)", s))?;
+    if !(8..=512).contains(&kbps) {
+        return Err("Audio bitrate must be between 8k and 512k".to_string());
+    }
+    Ok(kbps)
+}
+
+/// Parse `--volume` in dB: `-3`, `+6` or `6dB`, from -60 to 60.
+pub fn parse_volume(s: &str) -> Result<f32, String> {
+    let value = s.trim();
+    let value = value
+        .strip_suffix("dB")
+        .or_else(|| value.strip_suffix("db"))
+        .unwrap_or(value);
+    let db: f32 = value
+        .trim()
+        .parse()
+        .map_err(|_| format!("Invalid volume: {} (e.g. -3, +6 or 6dB)", s))?;
+    if !(-60.0..=60.0).contains(&db) {
+        return Err("Volume must be between -60 and 60 dB".to_string());
+    }
+    Ok(db)
+}
+
+/// Parse a fade length for `--audio-fade-in/--audio-fade-out`, in the same
+/// forms as [`parse_timestamp`].
+pub fn parse_fade(s: &str) -> Result<f64, String> {
+    let seconds = parse_timestamp(s)?;
+    if seconds <= 0.0 {
+        return Err("Fade length must be greater than zero".to_string());
+    }
+    Ok(seconds)
+}
+
+/// Parse a `--split-duration` part length, in the same forms as
+/// [`parse_timestamp`].
+pub fn parse_split_duration(s: &str) -> Result<f64, String> {
+    let seconds = parse_timestamp(s)?;
+    if seconds < 1.0 {
+        return Err("Parts must be at least 1 second long".to_string());
+    }
+    Ok(seconds)
+}
+
+/// Parse a colour adjustment value and check it against its range.
+fn parse_color_value(s: &str, name: &str, range: RangeInclusive<f32>) -> Result<f32, String> {
+    let value: f32 = s
+        .trim()
+        .parse()
+        .map_err(|_| format!("Invalid {}: {}", name.to_lowercase(), s))?;
+    if !range.contains(&value) {
+        return Err(format!(
+            "{} must be between {} and {}",
+            name,
+            range.start(),
+            range.end()
+        ));
+    }
+    Ok(value)
+}
+
+/// Parse `--brightness` (-1.0 to 1.0).
+pub fn parse_brightness(s: &str) -> Result<f32, String> {
+    parse_color_value(s, "Brightness", -1.0..=1.0)
+}
+
+/// Parse `--contrast` (0.0 to 3.0).
+pub fn parse_contrast(s: &str) -> Result<f32, String> {
+    parse_color_value(s, "Contrast", 0.0..=3.0)
+}
+
+/// Parse `--saturation` (0.0 to 3.0).
+pub fn parse_saturation(s: &str) -> Result<f32, String> {
+    parse_color_value(s, "Saturation", 0.0..=3.0)
+}
+
+/// Parse `--gamma` (0.1 to 10.0).
+pub fn parse_gamma(s: &str) -> Result<f32, String> {
+    parse_color_value(s, "Gamma", 0.1..=10.0)
+}
+
+/// Parse `--watermark-opacity` (0.0 to 1.0).
+pub fn parse_opacity(s: &str) -> Result<f32, String> {
+    parse_color_value(s, "Opacity", 0.0..=1.0)
+}
+
+/// Parse `--speed`: a factor such as `2`, `0.5` or `8x`, from 0.1 to 100.
+pub fn parse_speed(s: &str) -> Result<f64, String> {
+    let factor: f64 = s
+        .trim()
+        .trim_end_matches(['x', 'X'])
+        .parse()
+        .map_err(|_| format!("Invalid speed: {} (e.g. 2, 0.5 or 8x)", s))?;
+    if !(0.1..=100.0).contains(&factor) {
+        return Err("Speed must be between 0.1 and 100".to_string());
+    }
+    Ok(factor)
+}
+
+/// `--crop`: fixed coordinates or `auto`
+#[derive(Debug, Clone)]
+pub enum CropArg {
+    Manual(CropCoordinates),
+    Auto,
+}
+
+/// Parse `--crop`: `auto` or a [`parse_crop`] specification.
+pub fn parse_crop_arg(s: &str) -> Result<CropArg, String> {
+    if s.trim().eq_ignore_ascii_case("auto") {
+        Ok(CropArg::Auto)
+    } else {
+        parse_crop(s).map(CropArg::Manual)
+    }
+}
+
+/// Parse an aspect ratio such as `1:1`, `9:16` or `4:5`.
+pub fn parse_aspect_ratio(s: &str) -> Result<(u32, u32), String> {
+    let invalid = || format!("Invalid aspect ratio: {} (use W:H, e.g. 1:1 or 9:16)", s);
+    let (w, h) = s.trim().split_once(':').ok_or_else(invalid)?;
+    match (w.parse::<u32>(), h.parse::<u32>()) {
+        (Ok(w), Ok(h)) if w > 0 && h > 0 => Ok((w, h)),
+        _ => Err(invalid()),
+    }
+}
+
+/// Parse a contact sheet grid such as `4x4` or `5x3` (columns x rows).
+pub fn parse_grid(s: &str) -> Result<(u32, u32), String> {
+    let invalid = || format!("Invalid grid: {} (use COLSxROWS, e.g. 4x4)", s);
+    let (cols, rows) = s.trim().split_once(['x', 'X']).ok_or_else(invalid)?;
+    match (cols.parse::<u32>(), rows.parse::<u32>()) {
+        (Ok(cols), Ok(rows)) if (1..=20).contains(&cols) && (1..=20).contains(&rows) => {
+            Ok((cols, rows))
+        }
+        _ => Err(invalid()),
+    }
+}
+
 /// Parse a crop specification string.
 ///
 /// Accepts `WxH:X:Y` (e.g. `1920x1080:0:0`) or `W:H:X:Y`.
@@ -193,6 +982,66 @@ pub fn parse_crop(s: &str) -> Result<CropCoordinates, String> {
 }
 
 impl Cli {
+    /// `--remux` forces stream copy; any explicit encoding option rules it
+    /// out; otherwise build_args decides from the source streams.
+    fn remux_mode(&self) -> RemuxMode {
+        let encoding_requested = self.quality.is_some()
+            || self.codec.is_some()
+            || self.target_size.is_some()
+            || self.target_quality.is_some()
+            || self.two_pass
+            || self.audio_codec.is_some()
+            || self.audio_bitrate.is_some()
+            || self.audio_channels.is_some()
+            || self.audio_rate.is_some();
+
+        if self.remux {
+            RemuxMode::Always
+        } else if encoding_requested {
+            RemuxMode::Never
+        } else {
+            RemuxMode::Auto
+        }
+    }
+
+    /// Thumbnail mode settings, when `--thumbnails`, `--poster` or
+    /// `--contact-sheet` is given. `-o` names the output directory here.
+    /// Thumbnail mode works on one file, so several inputs are refused
+    /// rather than all but the first being dropped.
+    pub fn thumbnail_request(&self) -> Option<crate::error::Result<ThumbnailRequest>> {
+        if self.thumbnails.is_none() && self.poster.is_none() && self.contact_sheet.is_none() {
+            return None;
+        }
+        if self.input.len() > 1 {
+            return Some(Err(CompressoError::IncompatibleOptions(
+                "--thumbnails, --poster and --contact-sheet take a single input".to_string(),
+            )));
+        }
+        Some(Ok(ThumbnailRequest {
+            input_path: self.input.first().cloned().unwrap_or_default(),
+            output_dir: self.output.clone(),
+            thumbnails: self.thumbnails,
+            sprite: self.sprite,
+            poster: self.poster,
+            contact_sheet: self.contact_sheet,
+            format: self.image_format.into(),
+            width: self.thumbnail_width,
+            overwrite: self.overwrite,
+        }))
+    }
+
+    /// `--duration` counts from `--start`, so both end up as an absolute end.
+    fn trim_range(&self) -> Option<TrimRange> {
+        if self.start.is_none() && self.end.is_none() && self.duration.is_none() {
+            return None;
+        }
+        let start = self.start.unwrap_or(0.0);
+        Some(TrimRange {
+            start,
+            end: self.end.or(self.duration.map(|length| start + length)),
+        })
+    }
+
     pub fn to_config(&self) -> CompressionConfig {
         let flip = if self.flip_h || self.flip_v {
             Some(FlipOptions {
@@ -203,23 +1052,89 @@ impl Cli {
             None
         };
 
+        let (crop, auto_crop) = match &self.crop {
+            Some(CropArg::Manual(crop)) => (Some(crop.clone()), None),
+            Some(CropArg::Auto) => (None, Some(AutoCrop::Detect)),
+            None => (None, self.crop_aspect.map(|(w, h)| AutoCrop::Aspect(w, h))),
+        };
         let transforms = VideoTransforms {
-            crop: self.crop.clone(),
+            crop,
             rotate: self.rotate,
             flip,
         };
 
         CompressionConfig {
             input_path: self.input.first().cloned().unwrap_or_default(),
+            concat: Vec::new(),
             output_path: self.output.clone(),
             format: self.format.map(|f| f.into()),
             preset: self.preset.into(),
-            quality: self.quality,
+            codec: self.codec.map(|c| c.into()),
+            quality: self.quality.unwrap_or(70),
+            target_size: self.target_size,
+            target_quality: self.target_quality,
+            crf: None,
+            two_pass: self.two_pass,
+            measure_quality: self.measure_quality,
             width: self.width,
             height: self.height,
             fps: self.fps,
-            mute: self.mute,
+            max_resolution: self.max_resolution,
+            allow_upscale: self.allow_upscale,
+            mute: self.mute || self.timelapse,
+            audio: AudioSettings {
+                codec: self.audio_codec.map(|c| c.into()),
+                bitrate_kbps: self.audio_bitrate,
+                channels: self.audio_channels,
+                sample_rate: self.audio_rate,
+            },
+            audio_filters: AudioFilters {
+                normalize: self.normalize_audio,
+                loudness: None,
+                volume_db: self.volume,
+                fade_in: self.audio_fade_in,
+                fade_out: self.audio_fade_out,
+            },
+            audio_tracks: self.audio_tracks.clone().unwrap_or_default(),
+            subtitles: self.subtitles.clone().unwrap_or_default(),
+            burn_subtitles: self
+                .burn_subtitles
+                .clone()
+                .map(BurnSubtitles::File)
+                .or(self.burn_subtitle_track.map(BurnSubtitles::Track)),
+            metadata: self.metadata.into(),
+            preserve_mtime: self.preserve_mtime,
+            hdr: self.hdr.map(Into::into),
+            remux: self.remux_mode(),
+            trim: self.trim_range(),
+            speed: self.speed,
+            auto_crop,
             transforms,
+            filters: VideoFilters {
+                denoise: self.denoise.map(Into::into),
+                sharpen: self.sharpen,
+                deinterlace: self.deinterlace.into(),
+                color: ColorAdjust {
+                    brightness: self.brightness,
+                    contrast: self.contrast,
+                    saturation: self.saturation,
+                    gamma: self.gamma,
+                },
+                lut: self.lut.clone(),
+            },
+            overlay: Overlay {
+                image: self.watermark.clone(),
+                text: self.text_overlay.clone(),
+                font: self.text_font.clone(),
+                position: self.watermark_position.into(),
+                margin: self.watermark_margin,
+                opacity: self.watermark_opacity,
+            },
+            gif_dither: self.gif_dither.into(),
+            split: self
+                .split_size
+                .map(SplitMode::Size)
+                .or(self.split_duration.map(SplitMode::Duration)),
             overwrite: self.overwrite,
             verbose: self.verbose,
             json: self.json,
@@ -258,6 +1173,70 @@ mod tests {
         assert!(parse_crop("").is_err());
     }
 
+    #[test]
+    fn test_parse_crop_arg_auto() {
+        assert!(matches!(parse_crop_arg("auto"), Ok(CropArg::Auto)));
+        assert!(matches!(parse_crop_arg("AUTO"), Ok(CropArg::Auto)));
+        assert!(matches!(
+            parse_crop_arg("640x360:0:60"),
+            Ok(CropArg::Manual(_))
+        ));
+        assert!(parse_crop_arg("autox").is_err());
+    }
+
+    #[test]
+    fn test_parse_aspect_ratio() {
+        assert_eq!(parse_aspect_ratio("9:16"), Ok((9, 16)));
+        assert_eq!(parse_aspect_ratio(" 1:1 "), Ok((1, 1)));
+        assert!(parse_aspect_ratio("16x9").is_err());
+        assert!(parse_aspect_ratio("0:1").is_err());
+    }
+
+    // ---- colour adjustments ---------------------------------------------------
+
+    #[test]
+    fn test_parse_color_values() {
+        assert_eq!(parse_brightness("-0.1"), Ok(-0.1));
+        assert_eq!(parse_contrast("1.25"), Ok(1.25));
+        assert_eq!(parse_saturation("0"), Ok(0.0));
+        assert_eq!(parse_gamma(" 2.2 "), Ok(2.2));
+        assert!(parse_brightness("1.5").is_err());
+        assert!(parse_contrast("-1").is_err());
+        assert!(parse_gamma("0").is_err());
+        assert!(parse_saturation("vivid").is_err());
+    }
+
+    #[test]
+    fn test_parse_volume_and_fade() {
+        assert_eq!(parse_volume("-3"), Ok(-3.0));
+        assert_eq!(parse_volume("+6"), Ok(6.0));
+        assert_eq!(parse_volume("4.5dB"), Ok(4.5));
+        assert!(parse_volume("loud").is_err());
+        assert!(parse_volume("-90").is_err());
+        assert_eq!(parse_fade("1.5"), Ok(1.5));
+        assert_eq!(parse_fade("00:02"), Ok(2.0));
+        assert!(parse_fade("0").is_err());
+    }
+
+    #[test]
+    fn test_parse_grid() {
+        assert_eq!(parse_grid("4x4"), Ok((4, 4)));
+        assert_eq!(parse_grid("5X3"), Ok((5, 3)));
+        assert!(parse_grid("4").is_err());
+        assert!(parse_grid("0x4").is_err());
+        assert!(parse_grid("30x2").is_err());
+    }
+
+    #[test]
+    fn test_parse_speed() {
+        assert_eq!(parse_speed("2"), Ok(2.0));
+        assert_eq!(parse_speed("8x"), Ok(8.0));
+        assert_eq!(parse_speed("0.5X"), Ok(0.5));
+        assert!(parse_speed("0").is_err());
+        assert!(parse_speed("500").is_err());
+        assert!(parse_speed("fast").is_err());
+    }
+
     // ---- parse_rotation -------------------------------------------------------
 
     #[test]
@@ -272,4 +1251,340 @@ mod tests {
         assert!(parse_rotation("45").is_err());
         assert!(parse_rotation("abc").is_err());
     }
+
+    // ---- parse_size -----------------------------------------------------------
+
+    #[test]
+    fn test_parse_size_units() {
+        assert_eq!(parse_size("25MB"), Ok(25 * 1024 * 1024));
+        assert_eq!(parse_size("8M"), Ok(8 * 1024 * 1024));
+        assert_eq!(parse_size("500kb"), Ok(500 * 1024));
+        assert_eq!(parse_size("1.5G"), Ok(1536 * 1024 * 1024));
+        assert_eq!(parse_size("4096"), Ok(4096));
+    }
+
+    #[test]
+    fn test_parse_size_invalid() {
+        assert!(parse_size("").is_err());
+        assert!(parse_size("MB").is_err());
+        assert!(parse_size("10TB").is_err());
+        assert!(parse_size("0MB").is_err());
+    }
+
+    // ---- --target-quality -----------------------------------------------------
+
+    #[test]
+    fn test_target_quality_metric_inference() {
+        use crate::domain::QualityMetric;
+
+        let vmaf: QualityTarget = "93".parse().unwrap();
+        assert_eq!((vmaf.metric, vmaf.score), (QualityMetric::Vmaf, 93.0));
+        let ssim: QualityTarget = "0.98".parse().unwrap();
+        assert_eq!((ssim.metric, ssim.score), (QualityMetric::Ssim, 0.98));
+        let explicit: QualityTarget = "VMAF:0.5".parse().unwrap();
+        assert_eq!(explicit.metric, QualityMetric::Vmaf);
+    }
+
+    #[test]
+    fn test_target_quality_invalid() {
+        assert!("ssim:1.5".parse::<QualityTarget>().is_err());
+        assert!("101".parse::<QualityTarget>().is_err());
+        assert!("psnr:40".parse::<QualityTarget>().is_err());
+        assert!("high".parse::<QualityTarget>().is_err());
+    }
+
+    #[test]
+    fn test_target_quality_conflicts_with_quality() {
+        assert!(Cli::try_parse_from(["compresso", "in.mp4", "--target-quality", "93"]).is_ok());
+        assert!(
+            Cli::try_parse_from(["compresso", "in.mp4", "-q", "80", "--target-quality", "93"])
+                .is_err()
+        );
+    }
+
+    // ---- audio ----------------------------------------------------------------
+
+    #[test]
+    fn test_parse_audio_bitrate() {
+        assert_eq!(parse_audio_bitrate("96k"), Ok(96));
+        assert_eq!(parse_audio_bitrate("128K"), Ok(128));
+        assert_eq!(parse_audio_bitrate("64"), Ok(64));
+        assert!(parse_audio_bitrate("fast").is_err());
+        assert!(parse_audio_bitrate("2000k").is_err());
+    }
+
+    #[test]
+    fn test_audio_options_conflict_with_mute() {
+        assert!(
+            Cli::try_parse_from(["compresso", "in.mp4", "--mute", "--audio-codec", "opus"])
+                .is_err()
+        );
+    }
+
+    // ---- --remux --------------------------------------------------------------
+
+    fn remux_mode_for(args: &[&str]) -> RemuxMode {
+        Cli::try_parse_from(args).unwrap().to_config().remux
+    }
+
+    #[test]
+    fn test_remux_mode_selection() {
+        assert_eq!(
+            remux_mode_for(&["compresso", "in.mkv", "-f", "mp4"]),
+            RemuxMode::Auto
+        );
+        assert_eq!(
+            remux_mode_for(&["compresso", "in.mkv", "--remux", "-f", "mp4"]),
+            RemuxMode::Always
+        );
+        // An explicit quality means the user wants a re-encode.
+        assert_eq!(
+            remux_mode_for(&["compresso", "in.mkv", "-f", "mp4", "-q", "70"]),
+            RemuxMode::Never
+        );
+        assert!(Cli::try_parse_from(["compresso", "in.mkv", "--remux", "-q", "70"]).is_err());
+    }
+
+    // ---- trimming -------------------------------------------------------------
+
+    #[test]
+    fn test_parse_timestamp() {
+        assert_eq!(parse_timestamp("90.5"), Ok(90.5));
+        assert_eq!(parse_timestamp("01:30"), Ok(90.0));
+        assert_eq!(parse_timestamp("00:01:30.250"), Ok(90.25));
+        assert_eq!(parse_timestamp("1:00:00"), Ok(3600.0));
+        assert!(parse_timestamp("00:75:00").is_err());
+        assert!(parse_timestamp("1.5:00").is_err());
+        assert!(parse_timestamp("-5").is_err());
+        assert!(parse_timestamp("1:2:3:4").is_err());
+        assert!(parse_timestamp("abc").is_err());
+    }
+
+    #[test]
+    fn test_thumbnail_request_single_input() {
+        let parse = |args: &[&str]| Cli::try_parse_from(args);
+        let cli = parse(&["compresso", "in.mp4", "--thumbnails", "5"]).unwrap();
+        assert_eq!(
+            cli.thumbnail_request().unwrap().unwrap().input_path,
+            "in.mp4"
+        );
+        assert!(parse(&["compresso", "in.mp4"])
+            .unwrap()
+            .thumbnail_request()
+            .is_none());
+
+        let cli = parse(&["compresso", "a.mp4", "b.mp4", "--poster", "5"]).unwrap();
+        assert!(matches!(
+            cli.thumbnail_request(),
+            Some(Err(CompressoError::IncompatibleOptions(_)))
+        ));
+        // Compression-only options are refused instead of ignored.
+        assert!(parse(&["compresso", "--dir", "clips", "--thumbnails", "5"]).is_err());
+        for option in [
+            &["--start", "10"][..],
+            &["--end", "20"],
+            &["--duration", "5"],
+            &["--codec", "hevc"],
+            &["-q", "50"],
+            &["--width", "640"],
+            &["--speed", "2"],
+            &["--format", "webm"],
+            &["--crop", "100:100:0:0"],
+            &["--watermark", "logo.png"],
+        ] {
+            let mut args = vec!["compresso", "in.mp4", "--poster", "5"];
+            args.extend_from_slice(option);
+            assert_eq!(
+                parse(&args).unwrap_err().kind(),
+                clap::error::ErrorKind::ArgumentConflict,
+                "{:?}",
+                option
+            );
+        }
+        // Thumbnail settings and general flags are still fine.
+        assert!(parse(&[
+            "compresso",
+            "in.mp4",
+            "--thumbnails",
+            "5",
+            "--thumbnail-width",
+            "160",
+            "-y",
+            "--json"
+        ])
+        .is_ok());
+        assert!(parse(&[
+            "compresso",
+            "a.mp4",
+            "b.mp4",
+            "--concat",
+            "--contact-sheet",
+            "4x4"
+        ])
+        .is_err());
+        assert!(parse(&[
+            "compresso",
+            "in.mp4",
+            "--split-size",
+            "8MB",
+            "--poster",
+            "5"
+        ])
+        .is_err());
+    }
+
+    #[test]
+    fn test_split_from_args() {
+        let split = |args: &[&str]| Cli::try_parse_from(args).map(|cli| cli.to_config().split);
+        assert_eq!(split(&["compresso", "in.mp4"]).unwrap(), None);
+        assert_eq!(
+            split(&["compresso", "in.mp4", "--split-size", "25MB"]).unwrap(),
+            Some(SplitMode::Size(25 * 1024 * 1024))
+        );
+        assert_eq!(
+            split(&["compresso", "in.mp4", "--split-duration", "10:00"]).unwrap(),
+            Some(SplitMode::Duration(600.0))
+        );
+        assert!(split(&["compresso", "in.mp4", "--split-duration", "0.5"]).is_err());
+        assert!(split(&[
+            "compresso",
+            "in.mp4",
+            "--split-size",
+            "8MB",
+            "--split-duration",
+            "60"
+        ])
+        .is_err());
+    }
+
+    #[test]
+    fn test_trim_range_from_args() {
+        let trim = |args: &[&str]| Cli::try_parse_from(args).unwrap().to_config().trim;
+        assert_eq!(trim(&["compresso", "in.mp4"]), None);
+        assert_eq!(
+            trim(&["compresso", "in.mp4", "--start", "10", "--duration", "5"]),
+            Some(TrimRange {
+                start: 10.0,
+                end: Some(15.0)
+            })
+        );
+        assert_eq!(
+            trim(&["compresso", "in.mp4", "--end", "00:01:00"]),
+            Some(TrimRange {
+                start: 0.0,
+                end: Some(60.0)
+            })
+        );
+        assert!(
+            Cli::try_parse_from(["compresso", "in.mp4", "--end", "20", "--duration", "5"]).is_err()
+        );
+    }
+
+    // ---- --max-resolution -----------------------------------------------------
+
+    #[test]
+    fn test_max_resolution_parsing() {
+        let parse = |s: &str| s.parse::<MaxResolution>();
+        let hd = MaxResolution {
+            long: 1280,
+            short: 720,
+        };
+        assert_eq!(parse("1280x720"), Ok(hd));
+        // Orientation does not matter.
+        assert_eq!(parse("720x1280"), Ok(hd));
+        assert_eq!(parse("720p"), Ok(hd));
+        assert_eq!(
+            parse("480p"),
+            Ok(MaxResolution {
+                long: 854,
+                short: 480
+            })
+        );
+        assert_eq!(
+            parse("4K"),
+            Ok(MaxResolution {
+                long: 3840,
+                short: 2160
+            })
+        );
+        assert!(parse("720").is_err());
+        assert!(parse("8x8").is_err());
+
+        let cli = Cli::try_parse_from(["compresso", "in.mp4", "--fit", "1080p"]).unwrap();
+        assert_eq!(cli.to_config().max_resolution.map(|r| r.long), Some(1920));
+        assert!(
+            Cli::try_parse_from(["compresso", "in.mp4", "--fit", "720p", "--width", "640"])
+                .is_err()
+        );
+    }
+
+    // ---- track selection ------------------------------------------------------
+
+    #[test]
+    fn test_parse_audio_tracks() {
+        assert_eq!(parse_audio_tracks("all"), Ok(TrackSelection::All));
+        assert_eq!(
+            parse_audio_tracks("1, 3"),
+            Ok(TrackSelection::Numbers(vec![1, 3]))
+        );
+        assert_eq!(
+            parse_audio_tracks("lang:ENG,rus"),
+            Ok(TrackSelection::Languages(vec![
+                "eng".to_string(),
+                "rus".to_string()
+            ]))
+        );
+        assert!(parse_audio_tracks("0").is_err());
+        assert!(parse_audio_tracks("lang:").is_err());
+        assert!(parse_audio_tracks("first").is_err());
+    }
+
+    #[test]
+    fn test_parse_subtitles() {
+        assert_eq!(parse_subtitles("keep"), Ok(TrackSelection::All));
+        assert_eq!(parse_subtitles("drop"), Ok(TrackSelection::None));
+        assert_eq!(
+            parse_subtitles("lang:rus"),
+            Ok(TrackSelection::Languages(vec!["rus".to_string()]))
+        );
+        assert!(parse_subtitles("1").is_err());
+    }
+
+    #[test]
+    fn test_burn_subtitles_options() {
+        let cli =
+            Cli::try_parse_from(["compresso", "in.mkv", "--burn-subtitles", "subs.srt"]).unwrap();
+        assert_eq!(
+            cli.to_config().burn_subtitles,
+            Some(BurnSubtitles::File("subs.srt".to_string()))
+        );
+
+        let cli =
+            Cli::try_parse_from(["compresso", "in.mkv", "--burn-subtitle-track", "2"]).unwrap();
+        assert_eq!(
+            cli.to_config().burn_subtitles,
+            Some(BurnSubtitles::Track(2))
+        );
+
+        assert!(
+            Cli::try_parse_from(["compresso", "in.mkv", "--burn-subtitle-track", "0"]).is_err()
+        );
+        assert!(Cli::try_parse_from([
+            "compresso",
+            "in.mkv",
+            "--burn-subtitles",
+            "subs.srt",
+            "--burn-subtitle-track",
+            "1"
+        ])
+        .is_err());
+        assert!(Cli::try_parse_from([
+            "compresso",
+            "in.mkv",
+            "--remux",
+            "--burn-subtitles",
+            "subs.srt"
+        ])
+        .is_err());
+    }
 }
@@ -1,12 +1,146 @@
 use serde::{Deserialize, Serialize};
 
 /// Result of a successful video compression
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct CompressionResult {
     pub file_name: String,
     pub file_path: String,
     pub original_size: u64,
     pub compressed_size: u64,
+    /// Requested `--target-size` in bytes, if any
+    pub target_size: Option<u64>,
+    /// Whether the output fits the target size (None without `--target-size`)
+    pub target_met: Option<bool>,
+    /// Scores from `--measure-quality`
+    pub quality_metrics: Option<QualityMetrics>,
+    /// CRF picked by `--target-quality`
+    pub crf_search: Option<CrfSearch>,
+    /// Streams were copied into the new container instead of re-encoded
+    pub remuxed: bool,
+    /// How an HDR source was encoded; None for SDR
+    pub hdr: Option<HdrMode>,
+    /// Files written by `--split-size` / `--split-duration`, in order
+    /// (`file_path` is then the first of them)
+    pub parts: Vec<OutputPart>,
+}
+
+/// One file of an output cut into parts
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OutputPart {
+    pub path: String,
+    pub size: u64,
+}
+
+/// Perceptual quality of an encode compared with its source
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct QualityMetrics {
+    /// Structural similarity, 0-1 (1 = identical)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ssim: Option<f64>,
+    /// Peak signal-to-noise ratio in dB (None when the frames are identical)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub psnr: Option<f64>,
+    /// VMAF, 0-100 (only with an FFmpeg build that has libvmaf)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub vmaf: Option<f64>,
+}
+
+/// Metric used by `--target-quality`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum QualityMetric {
+    Ssim,
+    Vmaf,
+}
+
+impl QualityMetric {
+    pub fn name(&self) -> &'static str {
+        match self {
+            QualityMetric::Ssim => "SSIM",
+            QualityMetric::Vmaf => "VMAF",
+        }
+    }
+
+    /// Pick this metric's score out of a measurement
+    pub fn score(&self, metrics: &QualityMetrics) -> Option<f64> {
+        match self {
+            QualityMetric::Ssim => metrics.ssim,
+            QualityMetric::Vmaf => metrics.vmaf,
+        }
+    }
+}
+
+impl std::fmt::Display for QualityMetric {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+/// Perceptual quality an encode has to reach (`--target-quality`)
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct QualityTarget {
+    pub metric: QualityMetric,
+    pub score: f64,
+}
+
+impl std::str::FromStr for QualityTarget {
+    type Err = String;
+
+    /// Accepts `vmaf:93`, `ssim:0.98`, or a bare score: values up to 1 are
+    /// SSIM, larger ones VMAF.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim().to_lowercase();
+        let (metric, value) = match s.split_once(':') {
+            Some(("ssim", value)) => (Some(QualityMetric::Ssim), value),
+            Some(("vmaf", value)) => (Some(QualityMetric::Vmaf), value),
+            Some((other, _)) => {
+                return Err(format!(
+                    "Unknown quality metric: {}. Supported: ssim, vmaf",
+                    other
+                ))
+            }
+            None => (None, s.as_str()),
+        };
+        let score: f64 = value
+            .parse()
+            .map_err(|_| format!("Invalid quality score: {} (e.g. 93, vmaf:93, 0.98)", value))?;
+        let metric = metric.unwrap_or(if score <= 1.0 {
+            QualityMetric::Ssim
+        } else {
+            QualityMetric::Vmaf
+        });
+
+        let valid = match metric {
+            QualityMetric::Ssim => score > 0.0 && score <= 1.0,
+            QualityMetric::Vmaf => score > 0.0 && score <= 100.0,
+        };
+        if !valid {
+            return Err(format!(
+                "{} score out of range: {} (SSIM: 0-1, VMAF: 0-100)",
+                metric, score
+            ));
+        }
+        Ok(QualityTarget { metric, score })
+    }
+}
+
+impl std::fmt::Display for QualityTarget {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} {}", self.metric, self.score)
+    }
+}
+
+/// Outcome of the `--target-quality` CRF search
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct CrfSearch {
+    pub metric: QualityMetric,
+    pub target: f64,
+    /// CRF used for the full encode
+    pub crf: u16,
+    /// Mean score of the sample segments at that CRF
+    pub score: f64,
+    /// False when even the best CRF in range missed the target
+    pub target_met: bool,
 }
 
 /// File metadata information
@@ -19,13 +153,160 @@ pub struct FileMetadata {
     pub size: u64,
 }
 
-/// Video information extracted from FFmpeg
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// Video information extracted with ffprobe
+///
+/// The top-level fields describe the main video stream (the first one that
+/// is not cover art) and are what the encoder works with; `streams` lists
+/// everything in the file.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct VideoInfo {
     pub duration: Option<String>,
     pub duration_seconds: Option<f64>,
+    /// Displayed size, i.e. already swapped for 90°/270° rotated videos
     pub dimensions: Option<(u32, u32)>,
     pub fps: Option<f32>,
+    /// Codec of the main video stream, as FFmpeg names it (`h264`, `vp9`, ...)
+    pub video_codec: Option<String>,
+    /// Codec of the first audio stream (`aac`, `opus`, ...); None without audio
+    pub audio_codec: Option<String>,
+    /// Container as ffprobe names it (`mov,mp4,m4a,3gp,3g2,mj2`, `matroska,webm`)
+    pub container: Option<String>,
+    /// Overall bitrate in bit/s
+    pub bitrate: Option<u64>,
+    /// Recording date from the container (`creation_time` tag)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub creation_time: Option<String>,
+    #[serde(default)]
+    pub streams: Vec<StreamInfo>,
+    /// With `--concat`: every input in order, while the fields above
+    /// describe the first one with the summed duration
+    #[serde(skip)]
+    pub parts: Vec<VideoInfo>,
+}
+
+impl VideoInfo {
+    /// The stream the encoder works with: the first video stream that is
+    /// not cover art
+    pub fn main_video(&self) -> Option<&StreamInfo> {
+        self.streams
+            .iter()
+            .find(|s| s.kind == StreamKind::Video && !s.attached_pic)
+    }
+
+    /// `HDR10` (PQ) or `HLG` when the main video uses an HDR transfer
+    pub fn hdr_format(&self) -> Option<&'static str> {
+        match self.main_video()?.color_transfer.as_deref()? {
+            "smpte2084" => Some("HDR10"),
+            "arib-std-b67" => Some("HLG"),
+            _ => None,
+        }
+    }
+}
+
+/// Kind of a stream inside a container
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum StreamKind {
+    Video,
+    Audio,
+    Subtitle,
+    Data,
+    Attachment,
+}
+
+impl StreamKind {
+    pub fn name(&self) -> &'static str {
+        match self {
+            StreamKind::Video => "video",
+            StreamKind::Audio => "audio",
+            StreamKind::Subtitle => "subtitle",
+            StreamKind::Data => "data",
+            StreamKind::Attachment => "attachment",
+        }
+    }
+}
+
+impl std::fmt::Display for StreamKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+/// One stream as reported by ffprobe; fields that do not apply to the
+/// stream kind stay None
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StreamInfo {
+    /// Index in the container (what `-map 0:N` refers to)
+    pub index: u32,
+    #[serde(rename = "type")]
+    pub kind: StreamKind,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub codec: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub profile: Option<String>,
+    /// Bitrate in bit/s
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bitrate: Option<u64>,
+    /// ISO 639 language tag (`eng`, `rus`, ...)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub language: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    /// Marked as the default track of its kind
+    #[serde(default)]
+    pub default: bool,
+
+    // Video
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub width: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub height: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fps: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pix_fmt: Option<String>,
+    /// Shape of a pixel (`1:1` square, `4:3` for anamorphic DV, ...)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sample_aspect_ratio: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub color_range: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub color_space: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub color_transfer: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub color_primaries: Option<String>,
+    /// Display rotation in degrees from the stream's side data or tags
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rotation: Option<i32>,
+    /// HDR10 mastering display as x265 writes it:
+    /// `G(x,y)B(x,y)R(x,y)WP(x,y)L(max,min)` in 0.00002 and 0.0001 cd/m² units
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mastering_display: Option<String>,
+    /// HDR10 content light level as `MaxCLL,MaxFALL` in cd/m²
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_cll: Option<String>,
+    /// Embedded cover art rather than real video
+    #[serde(default)]
+    pub attached_pic: bool,
+
+    // Audio
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub channels: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub channel_layout: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sample_rate: Option<u32>,
+}
+
+impl StreamInfo {
+    /// Blu-ray/DVD subtitles, which are stored as pictures rather than text
+    pub fn is_image_subtitle(&self) -> bool {
+        matches!(
+            self.codec.as_deref(),
+            Some("hdmv_pgs_subtitle" | "dvd_subtitle" | "dvb_subtitle" | "xsub")
+        )
+    }
 }
 
 /// Crop coordinates for video
@@ -37,6 +318,25 @@ pub struct CropCoordinates {
     pub y: u32,
 }
 
+/// Crop worked out for each file from the video itself; resolved into
+/// `VideoTransforms::crop` before encoding
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AutoCrop {
+    /// Remove the black bars found by FFmpeg's cropdetect
+    Detect,
+    /// Largest centred crop with this aspect ratio (width, height)
+    Aspect(u32, u32),
+}
+
+impl std::fmt::Display for AutoCrop {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AutoCrop::Detect => write!(f, "auto"),
+            AutoCrop::Aspect(w, h) => write!(f, "{}:{}", w, h),
+        }
+    }
+}
+
 /// Flip options for video
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
 pub struct FlipOptions {
@@ -52,6 +352,135 @@ pub struct VideoTransforms {
     pub flip: Option<FlipOptions>,
 }
 
+/// `--denoise` strength
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Denoise {
+    Light,
+    Medium,
+    /// Non-local means: much slower, for heavy grain
+    Strong,
+}
+
+impl std::fmt::Display for Denoise {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Denoise::Light => write!(f, "light"),
+            Denoise::Medium => write!(f, "medium"),
+            Denoise::Strong => write!(f, "strong"),
+        }
+    }
+}
+
+/// `--deinterlace` mode
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Deinterlace {
+    /// Check a sample of the video and deinterlace if it is interlaced;
+    /// resolved to `On` or `Off` before encoding
+    Auto,
+    On,
+    #[default]
+    Off,
+}
+
+/// `--brightness`, `--contrast`, `--saturation` and `--gamma`; None leaves
+/// that value neutral
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct ColorAdjust {
+    pub brightness: Option<f32>,
+    pub contrast: Option<f32>,
+    pub saturation: Option<f32>,
+    pub gamma: Option<f32>,
+}
+
+impl ColorAdjust {
+    pub fn is_active(&self) -> bool {
+        self.brightness.is_some()
+            || self.contrast.is_some()
+            || self.saturation.is_some()
+            || self.gamma.is_some()
+    }
+}
+
+/// Cleanup and colour filters, applied in this order around the transforms:
+/// deinterlace, transforms, denoise, scaling, LUT, colour adjustment, sharpen
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct VideoFilters {
+    pub denoise: Option<Denoise>,
+    pub sharpen: bool,
+    pub deinterlace: Deinterlace,
+    pub color: ColorAdjust,
+    /// 3D LUT file (`.cube`, `.3dl`, ...)
+    pub lut: Option<String>,
+}
+
+impl VideoFilters {
+    /// Whether any filter will run (an unresolved `Auto` does not count)
+    pub fn is_active(&self) -> bool {
+        self.denoise.is_some()
+            || self.sharpen
+            || self.deinterlace == Deinterlace::On
+            || self.color.is_active()
+            || self.lut.is_some()
+    }
+}
+
+/// Where `--watermark` and `--text-overlay` are placed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OverlayPosition {
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    #[default]
+    BottomRight,
+    Center,
+}
+
+impl std::fmt::Display for OverlayPosition {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            OverlayPosition::TopLeft => write!(f, "top left"),
+            OverlayPosition::TopRight => write!(f, "top right"),
+            OverlayPosition::BottomLeft => write!(f, "bottom left"),
+            OverlayPosition::BottomRight => write!(f, "bottom right"),
+            OverlayPosition::Center => write!(f, "center"),
+        }
+    }
+}
+
+/// Image watermark and/or text drawn over the finished picture; both share
+/// the placement settings
+#[derive(Debug, Clone, PartialEq)]
+pub struct Overlay {
+    pub image: Option<String>,
+    pub text: Option<String>,
+    /// Font file for the text (otherwise fontconfig's default font)
+    pub font: Option<String>,
+    pub position: OverlayPosition,
+    /// Distance from the frame edges in pixels
+    pub margin: u32,
+    /// 0.0 (invisible) to 1.0 (opaque)
+    pub opacity: f32,
+}
+
+impl Default for Overlay {
+    fn default() -> Self {
+        Self {
+            image: None,
+            text: None,
+            font: None,
+            position: OverlayPosition::default(),
+            margin: 20,
+            opacity: 1.0,
+        }
+    }
+}
+
+impl Overlay {
+    pub fn is_active(&self) -> bool {
+        self.image.is_some() || self.text.is_some()
+    }
+}
+
 /// Compression preset
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
 pub enum Preset {
@@ -94,6 +523,10 @@ pub enum OutputFormat {
     Webm,
     Avi,
     Mkv,
+    /// Animated GIF (no audio)
+    Gif,
+    /// Animated WebP (no audio)
+    Webp,
 }
 
 impl OutputFormat {
@@ -104,9 +537,16 @@ impl OutputFormat {
             OutputFormat::Webm => "webm",
             OutputFormat::Avi => "avi",
             OutputFormat::Mkv => "mkv",
+            OutputFormat::Gif => "gif",
+            OutputFormat::Webp => "webp",
         }
     }
 
+    /// Animated image outputs: short silent previews rather than videos
+    pub fn is_animation(&self) -> bool {
+        matches!(self, OutputFormat::Gif | OutputFormat::Webp)
+    }
+
     pub fn from_extension(ext: &str) -> Option<Self> {
         match ext.to_lowercase().as_str() {
             "mp4" => Some(OutputFormat::Mp4),
@@ -114,6 +554,8 @@ impl OutputFormat {
             "webm" => Some(OutputFormat::Webm),
             "avi" => Some(OutputFormat::Avi),
             "mkv" => Some(OutputFormat::Mkv),
+            "gif" => Some(OutputFormat::Gif),
+            "webp" => Some(OutputFormat::Webp),
             _ => None,
         }
     }
@@ -123,8 +565,12 @@ impl std::str::FromStr for OutputFormat {
     type Err = String;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        Self::from_extension(s)
-            .ok_or_else(|| format!("Unknown format: {}. Supported: mp4, mov, webm, avi, mkv", s))
+        Self::from_extension(s).ok_or_else(|| {
+            format!(
+                "Unknown format: {}. Supported: mp4, mov, webm, avi, mkv, gif, webp",
+                s
+            )
+        })
     }
 }
 
@@ -134,19 +580,561 @@ impl std::fmt::Display for OutputFormat {
     }
 }
 
+/// Dithering used when reducing a GIF to its 256-colour palette
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum GifDither {
+    /// Ordered pattern: a little coarser, but compresses best
+    Bayer,
+    /// Error diffusion: smooth gradients, larger files
+    FloydSteinberg,
+    /// Lighter error diffusion (FFmpeg's default)
+    #[default]
+    Sierra,
+    /// Flat colour bands, smallest files
+    None,
+}
+
+impl GifDither {
+    /// Value for paletteuse's `dither` option
+    pub fn filter_value(&self) -> &'static str {
+        match self {
+            GifDither::Bayer => "bayer:bayer_scale=3",
+            GifDither::FloydSteinberg => "floyd_steinberg",
+            GifDither::Sierra => "sierra2_4a",
+            GifDither::None => "none",
+        }
+    }
+}
+
+impl std::fmt::Display for GifDither {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            GifDither::Bayer => write!(f, "bayer"),
+            GifDither::FloydSteinberg => write!(f, "floyd-steinberg"),
+            GifDither::Sierra => write!(f, "sierra"),
+            GifDither::None => write!(f, "none"),
+        }
+    }
+}
+
+/// Video codec used for the output stream
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VideoCodec {
+    /// H.264 / AVC (libx264)
+    H264,
+    /// H.265 / HEVC (libx265)
+    Hevc,
+    /// VP9 (libvpx-vp9)
+    Vp9,
+    /// AV1 (libsvtav1, falling back to libaom-av1)
+    Av1,
+}
+
+impl VideoCodec {
+    pub fn name(&self) -> &'static str {
+        match self {
+            VideoCodec::H264 => "h264",
+            VideoCodec::Hevc => "hevc",
+            VideoCodec::Vp9 => "vp9",
+            VideoCodec::Av1 => "av1",
+        }
+    }
+
+    /// Codec used when none is requested explicitly: VP9 for WebM and H.264
+    /// for every other container.
+    pub fn default_for_format(format: &str) -> Self {
+        match format {
+            "webm" => VideoCodec::Vp9,
+            _ => VideoCodec::H264,
+        }
+    }
+
+    /// Whether this codec can be stored in the given container (by extension).
+    pub fn supports_format(&self, format: &str) -> bool {
+        match self {
+            // H.264 works everywhere except WebM, which only allows VP8/VP9/AV1,
+            // and the animated image formats, which have their own encoders.
+            VideoCodec::H264 => !matches!(format, "webm" | "gif" | "webp"),
+            VideoCodec::Hevc => matches!(format, "mp4" | "mov" | "m4v" | "mkv"),
+            VideoCodec::Vp9 | VideoCodec::Av1 => matches!(format, "webm" | "mkv" | "mp4"),
+        }
+    }
+}
+
+impl std::str::FromStr for VideoCodec {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "h264" | "avc" | "x264" => Ok(VideoCodec::H264),
+            "hevc" | "h265" | "x265" => Ok(VideoCodec::Hevc),
+            "vp9" => Ok(VideoCodec::Vp9),
+            "av1" => Ok(VideoCodec::Av1),
+            _ => Err(format!(
+                "Unknown codec: {}. Supported: h264, hevc, vp9, av1",
+                s
+            )),
+        }
+    }
+}
+
+impl std::fmt::Display for VideoCodec {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+/// Audio codec used for the output stream
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AudioCodec {
+    /// AAC (FFmpeg's native encoder)
+    Aac,
+    /// Opus (libopus)
+    Opus,
+    /// MP3 (libmp3lame)
+    Mp3,
+    /// Keep the source audio stream as-is
+    Copy,
+}
+
+impl AudioCodec {
+    pub fn name(&self) -> &'static str {
+        match self {
+            AudioCodec::Aac => "aac",
+            AudioCodec::Opus => "opus",
+            AudioCodec::Mp3 => "mp3",
+            AudioCodec::Copy => "copy",
+        }
+    }
+
+    /// FFmpeg encoder name (None for stream copy)
+    pub fn encoder(&self) -> Option<&'static str> {
+        match self {
+            AudioCodec::Aac => Some("aac"),
+            AudioCodec::Opus => Some("libopus"),
+            AudioCodec::Mp3 => Some("libmp3lame"),
+            AudioCodec::Copy => None,
+        }
+    }
+
+    /// Codec used when none is requested explicitly: Opus for WebM, MP3 for
+    /// AVI and WMV, AAC for everything else.
+    pub fn default_for_format(format: &str) -> Self {
+        match format {
+            "webm" => AudioCodec::Opus,
+            "avi" | "wmv" => AudioCodec::Mp3,
+            _ => AudioCodec::Aac,
+        }
+    }
+
+    /// Bitrate (kbit/s) used when `--audio-bitrate` is not given
+    pub fn default_bitrate_kbps(&self) -> u32 {
+        match self {
+            AudioCodec::Opus => 96,
+            _ => 128,
+        }
+    }
+
+    /// Whether this codec can be stored in the given container (by extension).
+    /// Stream copy is always allowed; FFmpeg reports a mismatch itself.
+    pub fn supports_format(&self, format: &str) -> bool {
+        match self {
+            AudioCodec::Aac => !matches!(format, "webm" | "wmv"),
+            AudioCodec::Opus => matches!(format, "webm" | "mkv" | "mp4"),
+            AudioCodec::Mp3 => format != "webm",
+            AudioCodec::Copy => true,
+        }
+    }
+}
+
+impl std::str::FromStr for AudioCodec {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "aac" => Ok(AudioCodec::Aac),
+            "opus" | "libopus" => Ok(AudioCodec::Opus),
+            "mp3" | "libmp3lame" => Ok(AudioCodec::Mp3),
+            "copy" => Ok(AudioCodec::Copy),
+            _ => Err(format!(
+                "Unknown audio codec: {}. Supported: aac, opus, mp3, copy",
+                s
+            )),
+        }
+    }
+}
+
+impl std::fmt::Display for AudioCodec {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+/// Audio encoding options; every field left at None keeps the default
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct AudioSettings {
+    /// Audio codec (None = container default)
+    pub codec: Option<AudioCodec>,
+    /// Bitrate in kbit/s (None = codec default)
+    pub bitrate_kbps: Option<u32>,
+    /// Channel count, e.g. 1 to downmix to mono
+    pub channels: Option<u8>,
+    /// Sample rate in Hz
+    pub sample_rate: Option<u32>,
+}
+
+impl AudioSettings {
+    pub fn is_default(&self) -> bool {
+        *self == AudioSettings::default()
+    }
+}
+
+impl std::fmt::Display for AudioSettings {
+    /// Compact summary such as `opus 96k mono 48000 Hz`
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut parts: Vec<String> = Vec::new();
+        if let Some(codec) = self.codec {
+            parts.push(codec.to_string());
+        }
+        if let Some(kbps) = self.bitrate_kbps {
+            parts.push(format!("{}k", kbps));
+        }
+        match self.channels {
+            Some(1) => parts.push("mono".to_string()),
+            Some(2) => parts.push("stereo".to_string()),
+            Some(n) => parts.push(format!("{}ch", n)),
+            None => {}
+        }
+        if let Some(rate) = self.sample_rate {
+            parts.push(format!("{} Hz", rate));
+        }
+        write!(f, "{}", parts.join(" "))
+    }
+}
+
+/// loudnorm's first-pass measurement of the input, fed back to the second
+/// pass so it can apply one linear gain instead of compressing dynamically
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LoudnessStats {
+    /// Integrated loudness in LUFS
+    pub input_i: f64,
+    /// True peak in dBTP
+    pub input_tp: f64,
+    /// Loudness range in LU
+    pub input_lra: f64,
+    pub input_thresh: f64,
+    pub target_offset: f64,
+}
+
+/// Audio level filters, applied after the `--speed` change in this order:
+/// loudness normalization, volume, fades
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct AudioFilters {
+    /// EBU R128 loudness normalization
+    pub normalize: bool,
+    /// Measurement for `normalize` (None = one dynamic pass)
+    pub loudness: Option<LoudnessStats>,
+    /// Gain in dB
+    pub volume_db: Option<f32>,
+    /// Fade-in length in seconds, from the start of the output
+    pub fade_in: Option<f64>,
+    /// Fade-out length in seconds, up to the end of the output
+    pub fade_out: Option<f64>,
+}
+
+impl AudioFilters {
+    pub fn is_active(&self) -> bool {
+        self.normalize
+            || self.volume_db.is_some()
+            || self.fade_in.is_some()
+            || self.fade_out.is_some()
+    }
+}
+
+/// Bounding box for `--max-resolution` / `--fit`. It is orientation
+/// independent: `long` limits the long edge and `short` the short edge, so
+/// `720p` fits both landscape and vertical phone videos.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MaxResolution {
+    pub long: u32,
+    pub short: u32,
+}
+
+impl std::str::FromStr for MaxResolution {
+    type Err = String;
+
+    /// `1280x720` (either order) or a named size such as `720p`, `1080p`, `4k`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim().to_lowercase();
+        let invalid = || format!("Invalid resolution: {} (e.g. 1280x720, 720p, 1080p, 4k)", s);
+
+        let (a, b) = if let Some((w, h)) = s.split_once('x') {
+            let w: u32 = w.parse().map_err(|_| invalid())?;
+            let h: u32 = h.parse().map_err(|_| invalid())?;
+            (w, h)
+        } else {
+            let short: u32 = match s.as_str() {
+                "4k" => 2160,
+                "8k" => 4320,
+                _ => s
+                    .strip_suffix('p')
+                    .and_then(|n| n.parse().ok())
+                    .ok_or_else(invalid)?,
+            };
+            // 16:9 long edge, rounded to an even size (480p -> 854x480)
+            let long = ((short as f64 * 16.0 / 9.0 / 2.0).round() as u32) * 2;
+            (long, short)
+        };
+
+        if a < 16 || b < 16 {
+            return Err("Resolution must be at least 16x16".to_string());
+        }
+        Ok(MaxResolution {
+            long: a.max(b),
+            short: a.min(b),
+        })
+    }
+}
+
+impl std::fmt::Display for MaxResolution {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}x{}", self.long, self.short)
+    }
+}
+
+/// Section of the input to keep (`--start`, `--end`, `--duration`), in seconds
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct TrimRange {
+    pub start: f64,
+    /// None keeps everything after `start`
+    pub end: Option<f64>,
+}
+
+impl TrimRange {
+    /// Seconds kept, given the probed duration of the source; None when
+    /// neither `end` nor the source duration is known
+    pub fn length(&self, source_duration: Option<f64>) -> Option<f64> {
+        let end = match (self.end, source_duration) {
+            (Some(end), Some(total)) => end.min(total),
+            (Some(end), None) => end,
+            (None, Some(total)) => total,
+            (None, None) => return None,
+        };
+        Some(end - self.start)
+    }
+}
+
+impl std::fmt::Display for TrimRange {
+    /// `00:00:10.000 → 00:01:00.000`, or `00:00:10.000 → …` without an end
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.end {
+            Some(end) => write!(
+                f,
+                "{} → {}",
+                format_timestamp(self.start),
+                format_timestamp(end)
+            ),
+            None => write!(f, "{} → …", format_timestamp(self.start)),
+        }
+    }
+}
+
+/// Format seconds as `HH:MM:SS.mmm`
+pub fn format_timestamp(seconds: f64) -> String {
+    let millis = (seconds.max(0.0) * 1000.0).round() as u64;
+    format!(
+        "{:02}:{:02}:{:02}.{:03}",
+        millis / 3_600_000,
+        millis / 60_000 % 60,
+        millis / 1000 % 60,
+        millis % 1000
+    )
+}
+
+/// Format seconds as `HH:MM:SS`, dropping the fraction
+pub fn format_clock(seconds: f64) -> String {
+    let seconds = seconds.max(0.0).floor() as u64;
+    format!(
+        "{:02}:{:02}:{:02}",
+        seconds / 3600,
+        seconds / 60 % 60,
+        seconds % 60
+    )
+}
+
+/// Which audio (`--audio-tracks`) or subtitle (`--subtitles`) streams go
+/// into the output
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub enum TrackSelection {
+    /// FFmpeg's usual pick: the main audio track and no subtitles
+    #[default]
+    Default,
+    All,
+    None,
+    /// Track numbers counted from 1 within the stream kind
+    Numbers(Vec<u32>),
+    /// ISO 639 language tags (`eng`, `rus`, ...)
+    Languages(Vec<String>),
+}
+
+impl std::fmt::Display for TrackSelection {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TrackSelection::Default => write!(f, "default"),
+            TrackSelection::All => write!(f, "all"),
+            TrackSelection::None => write!(f, "none"),
+            TrackSelection::Numbers(numbers) => {
+                let numbers: Vec<String> = numbers.iter().map(|n| n.to_string()).collect();
+                write!(f, "{}", numbers.join(","))
+            }
+            TrackSelection::Languages(languages) => write!(f, "lang:{}", languages.join(",")),
+        }
+    }
+}
+
+/// Subtitles rendered into the picture
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BurnSubtitles {
+    /// External subtitle file (.srt, .ass, .ssa, .vtt)
+    File(String),
+    /// Subtitle track of the input, counted from 1
+    Track(u32),
+}
+
+impl std::fmt::Display for BurnSubtitles {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BurnSubtitles::File(path) => write!(f, "{}", path),
+            BurnSubtitles::Track(number) => write!(f, "#{}", number),
+        }
+    }
+}
+
+/// What happens to the source's metadata (creation date, GPS, camera, ...)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MetadataMode {
+    /// Copy all container and stream metadata
+    #[default]
+    Keep,
+    /// Drop everything, including stream languages and chapters
+    Strip,
+    /// Keep the creation date and stream languages/titles only
+    Minimal,
+}
+
+impl std::fmt::Display for MetadataMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MetadataMode::Keep => write!(f, "keep"),
+            MetadataMode::Strip => write!(f, "strip"),
+            MetadataMode::Minimal => write!(f, "minimal"),
+        }
+    }
+}
+
+/// What happens to HDR video
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum HdrMode {
+    /// Encode 10-bit with the source's HDR colour tags (HEVC, VP9, AV1)
+    Keep,
+    /// Convert to SDR BT.709
+    Tonemap,
+}
+
+impl std::fmt::Display for HdrMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            HdrMode::Keep => write!(f, "keep"),
+            HdrMode::Tonemap => write!(f, "tonemap"),
+        }
+    }
+}
+
+/// Whether streams are copied into the new container instead of re-encoded
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RemuxMode {
+    /// Copy when only the container changes and the source streams fit it
+    Auto,
+    /// Always copy (`--remux`)
+    Always,
+    /// Always re-encode
+    #[default]
+    Never,
+}
+
+/// Where `--split-size` / `--split-duration` cut the output into parts
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SplitMode {
+    /// Parts of at most this many bytes
+    Size(u64),
+    /// Parts of this many seconds
+    Duration(f64),
+}
+
 /// Compression configuration
 #[derive(Debug, Clone)]
 pub struct CompressionConfig {
     pub input_path: String,
+    /// Further inputs joined after `input_path` (`--concat`)
+    pub concat: Vec<String>,
     pub output_path: Option<String>,
     pub format: Option<OutputFormat>,
     pub preset: Preset,
+    /// Explicit video codec (None = container default)
+    pub codec: Option<VideoCodec>,
     pub quality: u8,
+    /// Target output size in bytes; replaces `quality` with a computed bitrate
+    pub target_size: Option<u64>,
+    /// Quality score to reach via a CRF search; replaces `quality`
+    pub target_quality: Option<QualityTarget>,
+    /// Explicit CRF (chosen by the `--target-quality` search); overrides `quality`
+    pub crf: Option<u16>,
+    /// Run an analysis pass before the real encode
+    pub two_pass: bool,
+    /// Compare the output with the source (SSIM/PSNR/VMAF) after encoding
+    pub measure_quality: bool,
     pub width: Option<u32>,
     pub height: Option<u32>,
     pub fps: Option<u32>,
+    /// Fit the video into this box, scaling only the long/short edges
+    pub max_resolution: Option<MaxResolution>,
+    /// Allow `width`/`height`/`max_resolution` to enlarge the video
+    pub allow_upscale: bool,
     pub mute: bool,
+    pub audio: AudioSettings,
+    /// Loudness normalization, volume and fades
+    pub audio_filters: AudioFilters,
+    /// Audio tracks to keep
+    pub audio_tracks: TrackSelection,
+    /// Subtitle tracks to keep (converted for the output container)
+    pub subtitles: TrackSelection,
+    /// Subtitles to render into the video
+    pub burn_subtitles: Option<BurnSubtitles>,
+    /// Metadata policy
+    pub metadata: MetadataMode,
+    /// Give the output the modification time of the input
+    pub preserve_mtime: bool,
+    /// HDR handling; None keeps HDR when the codec can carry it and
+    /// tone-maps otherwise
+    pub hdr: Option<HdrMode>,
+    /// Stream copy instead of re-encoding
+    pub remux: RemuxMode,
+    /// Only encode this section of the input
+    pub trim: Option<TrimRange>,
+    /// Playback speed factor (2.0 = twice as fast); audio keeps its pitch
+    pub speed: Option<f64>,
+    /// `--crop auto` / `--crop-aspect`
+    pub auto_crop: Option<AutoCrop>,
     pub transforms: VideoTransforms,
+    /// Denoise, sharpen and deinterlace
+    pub filters: VideoFilters,
+    /// Watermark and text overlay
+    pub overlay: Overlay,
+    /// Palette dithering for GIF output
+    pub gif_dither: GifDither,
+    /// Cut the output into numbered parts
+    pub split: Option<SplitMode>,
     pub overwrite: bool,
     pub verbose: bool,
     pub json: bool,
@@ -156,18 +1144,103 @@ impl Default for CompressionConfig {
     fn default() -> Self {
         Self {
             input_path: String::new(),
+            concat: Vec::new(),
             output_path: None,
             format: None,
             preset: Preset::default(),
+            codec: None,
             quality: 70,
+            target_size: None,
+            target_quality: None,
+            crf: None,
+            two_pass: false,
+            measure_quality: false,
             width: None,
             height: None,
             fps: None,
+            max_resolution: None,
+            allow_upscale: false,
             mute: false,
+            audio: AudioSettings::default(),
+            audio_filters: AudioFilters::default(),
+            audio_tracks: TrackSelection::default(),
+            subtitles: TrackSelection::default(),
+            burn_subtitles: None,
+            metadata: MetadataMode::default(),
+            preserve_mtime: false,
+            hdr: None,
+            remux: RemuxMode::default(),
+            trim: None,
+            speed: None,
+            auto_crop: None,
             transforms: VideoTransforms::default(),
+            filters: VideoFilters::default(),
+            overlay: Overlay::default(),
+            gif_dither: GifDither::default(),
+            split: None,
             overwrite: false,
             verbose: false,
             json: false,
         }
     }
 }
+
+/// Image format for thumbnails, posters and contact sheets
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ImageFormat {
+    #[default]
+    Jpeg,
+    Webp,
+}
+
+impl ImageFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ImageFormat::Jpeg => "jpg",
+            ImageFormat::Webp => "webp",
+        }
+    }
+}
+
+/// Stills to extract instead of compressing (`--thumbnails`, `--poster`,
+/// `--contact-sheet`)
+#[derive(Debug, Clone, PartialEq)]
+pub struct ThumbnailRequest {
+    pub input_path: String,
+    /// Directory for the images (default: next to the input)
+    pub output_dir: Option<String>,
+    /// Number of evenly spaced frames
+    pub thumbnails: Option<u32>,
+    /// Also tile the thumbnails into a sprite with a WebVTT track
+    pub sprite: bool,
+    /// Timestamp of the poster frame, in seconds
+    pub poster: Option<f64>,
+    /// Contact sheet grid as (columns, rows)
+    pub contact_sheet: Option<(u32, u32)>,
+    pub format: ImageFormat,
+    /// Width of thumbnails and contact sheet tiles (the poster keeps the
+    /// video's size)
+    pub width: u32,
+    pub overwrite: bool,
+}
+
+/// What a file written by thumbnail mode is
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StillKind {
+    Thumbnail,
+    Poster,
+    ContactSheet,
+    Sprite,
+    Vtt,
+}
+
+/// A file written by thumbnail mode
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct GeneratedFile {
+    pub kind: StillKind,
+    pub path: String,
+    /// Position in the video, in seconds (single frames only)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub timestamp: Option<f64>,
+}
@@ -1,11 +1,24 @@
 use std::error::Error;
 use std::fmt;
 
+use crate::domain::CropCoordinates;
+
 #[derive(Debug)]
 pub enum CompressoError {
     FileNotFound(String),
     InvalidInput(String),
     InvalidOutput(String),
+    IncompatibleOptions(String),
+    /// `--target-size` is below what the shortest acceptable encode needs
+    TargetSizeUnreachable {
+        target: u64,
+        minimum: u64,
+    },
+    /// `--crop` reaches outside the (rotated) frame
+    CropOutOfBounds {
+        crop: CropCoordinates,
+        frame: (u32, u32),
+    },
     FfmpegNotFound,
     FfmpegError(String),
     Cancelled,
@@ -23,6 +36,27 @@ impl fmt::Display for CompressoError {
             CompressoError::InvalidOutput(path) => {
                 write!(f, "{}: {}", t("invalid_output_path"), path)
             }
+            CompressoError::IncompatibleOptions(msg) => {
+                write!(f, "{}: {}", t("incompatible_options"), msg)
+            }
+            CompressoError::TargetSizeUnreachable { target, minimum } => write!(
+                f,
+                "{}: {} < {}",
+                t("target_size_unreachable"),
+                crate::fs::format_size(*target),
+                crate::fs::format_size(*minimum)
+            ),
+            CompressoError::CropOutOfBounds { crop, frame } => write!(
+                f,
+                "{}: {}x{} at ({}, {}), frame {}x{}",
+                t("crop_out_of_bounds"),
+                crop.width,
+                crop.height,
+                crop.x,
+                crop.y,
+                frame.0,
+                frame.1
+            ),
             CompressoError::FfmpegNotFound => write!(f, "{}", t("ffmpeg_not_found")),
             CompressoError::FfmpegError(msg) => write!(f, "{}: {}", t("ffmpeg_error"), msg),
             CompressoError::Cancelled => write!(f, "{}", t("compression_cancelled_by_user")),
@@ -10,8 +10,16 @@ use std::{
     },
 };
 
-use crate::domain::{CompressionConfig, CompressionResult, Preset, VideoInfo, VideoTransforms};
+use crate::domain::{
+    format_clock, format_timestamp, AudioCodec, AudioSettings, AutoCrop, BurnSubtitles,
+    CompressionConfig, CompressionResult, CrfSearch, CropCoordinates, Deinterlace, Denoise,
+    GeneratedFile, GifDither, HdrMode, ImageFormat, LoudnessStats, MaxResolution, MetadataMode,
+    OutputFormat, OutputPart, Overlay, OverlayPosition, Preset, QualityMetric, QualityMetrics,
+    RemuxMode, SplitMode, StillKind, StreamInfo, StreamKind, ThumbnailRequest, TrackSelection,
+    TrimRange, VideoCodec, VideoFilters, VideoInfo, VideoTransforms,
+};
 use crate::error::{CompressoError, Result};
+use crate::probe::PacketInfo;
 use crate::progress::ProgressMetrics;
 
 /// Global "quiet" flag. When set (e.g. by `--json` mode), diagnostic messages
@@ -37,15 +45,6 @@ fn is_quiet() -> bool {
 // Compile regex patterns once using OnceLock for better performance
 // These are used for parsing FFmpeg output
 
-/// Regex for parsing video duration (HH:MM:SS.MS)
-static DURATION_REGEX: OnceLock<Regex> = OnceLock::new();
-
-/// Regex for parsing video dimensions (WIDTHxHEIGHT)
-static DIMENSIONS_REGEX: OnceLock<Regex> = OnceLock::new();
-
-/// Regex for parsing video FPS
-static FPS_REGEX: OnceLock<Regex> = OnceLock::new();
-
 /// Regex for parsing FFmpeg progress (out_time_ms)
 static PROGRESS_TIME_MS_REGEX: OnceLock<Regex> = OnceLock::new();
 
@@ -55,6 +54,254 @@ static PROGRESS_TIME_REGEX: OnceLock<Regex> = OnceLock::new();
 /// Regex for parsing FFmpeg frame number
 static PROGRESS_FRAME_REGEX: OnceLock<Regex> = OnceLock::new();
 
+/// Regex for the ssim filter summary (`SSIM Y:... All:0.98 (17.2)`)
+static SSIM_REGEX: OnceLock<Regex> = OnceLock::new();
+
+/// Regex for the psnr filter summary (`PSNR y:... average:38.5 ...`)
+static PSNR_REGEX: OnceLock<Regex> = OnceLock::new();
+
+/// Regex for the libvmaf filter summary (`VMAF score: 95.1`)
+static VMAF_REGEX: OnceLock<Regex> = OnceLock::new();
+
+/// Regex for the idet summary (`Multi frame detection: TFF: 0 BFF: 0 Progressive: 96`)
+static IDET_REGEX: OnceLock<Regex> = OnceLock::new();
+
+/// Regex for a cropdetect suggestion (`crop=1920:800:0:140`)
+static CROPDETECT_REGEX: OnceLock<Regex> = OnceLock::new();
+
+/// Fraction of a `--target-size` budget available to the streams; the rest
+/// is reserved for container overhead (headers, index, interleaving).
+const TARGET_SIZE_USABLE_FRACTION: f64 = 0.96;
+
+/// Audio bitrate (kbit/s) reserved out of a `--target-size` budget for
+/// stream-copied audio, whose real bitrate is not known up front.
+const TARGET_SIZE_AUDIO_KBPS: u32 = 128;
+
+/// Lowest video bitrate (kbit/s) a `--target-size` encode may use; below this
+/// even 240p turns into an unwatchable smear.
+const MIN_TARGET_VIDEO_KBPS: u32 = 64;
+
+/// Bits per pixel per frame under which a resolution is considered starved
+/// and `--target-size` steps down to a smaller one.
+const MIN_BITS_PER_PIXEL: f64 = 0.04;
+
+/// Fraction of a `--split-size` limit the packets of a part may fill; the
+/// rest is left for the part's own headers and index.
+const SPLIT_SIZE_USABLE_FRACTION: f64 = 0.97;
+
+/// Keyframe interval (seconds) forced for `--split-size`, so a part never
+/// has to end more than this much before it would overflow.
+const SPLIT_KEYFRAME_SECONDS: f64 = 2.0;
+
+/// How the encoder is told how many bits to spend.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum RateControl {
+    /// Constant quality, derived from `--quality`
+    Crf(u16),
+    /// Average video bitrate in kbit/s, derived from `--target-size`
+    Bitrate(u32),
+}
+
+/// Number of segments encoded per candidate during the `--target-quality` search
+const CRF_SEARCH_SAMPLES: usize = 4;
+
+/// Length in seconds of each `--target-quality` sample segment
+const CRF_SEARCH_SAMPLE_SECONDS: f64 = 4.0;
+
+/// Seconds analysed by cropdetect at each sample point of `--crop auto`
+const CROPDETECT_SECONDS: f64 = 2.0;
+
+/// Seconds analysed by idet at each sample point of `--deinterlace auto`
+const IDET_SECONDS: f64 = 4.0;
+
+/// `--deinterlace`: one output frame per input frame, so the frame rate holds
+const DEINTERLACE_FILTER: &str = "yadif";
+
+/// `--sharpen`: luma only, so chroma noise is not amplified
+const SHARPEN_FILTER: &str = "unsharp=5:5:0.8:5:5:0.0";
+
+/// Long edge GIF/WebP output is capped at unless a size is given
+const ANIMATION_MAX_EDGE: u32 = 480;
+
+/// Frame rate GIF/WebP output is capped at unless `--fps` is given
+const ANIMATION_MAX_FPS: u32 = 15;
+
+/// `--normalize-audio` targets: -16 LUFS integrated (common for streaming
+/// and phones) with 1.5 dB of true-peak headroom
+const LOUDNORM_TARGET: &str = "I=-16:TP=-1.5:LRA=11";
+
+/// Sample rates libopus accepts
+const OPUS_SAMPLE_RATES: [u32; 5] = [48000, 24000, 16000, 12000, 8000];
+
+/// Bitrate budget computed for a `--target-size` encode
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct TargetBitrate {
+    video_kbps: u32,
+    /// Downscale (`scale=W:H`, one side `-2`) applied when the budget is too
+    /// thin for the source resolution
+    scale: Option<(i32, i32)>,
+}
+
+/// One pass of a two-pass encode
+#[derive(Debug, Clone, Copy)]
+struct EncodePass<'a> {
+    /// 1 = analysis pass (no output file), 2 = final pass
+    number: u8,
+    /// Path prefix for the encoder's statistics files
+    passlog: &'a str,
+}
+
+/// How `--concat` feeds its inputs to FFmpeg
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ConcatMode {
+    /// Same codecs and stream parameters: the concat demuxer reads the files
+    /// one after another as a single input
+    Demuxer,
+    /// Anything else: the concat filter joins them after fitting each into
+    /// the first one's frame size, frame rate and audio layout
+    Filter,
+}
+
+/// HDR (PQ or HLG, BT.2020) to SDR BT.709: linearize, tone-map with the
+/// Hable curve in float RGB, then convert back to limited-range YUV.
+const TONEMAP_FILTERS: &str = "zscale=t=linear:npl=100,format=gbrpf32le,zscale=p=bt709,\
+                               tonemap=tonemap=hable:desat=0,zscale=t=bt709:m=bt709:r=tv,\
+                               format=yuv420p";
+
+/// Filter for a `--denoise` level
+fn denoise_filter(level: Denoise) -> &'static str {
+    match level {
+        Denoise::Light => "hqdn3d=2:1.5:3:2.25",
+        Denoise::Medium => "hqdn3d=4:3:6:4.5",
+        Denoise::Strong => "nlmeans=s=3.5:p=7:r=15",
+    }
+}
+
+/// `--lut` then the eq filter for `--brightness` and friends
+fn color_filters(filters: &VideoFilters) -> Vec<String> {
+    let mut result = Vec::new();
+    if let Some(lut) = &filters.lut {
+        result.push(format!("lut3d={}", escape_filter_path(lut)));
+    }
+    let color = &filters.color;
+    let eq: Vec<String> = [
+        ("brightness", color.brightness),
+        ("contrast", color.contrast),
+        ("saturation", color.saturation),
+        ("gamma", color.gamma),
+    ]
+    .into_iter()
+    .filter_map(|(name, value)| value.map(|v| format!("{}={}", name, v)))
+    .collect();
+    if !eq.is_empty() {
+        result.push(format!("eq={}", eq.join(":")));
+    }
+    result
+}
+
+/// `x=...:y=...` placing an item of size (`w`, `h`) in a frame of size
+/// (`frame_w`, `frame_h`); the arguments are the filter's variable names.
+fn overlay_xy(overlay: &Overlay, (frame_w, frame_h, w, h): (&str, &str, &str, &str)) -> String {
+    let margin = overlay.margin;
+    let near = margin.to_string();
+    let x = match overlay.position {
+        OverlayPosition::TopLeft | OverlayPosition::BottomLeft => near.clone(),
+        OverlayPosition::TopRight | OverlayPosition::BottomRight => {
+            format!("{}-{}-{}", frame_w, w, margin)
+        }
+        OverlayPosition::Center => format!("({}-{})/2", frame_w, w),
+    };
+    let y = match overlay.position {
+        OverlayPosition::TopLeft | OverlayPosition::TopRight => near,
+        OverlayPosition::BottomLeft | OverlayPosition::BottomRight => {
+            format!("{}-{}-{}", frame_h, h, margin)
+        }
+        OverlayPosition::Center => format!("({}-{})/2", frame_h, h),
+    };
+    format!("x={}:y={}", x, y)
+}
+
+/// drawtext for `--text-overlay`: white text with a dark outline, sized
+/// relative to the frame height
+fn text_overlay_filter(overlay: &Overlay) -> Option<String> {
+    let text = overlay.text.as_deref()?;
+    let mut filter = format!(
+        "drawtext=text={}:expansion=none:fontsize=h/20:fontcolor=white@{}:borderw=2:bordercolor=black@{}:{}",
+        escape_filter_value(text),
+        overlay.opacity,
+        overlay.opacity,
+        overlay_xy(overlay, ("w", "h", "text_w", "text_h"))
+    );
+    if let Some(font) = &overlay.font {
+        filter.push_str(&format!(":fontfile={}", escape_filter_path(font)));
+    }
+    Some(filter)
+}
+
+/// Filter graph for `--watermark`: the usual chain on the `source` video,
+/// then the image (input number `image_input`) laid over it and the
+/// optional `post` filters. The result is labelled `[v]`.
+fn watermark_graph(
+    source: &str,
+    chain: &str,
+    overlay: &Overlay,
+    image_input: usize,
+    post: Option<&str>,
+) -> String {
+    let mut image = "format=rgba".to_string();
+    if overlay.opacity < 1.0 {
+        image.push_str(&format!(",colorchannelmixer=aa={}", overlay.opacity));
+    }
+    format!(
+        "{}{}[main];[{}:v]{}[wm];[main][wm]overlay={}{}[v]",
+        source,
+        chain,
+        image_input,
+        image,
+        overlay_xy(overlay, ("W", "H", "w", "h")),
+        post.map(|p| format!(",{}", p)).unwrap_or_default()
+    )
+}
+
+/// Whether the output is an animated image (GIF or WebP) rather than a video
+fn is_animation(output_format: &str) -> bool {
+    OutputFormat::from_extension(output_format).is_some_and(|f| f.is_animation())
+}
+
+/// The two GIF stages in one graph: palettegen picks the 256 colours that
+/// best fit the clip (favouring what moves), then paletteuse maps every
+/// frame onto them. Only changed rectangles are re-dithered, which keeps
+/// static areas from shimmering and compresses better.
+fn gif_palette_filters(dither: GifDither) -> String {
+    format!(
+        "split[g1][g2];[g1]palettegen=stats_mode=diff[pal];[g2][pal]paletteuse=dither={}:diff_mode=rectangle",
+        dither.filter_value()
+    )
+}
+
+/// Escape a path for use as a filter option inside `-vf`. FFmpeg unescapes
+/// it twice, once when splitting the filtergraph and once when parsing the
+/// option, so both levels are applied (`C:\subs` becomes `C\\:\\\\subs`).
+fn escape_filter_path(path: &str) -> String {
+    escape_filter_value(&strip_verbatim_prefix(path))
+}
+
+/// Escape any text for use as a filter option, as for [`escape_filter_path`].
+fn escape_filter_value(value: &str) -> String {
+    fn escape(value: &str, special: &[char]) -> String {
+        let mut escaped = String::with_capacity(value.len());
+        for c in value.chars() {
+            if special.contains(&c) {
+                escaped.push('\\');
+            }
+            escaped.push(c);
+        }
+        escaped
+    }
+    let option = escape(value, &['\\', '\'', ':']);
+    escape(&option, &['\\', '\'', '[', ']', ',', ';'])
+}
+
 /// Strip the Windows `\\?\` verbatim prefix from a canonicalized path so it can
 /// be matched against user-readable denylist entries (e.g. `C:\Windows\`).
 ///
@@ -79,6 +326,13 @@ struct TempFileGuard {
     path: PathBuf,
     keep: Arc<AtomicBool>,
     child: Option<Arc<SharedChild>>,
+    /// Two-pass statistics files (`<prefix>*`), removed even on success
+    passlog_prefix: Option<PathBuf>,
+    /// `--concat` input list, removed even on success
+    concat_list: Option<PathBuf>,
+    /// Parts cut from the temp file (`<prefix>*`); on success they have
+    /// been renamed already
+    parts_prefix: Option<PathBuf>,
 }
 
 impl TempFileGuard {
@@ -87,9 +341,24 @@ impl TempFileGuard {
             path,
             keep: Arc::new(AtomicBool::new(false)),
             child: None,
+            passlog_prefix: None,
+            concat_list: None,
+            parts_prefix: None,
         }
     }
 
+    fn set_passlog_prefix(&mut self, prefix: PathBuf) {
+        self.passlog_prefix = Some(prefix);
+    }
+
+    fn set_concat_list(&mut self, path: PathBuf) {
+        self.concat_list = Some(path);
+    }
+
+    fn set_parts_prefix(&mut self, prefix: PathBuf) {
+        self.parts_prefix = Some(prefix);
+    }
+
     fn set_child(&mut self, child: Arc<SharedChild>) {
         self.child = Some(child);
     }
@@ -132,19 +401,157 @@ impl Drop for TempFileGuard {
                 }
             }
         }
+
+        if let Some(ref list) = self.concat_list {
+            let _ = std::fs::remove_file(list);
+        }
+
+        // Pass statistics are never part of the result, so they go in every case
+        if let Some(ref prefix) = self.passlog_prefix {
+            remove_prefixed(prefix);
+        }
+        if let Some(ref prefix) = self.parts_prefix {
+            remove_prefixed(prefix);
+        }
+    }
+}
+
+/// Remove every file in the folder of `prefix` whose name starts with its
+/// file name
+fn remove_prefixed(prefix: &Path) {
+    if let (Some(dir), Some(stem)) = (prefix.parent(), prefix.file_name()) {
+        let stem = stem.to_string_lossy();
+        if let Ok(entries) = std::fs::read_dir(dir) {
+            for entry in entries.flatten() {
+                if entry
+                    .file_name()
+                    .to_string_lossy()
+                    .starts_with(stem.as_ref())
+                {
+                    let _ = std::fs::remove_file(entry.path());
+                }
+            }
+        }
     }
 }
 
 /// FFmpeg wrapper for video compression
 pub struct FFmpeg {
     ffmpeg_path: String,
+    /// ffprobe from the same installation, used for `get_video_info`
+    ffprobe_path: Option<String>,
+    /// Encoder names compiled into this FFmpeg build (`ffmpeg -encoders`),
+    /// probed lazily on first use.
+    encoders: OnceLock<Vec<String>>,
+    /// Filter names compiled into this FFmpeg build (`ffmpeg -filters`),
+    /// probed lazily on first use.
+    filters: OnceLock<Vec<String>>,
+    /// `configuration:` line of `ffmpeg -version`, probed lazily on first use.
+    build_config: OnceLock<String>,
 }
 
 impl FFmpeg {
     /// Create new FFmpeg instance
     pub fn new() -> Result<Self> {
         let ffmpeg_path = Self::find_ffmpeg()?;
-        Ok(Self { ffmpeg_path })
+        let ffprobe_path = Self::find_ffprobe(&ffmpeg_path);
+        Ok(Self {
+            ffmpeg_path,
+            ffprobe_path,
+            encoders: OnceLock::new(),
+            filters: OnceLock::new(),
+            build_config: OnceLock::new(),
+        })
+    }
+
+    /// Check whether the detected FFmpeg build ships the given encoder
+    /// (e.g. `libsvtav1`). A failed probe is treated as "not available".
+    fn has_encoder(&self, name: &str) -> bool {
+        self.encoders
+            .get_or_init(|| {
+                Command::new(&self.ffmpeg_path)
+                    .args(["-hide_banner", "-encoders"])
+                    .stderr(Stdio::null())
+                    .output()
+                    .map(|output| {
+                        Self::parse_encoder_list(&String::from_utf8_lossy(&output.stdout))
+                    })
+                    .unwrap_or_default()
+            })
+            .iter()
+            .any(|encoder| encoder == name)
+    }
+
+    /// Parse the table printed by `ffmpeg -encoders`.
+    ///
+    /// The listing starts with a legend terminated by a ` ------` line, then
+    /// has one encoder per line: ` V....D libx264  libx264 H.264 / AVC ...`.
+    fn parse_encoder_list(output: &str) -> Vec<String> {
+        output
+            .lines()
+            .skip_while(|line| !line.trim_start().starts_with("---"))
+            .skip(1)
+            .filter_map(|line| line.split_whitespace().nth(1))
+            .map(str::to_string)
+            .collect()
+    }
+
+    /// Check whether the detected FFmpeg build ships the given filter
+    /// (e.g. `libvmaf`). A failed probe is treated as "not available".
+    fn has_filter(&self, name: &str) -> bool {
+        self.filters
+            .get_or_init(|| {
+                Command::new(&self.ffmpeg_path)
+                    .args(["-hide_banner", "-filters"])
+                    .stderr(Stdio::null())
+                    .output()
+                    .map(|output| Self::parse_filter_list(&String::from_utf8_lossy(&output.stdout)))
+                    .unwrap_or_default()
+            })
+            .iter()
+            .any(|filter| filter == name)
+    }
+
+    /// Whether drawtext can find a default font, i.e. FFmpeg was built with
+    /// fontconfig. A failed probe is treated as "not available".
+    fn has_fontconfig(&self) -> bool {
+        self.build_config
+            .get_or_init(|| {
+                Command::new(&self.ffmpeg_path)
+                    .args(["-hide_banner", "-version"])
+                    .stderr(Stdio::null())
+                    .output()
+                    .map(|output| {
+                        String::from_utf8_lossy(&output.stdout)
+                            .lines()
+                            .find(|line| line.starts_with("configuration:"))
+                            .unwrap_or_default()
+                            .to_string()
+                    })
+                    .unwrap_or_default()
+            })
+            .split_whitespace()
+            .any(|flag| flag == "--enable-libfontconfig" || flag == "--enable-fontconfig")
+    }
+
+    /// Parse the table printed by `ffmpeg -filters`.
+    ///
+    /// Unlike `-encoders` there is no separator line after the legend, so
+    /// filter rows are recognised by their `in->out` column:
+    /// ` T.C libvmaf  VV->V  Calculate the VMAF between two video streams.`
+    fn parse_filter_list(output: &str) -> Vec<String> {
+        output
+            .lines()
+            .filter_map(|line| {
+                let mut columns = line.split_whitespace();
+                let _flags = columns.next()?;
+                let name = columns.next()?;
+                columns
+                    .next()
+                    .filter(|io| io.contains("->"))
+                    .map(|_| name.to_string())
+            })
+            .collect()
     }
 
     /// Find FFmpeg binary with security considerations
@@ -223,6 +630,30 @@ impl FFmpeg {
         Err(CompressoError::FfmpegNotFound)
     }
 
+    /// Find the ffprobe that belongs to the resolved FFmpeg.
+    ///
+    /// The binary next to FFmpeg is preferred so both come from the same
+    /// (trusted) location; PATH is only a fallback and is logged like it is
+    /// for FFmpeg.
+    fn find_ffprobe(ffmpeg_path: &str) -> Option<String> {
+        let name = if cfg!(windows) {
+            "ffprobe.exe"
+        } else {
+            "ffprobe"
+        };
+        let sibling = Path::new(ffmpeg_path).with_file_name(name);
+        if sibling.is_file() {
+            return Some(sibling.to_string_lossy().to_string());
+        }
+
+        let path = which::which("ffprobe").ok()?;
+        let path_str = path.to_string_lossy().to_string();
+        if !is_quiet() {
+            eprintln!("⚠ Using ffprobe from system PATH: {}", path_str);
+        }
+        Some(path_str)
+    }
+
     /// Verify bundled FFmpeg binary integrity
     ///
     /// This is a basic verification that checks if the binary is executable
@@ -461,67 +892,66 @@ impl FFmpeg {
     /// Get video information
     ///
     /// Note: This function does not pre-check file existence to avoid TOCTOU race conditions.
-    /// FFmpeg will atomically open and validate the file.
+    /// ffprobe will atomically open and validate the file.
     pub fn get_video_info(&self, video_path: &str) -> Result<VideoInfo> {
-        let output = Command::new(&self.ffmpeg_path)
-            .args(["-i", video_path, "-hide_banner"])
+        let output = Command::new(self.ffprobe()?)
+            .args([
+                "-v",
+                "error",
+                "-print_format",
+                "json",
+                "-show_streams",
+                "-show_format",
+                video_path,
+            ])
+            .stdout(Stdio::piped())
             .stderr(Stdio::piped())
-            .stdout(Stdio::null())
             .output()?;
 
-        // Check if FFmpeg failed (likely file not found or invalid)
         if !output.status.success() {
             let stderr = String::from_utf8_lossy(&output.stderr);
             if stderr.contains("No such file") || stderr.contains("does not exist") {
                 return Err(CompressoError::FileNotFound(video_path.to_string()));
             }
+            return Err(CompressoError::CorruptedVideo);
         }
 
-        let stderr = String::from_utf8_lossy(&output.stderr);
-
-        let duration = Self::parse_duration(&stderr);
-        let duration_seconds = duration.as_ref().and_then(|d| Self::duration_to_seconds(d));
-        let dimensions = Self::parse_dimensions(&stderr);
-        let fps = Self::parse_fps(&stderr);
-
-        Ok(VideoInfo {
-            duration,
-            duration_seconds,
-            dimensions,
-            fps,
-        })
+        crate::probe::parse_ffprobe_json(&String::from_utf8_lossy(&output.stdout))
+            .map_err(|e| CompressoError::FfmpegError(format!("unreadable ffprobe output: {}", e)))
     }
 
-    fn parse_duration(output: &str) -> Option<String> {
-        let re = DURATION_REGEX.get_or_init(|| {
-            // SAFETY: the pattern is a compile-time constant literal that is
-            // known to be a valid regex; this cannot fail.
-            Regex::new(r"Duration: (?P<duration>\d{2}:\d{2}:\d{2}\.\d{2})").unwrap()
-        });
-        re.captures(output).map(|cap| cap["duration"].to_string())
+    /// Time, size and keyframe flag of every packet of a file
+    fn get_packets(&self, path: &str) -> Result<Vec<PacketInfo>> {
+        let output = Command::new(self.ffprobe()?)
+            .args([
+                "-v",
+                "error",
+                "-print_format",
+                "json",
+                "-show_entries",
+                "packet=codec_type,pts_time,dts_time,size,flags",
+                path,
+            ])
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .output()?;
+        if !output.status.success() {
+            return Err(CompressoError::FfmpegError(
+                String::from_utf8_lossy(&output.stderr).trim().to_string(),
+            ));
+        }
+        crate::probe::parse_packets_json(&String::from_utf8_lossy(&output.stdout))
+            .map_err(|e| CompressoError::FfmpegError(format!("unreadable ffprobe output: {}", e)))
     }
 
-    fn parse_dimensions(output: &str) -> Option<(u32, u32)> {
-        let re = DIMENSIONS_REGEX.get_or_init(|| {
-            // SAFETY: compile-time constant literal, valid regex.
-            Regex::new(r"Video:.*? (\d{2,5})x(\d{2,5})").unwrap()
-        });
-        re.captures(output).and_then(|cap| {
-            let width = cap.get(1)?.as_str().parse().ok()?;
-            let height = cap.get(2)?.as_str().parse().ok()?;
-            Some((width, height))
+    fn ffprobe(&self) -> Result<&str> {
+        self.ffprobe_path.as_deref().ok_or_else(|| {
+            CompressoError::FfmpegError(
+                "ffprobe was not found next to FFmpeg or in PATH; it ships with FFmpeg".to_string(),
+            )
         })
     }
 
-    fn parse_fps(output: &str) -> Option<f32> {
-        let re = FPS_REGEX.get_or_init(|| {
-            // SAFETY: compile-time constant literal, valid regex.
-            Regex::new(r"(\d+(?:\.\d+)?)\s*fps").unwrap()
-        });
-        re.captures(output)
-            .and_then(|cap| cap.get(1)?.as_str().parse().ok())
-    }
-
     fn duration_to_seconds(duration: &str) -> Option<f64> {
         let parts: Vec<&str> = duration.split(':').collect();
         if parts.len() != 3 {
@@ -571,29 +1001,24 @@ impl FFmpeg {
 
         // Validate and canonicalize input path (protect against path traversal)
         let validated_input = Self::validate_input_path(input_path)?;
+        Self::check_extra_inputs(config)?;
 
         // Get video info for progress calculation
         // Use provided info if available to avoid double FFmpeg spawn
         let video_info = match video_info {
             Some(info) => info.clone(),
-            None => self.get_video_info(&validated_input)?,
+            None => self.concat_info(config, self.get_video_info(&validated_input)?)?,
         };
 
-        let total_duration = video_info.duration_seconds.unwrap_or(0.0);
+        // Progress and ETA are measured against what is actually encoded.
+        let total_duration = Self::output_duration(config, &video_info).unwrap_or(0.0);
         let fps = video_info.fps.unwrap_or(30.0);
         let total_frames = (total_duration * fps as f64) as u32;
 
         // Determine output format and path
-        let output_format = config
-            .format
-            .map(|f| f.extension().to_string())
-            .unwrap_or_else(|| {
-                Path::new(&validated_input)
-                    .extension()
-                    .and_then(|e| e.to_str())
-                    .unwrap_or("mp4")
-                    .to_string()
-            });
+        let output_format = Self::output_format(config, &validated_input);
+        let remuxed = Self::should_remux(config, &video_info, &validated_input, &output_format)?;
+        let hdr = self.hdr_mode(config, &video_info, &output_format)?;
 
         let output_path = match &config.output_path {
             Some(p) => p.clone(),
@@ -604,22 +1029,28 @@ impl FFmpeg {
         Self::validate_output_path(&output_path)?;
 
         // Atomically check if output exists and prevent overwrite if not set
-        // This uses create_new() which atomically fails if file exists
+        // This uses create_new() which atomically fails if file exists.
+        // A split output is written as parts; the first one is checked here,
+        // the rest once their number is known.
+        let first_output = match config.split {
+            Some(_) => crate::fs::part_path(&output_path, 1),
+            None => output_path.clone(),
+        };
         if !config.overwrite {
             match std::fs::OpenOptions::new()
                 .write(true)
                 .create_new(true)
-                .open(&output_path)
+                .open(&first_output)
             {
                 Ok(f) => {
                     // File didn't exist, we created it. Remove it immediately.
                     drop(f);
-                    let _ = std::fs::remove_file(&output_path);
+                    let _ = std::fs::remove_file(&first_output);
                 }
                 Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => {
                     return Err(CompressoError::InvalidOutput(format!(
                         "File already exists: {}. Use -y to overwrite.",
-                        output_path
+                        first_output
                     )));
                 }
                 Err(e) => return Err(e.into()),
@@ -653,8 +1084,27 @@ impl FFmpeg {
         // Create RAII guard to ensure temp file is cleaned up on any exit path
         let mut temp_guard = TempFileGuard::new(PathBuf::from(&temp_output_path));
 
-        // Get original size
-        let original_size = std::fs::metadata(&validated_input)?.len();
+        // Get original size (of all inputs with --concat)
+        let mut original_size = std::fs::metadata(&validated_input)?.len();
+        for path in &config.concat {
+            original_size += std::fs::metadata(path)?.len();
+        }
+
+        // The concat demuxer reads the inputs from a list file; relative
+        // entries would resolve against its folder, so they are canonical.
+        let encode_input = if Self::concat_mode(&video_info) == Some(ConcatMode::Demuxer) {
+            let mut inputs = vec![validated_input.clone()];
+            for path in &config.concat {
+                inputs.push(Self::validate_input_path(path)?);
+            }
+            let list = std::env::temp_dir()
+                .join(format!("compresso-concat-{}.ffconcat", nanoid::nanoid!(8)));
+            temp_guard.set_concat_list(list.clone());
+            std::fs::write(&list, Self::concat_list(&inputs))?;
+            list.to_string_lossy().into_owned()
+        } else {
+            validated_input.clone()
+        };
 
         // Create progress metrics for tracking speed and ETA
         let progress_metrics = Arc::new(Mutex::new(ProgressMetrics::new(
@@ -663,22 +1113,210 @@ impl FFmpeg {
         )));
         let metrics_for_thread = progress_metrics.clone();
 
-        // Build FFmpeg arguments (write to temp file for atomic operation)
-        let args = self.build_args(config, &validated_input, &temp_output_path, &output_format);
+        // Two-pass encodes keep their statistics files under a unique prefix
+        // in the temp dir; the guard removes them however we exit.
+        let passlog = if config.two_pass {
+            let prefix =
+                std::env::temp_dir().join(format!("compresso-passlog-{}", nanoid::nanoid!(8)));
+            temp_guard.set_passlog_prefix(prefix.clone());
+            Some(prefix.to_string_lossy().to_string())
+        } else {
+            None
+        };
+
+        // Build FFmpeg arguments for every pass up front (write to temp file for
+        // atomic operation), so option errors surface before anything runs.
+        // Each pass reports progress within its own share of 0-100%.
+        let passes: Vec<(Vec<String>, (f64, f64))> = match passlog.as_deref() {
+            Some(passlog) => vec![
+                (
+                    self.build_args(
+                        config,
+                        &video_info,
+                        &encode_input,
+                        &temp_output_path,
+                        &output_format,
+                        Some(EncodePass { number: 1, passlog }),
+                    )?,
+                    (0.0, 50.0),
+                ),
+                (
+                    self.build_args(
+                        config,
+                        &video_info,
+                        &encode_input,
+                        &temp_output_path,
+                        &output_format,
+                        Some(EncodePass { number: 2, passlog }),
+                    )?,
+                    (50.0, 100.0),
+                ),
+            ],
+            None => vec![(
+                self.build_args(
+                    config,
+                    &video_info,
+                    &encode_input,
+                    &temp_output_path,
+                    &output_format,
+                    None,
+                )?,
+                (0.0, 100.0),
+            )],
+        };
 
         if config.verbose {
-            // Sanitize arguments to avoid leaking full paths in logs
-            let sanitized_args = Self::sanitize_args_for_logging(&args);
-            eprintln!(
-                "ℹ FFmpeg command (paths sanitized): ffmpeg {}",
-                sanitized_args.join(" ")
-            );
+            for (args, _) in &passes {
+                // Sanitize arguments to avoid leaking full paths in logs
+                let sanitized_args = Self::sanitize_args_for_logging(args);
+                eprintln!(
+                    "ℹ FFmpeg command (paths sanitized): ffmpeg {}",
+                    sanitized_args.join(" ")
+                );
+            }
+        }
+
+        // Channel for progress updates (progress, current_frame), shared by all passes
+        let (tx, rx) = crossbeam_channel::unbounded::<(f64, u32)>();
+
+        // Spawn thread for progress callback
+        let cancelled_for_progress = cancelled.clone();
+        let mut last_frame: u32 = 0;
+        let mut last_time = std::time::Instant::now();
+        let mut last_fps: f64 = 0.0;
+
+        std::thread::spawn(move || {
+            while let Ok((progress, current_frame)) = rx.recv() {
+                if cancelled_for_progress.load(Ordering::Relaxed) {
+                    break;
+                }
+
+                // Calculate FPS (frames per second)
+                let now = std::time::Instant::now();
+                let elapsed = now.duration_since(last_time).as_secs_f64();
+
+                // The frame counter restarts with every pass
+                if current_frame < last_frame {
+                    last_frame = current_frame;
+                    last_time = now;
+                }
+
+                // Update FPS calculation if enough time has passed
+                if elapsed > 0.3 && current_frame > last_frame {
+                    let frames_processed = current_frame.saturating_sub(last_frame);
+                    last_fps = frames_processed as f64 / elapsed;
+                    last_frame = current_frame;
+                    last_time = now;
+                }
+
+                // Update progress metrics to get ETA
+                let eta = if let Ok(mut metrics) = metrics_for_thread.lock() {
+                    metrics.update_progress(progress);
+                    metrics.calculate_eta()
+                } else {
+                    None
+                };
+
+                progress_callback(progress, current_frame, total_frames, last_fps, eta);
+            }
+        });
+
+        for (args, progress_range) in &passes {
+            self.run_pass(
+                args,
+                *progress_range,
+                total_duration,
+                &mut temp_guard,
+                &cancelled,
+                tx.clone(),
+            )?;
+        }
+        drop(tx);
+
+        // --split-size / --split-duration: the parts are cut from the whole
+        // encode and moved into place; the encode itself is not kept.
+        let parts = match config.split {
+            Some(split) => self.split_output(
+                split,
+                &temp_output_path,
+                &output_path,
+                config,
+                &mut temp_guard,
+            )?,
+            None => Vec::new(),
+        };
+
+        // Success! Tell the guard to keep the temp file (we'll rename it)
+        temp_guard.keep();
+
+        // Atomic rename: move temp file to final output path
+        let written: Vec<String> = if parts.is_empty() {
+            std::fs::rename(&temp_output_path, &output_path)?;
+            vec![output_path]
+        } else {
+            if Path::new(&temp_output_path).exists() {
+                std::fs::remove_file(&temp_output_path)?;
+            }
+            parts.iter().map(|part| part.path.clone()).collect()
+        };
+
+        // The files are already in place, so a failure here is only a warning.
+        if config.preserve_mtime {
+            for path in &written {
+                if let Err(e) = Self::copy_mtime(&validated_input, path) {
+                    if !is_quiet() {
+                        eprintln!("⚠ Could not set the modification time of {}: {}", path, e);
+                    }
+                }
+            }
+        }
+
+        // Get compressed size (of all parts together)
+        let mut compressed_size = 0;
+        for path in &written {
+            compressed_size += std::fs::metadata(path)?.len();
         }
+        let target_met = config.target_size.map(|target| compressed_size <= target);
+        let output_path = written[0].clone();
+
+        Ok(CompressionResult {
+            file_name: Path::new(&output_path)
+                .file_name()
+                .and_then(|n| n.to_str())
+                .unwrap_or("output")
+                .to_string(),
+            file_path: output_path,
+            original_size,
+            compressed_size,
+            target_size: config.target_size,
+            target_met,
+            // Filled in by the caller via measure_quality() / search_crf()
+            quality_metrics: None,
+            crf_search: None,
+            remuxed,
+            hdr,
+            parts,
+        })
+    }
 
+    /// Run one FFmpeg invocation to completion, forwarding its progress
+    /// (scaled into `progress_range`) to `progress_tx`.
+    ///
+    /// The child is registered with `temp_guard`, so an early return on
+    /// cancellation or failure kills it and cleans up the temp files.
+    fn run_pass(
+        &self,
+        args: &[String],
+        (range_start, range_end): (f64, f64),
+        total_duration: f64,
+        temp_guard: &mut TempFileGuard,
+        cancelled: &Arc<AtomicBool>,
+        progress_tx: crossbeam_channel::Sender<(f64, u32)>,
+    ) -> Result<()> {
         // Spawn FFmpeg process
         let mut command = Command::new(&self.ffmpeg_path);
         command
-            .args(&args)
+            .args(args)
             .stdout(Stdio::piped())
             .stderr(Stdio::piped());
 
@@ -691,8 +1329,8 @@ impl FFmpeg {
         // Give the guard access to the child process so it can kill it on drop
         temp_guard.set_child(child.clone());
 
-        // Channel for progress updates (progress, current_frame)
-        let (tx, rx) = crossbeam_channel::unbounded::<(f64, u32)>();
+        // Map this pass's 0-100% onto its share of the overall progress
+        let scale = move |progress: f64| range_start + progress * (range_end - range_start) / 100.0;
 
         // Spawn thread to read stdout (progress)
         let cancelled_clone = cancelled.clone();
@@ -731,7 +1369,7 @@ impl FFmpeg {
                             if total_duration > 0.0 {
                                 let progress =
                                     (current_seconds / total_duration * 100.0).min(100.0);
-                                let _ = tx.try_send((progress, current_frame));
+                                let _ = progress_tx.try_send((scale(progress), current_frame));
                             }
                         }
                     }
@@ -740,7 +1378,7 @@ impl FFmpeg {
                         if let Some(seconds) = Self::duration_to_seconds(&cap[1]) {
                             if total_duration > 0.0 {
                                 let progress = (seconds / total_duration * 100.0).min(100.0);
-                                let _ = tx.try_send((progress, current_frame));
+                                let _ = progress_tx.try_send((scale(progress), current_frame));
                             }
                         }
                     }
@@ -748,78 +1386,41 @@ impl FFmpeg {
             }
         });
 
-        // Spawn thread for progress callback
-        let cancelled_for_progress = cancelled.clone();
-        let mut last_frame: u32 = 0;
-        let mut last_time = std::time::Instant::now();
-        let mut last_fps: f64 = 0.0;
+        // Spawn thread to wait for process completion (blocking, no busy-wait)
+        let child_for_wait = child.clone();
+        let (completion_tx, completion_rx) = crossbeam_channel::bounded(1);
 
         std::thread::spawn(move || {
-            while let Ok((progress, current_frame)) = rx.recv() {
-                if cancelled_for_progress.load(Ordering::Relaxed) {
-                    break;
-                }
-
-                // Calculate FPS (frames per second)
-                let now = std::time::Instant::now();
-                let elapsed = now.duration_since(last_time).as_secs_f64();
+            // Block until process completes (no CPU waste)
+            let result = child_for_wait.wait();
+            let _ = completion_tx.send(result);
+        });
 
-                // Update FPS calculation if enough time has passed
-                if elapsed > 0.3 && current_frame > last_frame {
-                    let frames_processed = current_frame.saturating_sub(last_frame);
-                    last_fps = frames_processed as f64 / elapsed;
-                    last_frame = current_frame;
-                    last_time = now;
-                }
-
-                // Update progress metrics to get ETA
-                let eta = if let Ok(mut metrics) = metrics_for_thread.lock() {
-                    metrics.update_progress(progress);
-                    metrics.calculate_eta()
-                } else {
-                    None
-                };
-
-                progress_callback(progress, current_frame, total_frames, last_fps, eta);
-            }
-        });
-
-        // Spawn thread to wait for process completion (blocking, no busy-wait)
-        let child_for_wait = child.clone();
-        let (completion_tx, completion_rx) = crossbeam_channel::bounded(1);
-
-        std::thread::spawn(move || {
-            // Block until process completes (no CPU waste)
-            let result = child_for_wait.wait();
-            let _ = completion_tx.send(result);
-        });
-
-        // Wait for completion or cancellation using select (efficient, no polling)
-        loop {
-            // Check for cancellation
-            if cancelled.load(Ordering::Relaxed) {
-                // temp_guard will automatically kill FFmpeg and clean up the file on return
-                return Err(CompressoError::Cancelled);
-            }
+        // Wait for completion or cancellation using select (efficient, no polling)
+        loop {
+            // Check for cancellation
+            if cancelled.load(Ordering::Relaxed) {
+                // temp_guard will automatically kill FFmpeg and clean up the file on return
+                return Err(CompressoError::Cancelled);
+            }
 
             // Wait for completion with timeout (allows periodic cancellation checks)
             match completion_rx.recv_timeout(std::time::Duration::from_millis(100)) {
                 Ok(Ok(status)) => {
                     // Process completed
                     if status.success() {
-                        break;
-                    } else {
-                        // temp_guard will automatically clean up the file on return
-                        // Read stderr for error message
-                        if let Some(mut stderr) = child.take_stderr() {
-                            let mut error_msg = String::new();
-                            let _ = std::io::Read::read_to_string(&mut stderr, &mut error_msg);
-                            if !error_msg.is_empty() {
-                                return Err(CompressoError::FfmpegError(error_msg));
-                            }
+                        return Ok(());
+                    }
+                    // temp_guard will automatically clean up the file on return
+                    // Read stderr for error message
+                    if let Some(mut stderr) = child.take_stderr() {
+                        let mut error_msg = String::new();
+                        let _ = std::io::Read::read_to_string(&mut stderr, &mut error_msg);
+                        if !error_msg.is_empty() {
+                            return Err(CompressoError::FfmpegError(error_msg));
                         }
-                        return Err(CompressoError::CorruptedVideo);
                     }
+                    return Err(CompressoError::CorruptedVideo);
                 }
                 Ok(Err(e)) => {
                     // Process wait failed
@@ -838,110 +1439,819 @@ impl FFmpeg {
                 }
             }
         }
+    }
 
-        // Success! Tell the guard to keep the temp file (we'll rename it)
-        temp_guard.keep();
+    /// How an HDR source is encoded: `--hdr`, or by default kept when the
+    /// codec can carry it and tone-mapped otherwise. A stream copy always
+    /// keeps it. None for SDR video. An error when tone-mapping would be
+    /// needed but FFmpeg lacks the zscale filter, since the output would
+    /// otherwise look washed out.
+    pub fn hdr_mode(
+        &self,
+        config: &CompressionConfig,
+        video_info: &VideoInfo,
+        output_format: &str,
+    ) -> Result<Option<HdrMode>> {
+        let Some(format) = video_info.hdr_format() else {
+            return Ok(None);
+        };
+        if Self::should_remux(config, video_info, &config.input_path, output_format)? {
+            return Ok(Some(HdrMode::Keep));
+        }
 
-        // Atomic rename: move temp file to final output path
-        std::fs::rename(&temp_output_path, &output_path)?;
+        let codec = config
+            .codec
+            .unwrap_or_else(|| VideoCodec::default_for_format(output_format));
+        // x265, libvpx-vp9 and both AV1 encoders take 10-bit input.
+        let can_keep = codec != VideoCodec::H264;
+        let can_tonemap = self.has_filter("zscale");
+        match config.hdr {
+            Some(HdrMode::Keep) if !can_keep => Err(CompressoError::IncompatibleOptions(format!(
+                "{} video cannot carry {} (use --codec hevc or --hdr tonemap)",
+                codec, format
+            ))),
+            Some(HdrMode::Tonemap) if !can_tonemap => Err(CompressoError::IncompatibleOptions(
+                "tone-mapping needs the zscale filter, which this FFmpeg build lacks".to_string(),
+            )),
+            Some(mode) => Ok(Some(mode)),
+            None if can_keep => Ok(Some(HdrMode::Keep)),
+            None if can_tonemap => Ok(Some(HdrMode::Tonemap)),
+            // Encoding anyway would give washed-out SDR.
+            None if is_animation(output_format) => {
+                Err(CompressoError::IncompatibleOptions(format!(
+                    "{} output cannot carry {} and tone-mapping needs the zscale filter, \
+                     which this FFmpeg build lacks (use an FFmpeg build with zscale)",
+                    output_format.to_uppercase(),
+                    format
+                )))
+            }
+            None => Err(CompressoError::IncompatibleOptions(format!(
+                "{} video cannot carry {} and tone-mapping needs the zscale filter, which this \
+                 FFmpeg build lacks (use --codec hevc or an FFmpeg build with zscale)",
+                codec, format
+            ))),
+        }
+    }
 
-        // Get compressed size
-        let compressed_size = std::fs::metadata(&output_path)?.len();
+    /// Colour tags for the output: the source's HDR tags, or BT.709 after
+    /// tone-mapping.
+    fn push_color_args(args: &mut Vec<String>, hdr: HdrMode, video_info: &VideoInfo) {
+        let video = video_info.main_video();
+        let tag = |value: Option<&String>, default: &str| {
+            value.map_or(default.to_string(), |v| v.clone())
+        };
+        let (primaries, transfer, space) = match hdr {
+            HdrMode::Keep => (
+                tag(video.and_then(|v| v.color_primaries.as_ref()), "bt2020"),
+                tag(video.and_then(|v| v.color_transfer.as_ref()), "smpte2084"),
+                tag(video.and_then(|v| v.color_space.as_ref()), "bt2020nc"),
+            ),
+            HdrMode::Tonemap => (
+                "bt709".to_string(),
+                "bt709".to_string(),
+                "bt709".to_string(),
+            ),
+        };
+        args.extend([
+            "-color_primaries".to_string(),
+            primaries,
+            "-color_trc".to_string(),
+            transfer,
+            "-colorspace".to_string(),
+            space,
+        ]);
+    }
 
-        Ok(CompressionResult {
-            file_name: Path::new(&output_path)
-                .file_name()
-                .and_then(|n| n.to_str())
-                .unwrap_or("output")
-                .to_string(),
-            file_path: output_path,
-            original_size,
-            compressed_size,
+    /// Output container extension: `--format`, else the input's extension.
+    pub fn output_format(config: &CompressionConfig, input_path: &str) -> String {
+        config
+            .format
+            .map(|f| f.extension().to_string())
+            .unwrap_or_else(|| {
+                Path::new(input_path)
+                    .extension()
+                    .and_then(|e| e.to_str())
+                    .unwrap_or("mp4")
+                    .to_string()
+            })
+    }
+
+    /// Compare an encoded file against its source with FFmpeg's ssim and
+    /// psnr filters, plus libvmaf when the build has it.
+    ///
+    /// The source gets the same transforms and frame rate as the encode, and
+    /// the encoded video is scaled back to the transformed source size so
+    /// frames line up even when `--width/--height` changed the resolution.
+    pub fn measure_quality(
+        &self,
+        config: &CompressionConfig,
+        video_info: &VideoInfo,
+        encoded_path: &str,
+    ) -> Result<QualityMetrics> {
+        let reference = Self::validate_input_path(&config.input_path)?;
+        let encoded = Self::validate_input_path(encoded_path)?;
+        // A trimmed encode is compared with the same section of the source.
+        let window = config
+            .trim
+            .and_then(|trim| Some((trim.start, trim.length(video_info.duration_seconds)?)));
+        self.run_quality_measurement(config, video_info, &reference, &encoded, window)
+    }
+
+    /// Run the metric filters; `window` (start, length in seconds) limits the
+    /// reference to the segment an encoded sample was cut from.
+    fn run_quality_measurement(
+        &self,
+        config: &CompressionConfig,
+        video_info: &VideoInfo,
+        reference: &str,
+        encoded: &str,
+        window: Option<(f64, f64)>,
+    ) -> Result<QualityMetrics> {
+        let args = self.build_quality_args(
+            config,
+            video_info,
+            reference,
+            encoded,
+            window,
+            self.has_filter("libvmaf"),
+        )?;
+
+        let output = Command::new(&self.ffmpeg_path)
+            .args(&args)
+            .stdout(Stdio::null())
+            .stderr(Stdio::piped())
+            .output()?;
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        if !output.status.success() {
+            return Err(CompressoError::FfmpegError(stderr.trim().to_string()));
+        }
+
+        let metrics = Self::parse_quality_metrics(&stderr);
+        if metrics.ssim.is_none() && metrics.psnr.is_none() && metrics.vmaf.is_none() {
+            return Err(CompressoError::FfmpegError(
+                "no quality scores in FFmpeg output".to_string(),
+            ));
+        }
+        Ok(metrics)
+    }
+
+    fn build_quality_args(
+        &self,
+        config: &CompressionConfig,
+        video_info: &VideoInfo,
+        reference_path: &str,
+        encoded_path: &str,
+        window: Option<(f64, f64)>,
+        with_vmaf: bool,
+    ) -> Result<Vec<String>> {
+        // The cleanup filters are part of the intended picture too.
+        let mut reference: Vec<String> = Vec::new();
+        if config.filters.deinterlace == Deinterlace::On {
+            reference.push(DEINTERLACE_FILTER.to_string());
+        }
+        self.apply_transforms(&config.transforms, &mut reference);
+        if let Some(level) = config.filters.denoise {
+            reference.push(denoise_filter(level).to_string());
+        }
+        reference.extend(color_filters(&config.filters));
+        if config.filters.sharpen {
+            reference.push(SHARPEN_FILTER.to_string());
+        }
+        // With --speed the frame rate is held after the retiming instead.
+        if let (Some(fps), None) = (config.fps, config.speed) {
+            reference.push(format!("fps={}", fps));
+        }
+        // Compare in SDR when the output was tone-mapped.
+        let output_format = Self::output_format(config, reference_path);
+        if self.hdr_mode(config, video_info, &output_format)? == Some(HdrMode::Tonemap) {
+            reference.push(TONEMAP_FILTERS.to_string());
+        }
+        // Burned-in subtitles are part of the intended picture.
+        let offset = window.map_or(0.0, |(start, _)| start);
+        reference.extend(Self::burn_subtitle_filters(
+            config,
+            video_info,
+            reference_path,
+            offset,
+        )?);
+        // So are the overlays; the watermark is added in the graph below.
+        reference.extend(text_overlay_filter(&config.overlay));
+        reference.extend(Self::speed_filters(config, video_info));
+
+        let mut encoded: Vec<String> = Vec::new();
+        if let Some((w, h)) = video_info
+            .dimensions
+            .map(|dims| Self::transformed_dimensions(&config.transforms, dims))
+        {
+            encoded.push(format!("scale={}:{}:flags=bicubic", w, h));
+        }
+
+        // Both sides: same pixel format and timestamps starting at zero.
+        let metrics: &[&str] = if with_vmaf {
+            &["ssim", "psnr", "libvmaf"]
+        } else {
+            &["ssim", "psnr"]
+        };
+        let mut graph = Vec::new();
+        for (label, mut chain) in [("d", encoded), ("r", reference)] {
+            let outputs: String = (0..metrics.len())
+                .map(|i| format!("[{}{}]", label, i))
+                .collect();
+            // The watermark (third input) goes over the finished reference
+            // picture, as it does over the encode.
+            if label == "r" && config.overlay.image.is_some() {
+                if chain.is_empty() {
+                    chain.push("null".to_string());
+                }
+                graph.push(watermark_graph(
+                    "[1:v]",
+                    &chain.join(","),
+                    &config.overlay,
+                    2,
+                    Some("format=yuv420p,setpts=PTS-STARTPTS"),
+                ));
+                graph.push(format!("[v]split={}{}", metrics.len(), outputs));
+                continue;
+            }
+            chain.push("format=yuv420p".to_string());
+            chain.push("setpts=PTS-STARTPTS".to_string());
+            let input = if label == "d" { 0 } else { 1 };
+            graph.push(format!(
+                "[{}:v]{},split={}{}",
+                input,
+                chain.join(","),
+                metrics.len(),
+                outputs
+            ));
+        }
+        // libvmaf expects the distorted video first and the reference second.
+        for (i, metric) in metrics.iter().enumerate() {
+            graph.push(format!("[d{}][r{}]{}", i, i, metric));
+        }
+
+        let mut args = vec![
+            "-hide_banner".to_string(),
+            "-nostats".to_string(),
+            "-i".to_string(),
+            encoded_path.to_string(),
+        ];
+        if let Some((start, length)) = window {
+            args.extend(["-ss".to_string(), format!("{:.3}", start)]);
+            args.extend(["-t".to_string(), format!("{:.3}", length)]);
+        }
+        args.extend(["-i".to_string(), reference_path.to_string()]);
+        if let Some(image) = &config.overlay.image {
+            args.extend(["-i".to_string(), image.clone()]);
+        }
+        args.extend([
+            "-filter_complex".to_string(),
+            graph.join(";"),
+            "-f".to_string(),
+            "null".to_string(),
+            "-".to_string(),
+        ]);
+        Ok(args)
+    }
+
+    /// Find the highest (cheapest) CRF whose encode still reaches
+    /// `--target-quality`.
+    ///
+    /// A few short segments spread across the video are encoded at each
+    /// candidate CRF and scored against the source; the mean score drives a
+    /// binary search over the codec's CRF range. When no CRF in range reaches
+    /// the target, the best-quality end of the range is returned with
+    /// `target_met = false`.
+    pub fn search_crf(
+        &self,
+        config: &CompressionConfig,
+        video_info: &VideoInfo,
+        cancelled: Arc<AtomicBool>,
+    ) -> Result<CrfSearch> {
+        let target = config.target_quality.ok_or_else(|| {
+            CompressoError::IncompatibleOptions("CRF search needs --target-quality".to_string())
+        })?;
+        if target.metric == QualityMetric::Vmaf && !self.has_filter("libvmaf") {
+            return Err(CompressoError::IncompatibleOptions(
+                "a VMAF --target-quality needs an FFmpeg build with libvmaf; use an SSIM score (0-1) instead"
+                    .to_string(),
+            ));
+        }
+        let duration = Self::section_duration(config, video_info).unwrap_or(0.0);
+        if duration <= 0.0 {
+            return Err(CompressoError::InvalidInput(
+                "could not determine the video duration, which --target-quality needs".to_string(),
+            ));
+        }
+
+        let validated_input = Self::validate_input_path(&config.input_path)?;
+        Self::check_extra_inputs(config)?;
+        let output_format = Self::output_format(config, &validated_input);
+        let codec = config
+            .codec
+            .unwrap_or_else(|| VideoCodec::default_for_format(&output_format));
+        // Windows are sampled from the trimmed section but seek in the source.
+        let offset = config.trim.map_or(0.0, |trim| trim.start);
+        let windows: Vec<(f64, f64)> = Self::sample_windows(duration)
+            .into_iter()
+            .map(|(start, length)| (offset + start, length))
+            .collect();
+
+        // Samples only need the picture: single pass, no audio, no size target.
+        // Overlays stay, since the reference carries them too.
+        let sample_config = CompressionConfig {
+            target_size: None,
+            target_quality: None,
+            two_pass: false,
+            trim: None,
+            mute: true,
+            subtitles: TrackSelection::None,
+            overwrite: true,
+            ..config.clone()
+        };
+
+        let (min_crf, max_crf) = Self::crf_search_range(codec);
+        let (crf, score, target_met) = Self::bisect_crf(min_crf, max_crf, target.score, |crf| {
+            let sample_config = CompressionConfig {
+                crf: Some(crf),
+                ..sample_config.clone()
+            };
+            let mut total = 0.0;
+            for &(start, length) in &windows {
+                total += self.score_sample(
+                    &sample_config,
+                    video_info,
+                    &validated_input,
+                    &output_format,
+                    (start, length),
+                    target.metric,
+                    &cancelled,
+                )?;
+            }
+            let score = total / windows.len() as f64;
+            if config.verbose {
+                eprintln!("ℹ CRF {}: {} {:.4}", crf, target.metric, score);
+            }
+            Ok(score)
+        })?;
+
+        Ok(CrfSearch {
+            metric: target.metric,
+            target: target.score,
+            crf,
+            score,
+            target_met,
+        })
+    }
+
+    /// Encode one sample segment and score it against the same segment of
+    /// the source.
+    #[allow(clippy::too_many_arguments)]
+    fn score_sample(
+        &self,
+        config: &CompressionConfig,
+        video_info: &VideoInfo,
+        input_path: &str,
+        output_format: &str,
+        (start, length): (f64, f64),
+        metric: QualityMetric,
+        cancelled: &Arc<AtomicBool>,
+    ) -> Result<f64> {
+        if cancelled.load(Ordering::Relaxed) {
+            return Err(CompressoError::Cancelled);
+        }
+
+        let sample_path = std::env::temp_dir().join(format!(
+            "compresso-sample-{}.{}",
+            nanoid::nanoid!(8),
+            output_format
+        ));
+        let sample = sample_path.to_string_lossy().to_string();
+        let mut sample_guard = TempFileGuard::new(sample_path);
+
+        let mut args = vec![
+            "-ss".to_string(),
+            format!("{:.3}", start),
+            "-t".to_string(),
+            format!("{:.3}", length),
+        ];
+        args.extend(self.build_args(
+            config,
+            video_info,
+            input_path,
+            &sample,
+            output_format,
+            None,
+        )?);
+
+        // Sample progress is not reported; the receiver is dropped right away.
+        let (tx, _) = crossbeam_channel::unbounded::<(f64, u32)>();
+        self.run_pass(
+            &args,
+            (0.0, 100.0),
+            length,
+            &mut sample_guard,
+            cancelled,
+            tx,
+        )?;
+
+        let metrics = self.run_quality_measurement(
+            config,
+            video_info,
+            input_path,
+            &sample,
+            Some((start, length)),
+        )?;
+        metric.score(&metrics).ok_or_else(|| {
+            CompressoError::FfmpegError(format!("no {} score in FFmpeg output", metric))
         })
+        // sample_guard deletes the sample here
+    }
+
+    /// Segments (start, length in seconds) encoded during the CRF search:
+    /// `CRF_SEARCH_SAMPLES` evenly spaced windows, or the whole video when it
+    /// is too short to be worth sampling.
+    fn sample_windows(duration: f64) -> Vec<(f64, f64)> {
+        let sampled = CRF_SEARCH_SAMPLES as f64 * CRF_SEARCH_SAMPLE_SECONDS;
+        if duration <= sampled * 2.0 {
+            return vec![(0.0, duration)];
+        }
+        (1..=CRF_SEARCH_SAMPLES)
+            .map(|i| {
+                let center = duration * i as f64 / (CRF_SEARCH_SAMPLES + 1) as f64;
+                (
+                    center - CRF_SEARCH_SAMPLE_SECONDS / 2.0,
+                    CRF_SEARCH_SAMPLE_SECONDS,
+                )
+            })
+            .collect()
+    }
+
+    /// Binary search for the highest CRF in `min_crf..=max_crf` whose score
+    /// is at least `target`, assuming the score falls as CRF rises.
+    ///
+    /// Returns `(crf, score, target_met)`; falls back to `min_crf` when no
+    /// candidate passes.
+    fn bisect_crf<F>(
+        min_crf: u16,
+        max_crf: u16,
+        target: f64,
+        mut score_at: F,
+    ) -> Result<(u16, f64, bool)>
+    where
+        F: FnMut(u16) -> Result<f64>,
+    {
+        let mut tried: Vec<(u16, f64)> = Vec::new();
+        let mut best: Option<(u16, f64)> = None;
+        let (mut lo, mut hi) = (min_crf, max_crf);
+
+        while lo <= hi {
+            let mid = lo + (hi - lo) / 2;
+            let score = score_at(mid)?;
+            tried.push((mid, score));
+            if score >= target {
+                best = Some((mid, score));
+                lo = mid + 1;
+            } else if mid == min_crf {
+                break;
+            } else {
+                hi = mid - 1;
+            }
+        }
+
+        match best {
+            Some((crf, score)) => Ok((crf, score, true)),
+            None => {
+                let score = match tried.iter().find(|(crf, _)| *crf == min_crf) {
+                    Some(&(_, score)) => score,
+                    None => score_at(min_crf)?,
+                };
+                Ok((min_crf, score, false))
+            }
+        }
+    }
+
+    /// CRF range explored by `--target-quality`; wider than the `--quality`
+    /// mapping so strict targets can go below its best-quality end.
+    fn crf_search_range(codec: VideoCodec) -> (u16, u16) {
+        match codec {
+            VideoCodec::H264 => (16, 38),
+            VideoCodec::Hevc => (18, 40),
+            VideoCodec::Vp9 => (15, 50),
+            VideoCodec::Av1 => (15, 55),
+        }
+    }
+
+    /// Pull the summary scores the metric filters log when they finish.
+    fn parse_quality_metrics(output: &str) -> QualityMetrics {
+        // SAFETY: all patterns below are compile-time constant literals known
+        // to be valid regexes; these cannot fail.
+        let ssim = SSIM_REGEX.get_or_init(|| Regex::new(r"SSIM .*All:(\d+(?:\.\d+)?)").unwrap());
+        let psnr =
+            PSNR_REGEX.get_or_init(|| Regex::new(r"PSNR .*average:(\d+(?:\.\d+)?)").unwrap());
+        let vmaf = VMAF_REGEX.get_or_init(|| Regex::new(r"VMAF score: (\d+(?:\.\d+)?)").unwrap());
+        let score = |re: &Regex| {
+            re.captures(output)
+                .and_then(|cap| cap[1].parse::<f64>().ok())
+        };
+
+        QualityMetrics {
+            ssim: score(ssim),
+            psnr: score(psnr),
+            vmaf: score(vmaf),
+        }
     }
 
     fn build_args(
         &self,
         config: &CompressionConfig,
+        video_info: &VideoInfo,
         input_path: &str,
         output_path: &str,
         output_format: &str,
-    ) -> Vec<String> {
-        let mut args: Vec<String> = vec![
-            "-i".to_string(),
-            input_path.to_string(),
+        pass: Option<EncodePass<'_>>,
+    ) -> Result<Vec<String>> {
+        let mut args: Vec<String> = Vec::new();
+
+        if let (Some(crop), Some(dims)) = (&config.transforms.crop, video_info.dimensions) {
+            Self::check_crop(crop, Self::rotated_dimensions(&config.transforms, dims))?;
+        }
+        // Copied subtitle tracks would keep the original timing.
+        if config.speed.is_some()
+            && !matches!(
+                config.subtitles,
+                TrackSelection::Default | TrackSelection::None
+            )
+        {
+            return Err(CompressoError::IncompatibleOptions(
+                "--speed cannot keep subtitle tracks, whose timing would no longer match; \
+                 use --burn-subtitles instead"
+                    .to_string(),
+            ));
+        }
+        self.check_text_overlay(config)?;
+        let concat = Self::concat_mode(video_info);
+        Self::check_concat(config, concat)?;
+
+        // Trimming: -ss before -i seeks the input (frame-accurate when
+        // re-encoding) and -t after it limits the output length. The concat
+        // filter joins whole inputs, so there the graph cuts the start.
+        if let Some(trim) = config.trim {
+            Self::check_trim(&trim, video_info)?;
+            if trim.start > 0.0 && concat != Some(ConcatMode::Filter) {
+                args.extend(["-ss".to_string(), format!("{:.3}", trim.start)]);
+            }
+        }
+        match concat {
+            // `input_path` is the list of inputs written by compress_video
+            Some(ConcatMode::Demuxer) => {
+                args.extend(["-f", "concat", "-safe", "0", "-i", input_path].map(String::from))
+            }
+            Some(ConcatMode::Filter) => {
+                for path in
+                    std::iter::once(input_path).chain(config.concat.iter().map(|p| p.as_str()))
+                {
+                    args.extend(["-i".to_string(), path.to_string()]);
+                }
+            }
+            None => args.extend(["-i".to_string(), input_path.to_string()]),
+        }
+        // The watermark is the last input; as a still image it is read whole.
+        if let Some(image) = &config.overlay.image {
+            args.extend(["-i".to_string(), image.clone()]);
+        }
+        args.extend([
             "-hide_banner".to_string(),
             "-progress".to_string(),
             "-".to_string(),
             "-nostats".to_string(),
             "-loglevel".to_string(),
             "error".to_string(),
-        ];
+        ]);
+        // -t counts output time, which --speed stretches or shrinks.
+        if let Some(length) = config
+            .trim
+            .and_then(|trim| trim.length(video_info.duration_seconds))
+        {
+            let length = length / config.speed.unwrap_or(1.0);
+            args.extend(["-t".to_string(), format!("{:.3}", length)]);
+        }
+
+        // Pass 1 only collects statistics: no audio, no file, no muxer flags.
+        let is_final_pass = pass.map_or(true, |p| p.number == 2);
+        let subtitle_codecs =
+            Self::push_stream_maps(&mut args, config, video_info, output_format, is_final_pass)?;
+
+        if Self::should_remux(config, video_info, input_path, output_format)? {
+            Self::push_remux_args(
+                &mut args,
+                config,
+                video_info,
+                output_path,
+                output_format,
+                subtitle_codecs,
+            );
+            return Ok(args);
+        }
 
-        // Calculate CRF from quality (0-100).
-        // Lower CRF = higher quality. Range: 24 (best) to 36 (worst).
-        let max_crf: u16 = 36;
-        let min_crf: u16 = 24;
-        let quality = config.quality.min(100) as u16;
-        let crf = min_crf + (max_crf - min_crf) * (100 - quality) / 100;
-        let crf_str = crf.to_string();
+        // Select the video encoder. An explicit --codec wins; otherwise the
+        // container decides (VP9 for WebM, H.264 for everything else).
+        let codec = config
+            .codec
+            .unwrap_or_else(|| VideoCodec::default_for_format(output_format));
+        let animated = is_animation(output_format);
+        if animated {
+            Self::check_animation(config, output_format)?;
+        } else if !codec.supports_format(output_format) {
+            return Err(CompressoError::IncompatibleOptions(format!(
+                "{} video cannot be stored in a .{} file",
+                codec, output_format
+            )));
+        }
 
-        // Select the video encoder based on the output container.
-        //
         // Each encoder uses a single, consistent quality-control scheme:
         //   - libx264    -> -crf only
+        //   - libx265    -> -crf only (own CRF scale, see crf_for_quality)
         //   - libvpx-vp9 -> -b:v 0 -crf  (VP9 needs -b:v 0 to honor CRF)
+        //   - libsvtav1  -> -crf only
+        //   - libaom-av1 -> -b:v 0 -crf  (same rule as VP9)
         //
         // NOTE: the Ironclad preset previously passed `-qp 0` together with
         // `-crf`. libx264 honors -qp over -crf, and -qp 0 is lossless, so the
         // "quality" preset silently produced files *larger* than the source
         // while ignoring the user's quality setting entirely. Fixed by keeping
         // CRF as the single source of truth for quality.
+        //
+        // With --target-size the CRF is replaced by an average bitrate computed
+        // from the probed duration (see plan_target_bitrate).
         let is_mp4_family = matches!(output_format, "mp4" | "mov" | "m4v");
-
-        if output_format == "webm" {
-            // VP9: libvpx-vp9
-            args.extend(["-c:v".to_string(), "libvpx-vp9".to_string()]);
-            args.extend(["-b:v".to_string(), "0".to_string()]);
-            args.extend(["-crf".to_string(), crf_str]);
-            // VP9 speed/quality is controlled via -deadline and -cpu-used,
-            // not the libx264 -preset option.
-            match config.preset {
-                Preset::Thunderbolt => {
-                    args.extend(["-deadline".to_string(), "good".to_string()]);
-                    args.extend(["-cpu-used".to_string(), "5".to_string()]);
-                }
-                Preset::Ironclad => {
-                    args.extend(["-deadline".to_string(), "best".to_string()]);
-                }
+        // Frame size after transforms and the requested scaling, which is
+        // what the size planning below has to budget for.
+        let frame = video_info
+            .dimensions
+            .map(|dims| Self::transformed_dimensions(&config.transforms, dims));
+        let requested_scale = Self::requested_scale(config, frame);
+        let frame = match (requested_scale, frame) {
+            (Some(scale), Some(frame)) => Some(Self::scaled_dimensions(scale, frame)),
+            _ => frame,
+        };
+        let target = match config.target_size {
+            Some(target_bytes) => {
+                // Every kept audio track gets the same bitrate.
+                let audio_tracks =
+                    Self::select_tracks(&config.audio_tracks, StreamKind::Audio, video_info)?.len()
+                        as u32;
+                let audio_kbps =
+                    Self::audio_bitrate_kbps(config, output_format).unwrap_or(0) * audio_tracks;
+                let fps = config
+                    .fps
+                    .map(|f| f as f64)
+                    .or(video_info.fps.map(|f| f as f64))
+                    .unwrap_or(30.0);
+                // Never override an explicit --width/--height.
+                let allow_downscale = config.width.is_none() && config.height.is_none();
+                Some(Self::plan_target_bitrate(
+                    target_bytes,
+                    Self::output_duration(config, video_info).unwrap_or(0.0),
+                    audio_kbps,
+                    frame.filter(|_| allow_downscale),
+                    fps,
+                )?)
             }
-            args.extend(["-row-mt".to_string(), "1".to_string()]);
-            args.extend(["-pix_fmt".to_string(), "yuv420p".to_string()]);
+            None => None,
+        };
+        let rate = match (target, config.crf) {
+            (Some(plan), _) => RateControl::Bitrate(plan.video_kbps),
+            (None, Some(crf)) => RateControl::Crf(crf),
+            (None, None) => RateControl::Crf(Self::crf_for_quality(codec, config.quality)),
+        };
+
+        // x264/x265 only do two-pass in bitrate mode; VP9 and libaom also
+        // accept CRF (constrained quality), where the first pass still helps.
+        if pass.is_some()
+            && matches!(rate, RateControl::Crf(_))
+            && matches!(codec, VideoCodec::H264 | VideoCodec::Hevc)
+        {
+            return Err(CompressoError::IncompatibleOptions(format!(
+                "--two-pass with {} needs --target-size",
+                codec
+            )));
+        }
+        // yuv420p ensures broad player compatibility (QuickTime, browsers,
+        // etc.); HDR needs 10 bits and its colour tags.
+        let hdr = self.hdr_mode(config, video_info, output_format)?;
+        let hdr10 = video_info
+            .main_video()
+            .filter(|_| hdr == Some(HdrMode::Keep) && video_info.hdr_format() == Some("HDR10"));
+        if animated {
+            self.push_animation_encoder(&mut args, config, output_format)?;
         } else {
-            // H.264 (libx264) for mp4/mov/m4v/avi/mkv
-            args.extend(["-c:v".to_string(), "libx264".to_string()]);
-            args.extend(["-crf".to_string(), crf_str]);
-            match config.preset {
-                Preset::Thunderbolt => {
-                    args.extend(["-preset".to_string(), "ultrafast".to_string()]);
-                    args.extend(["-tune".to_string(), "fastdecode".to_string()]);
-                }
-                Preset::Ironclad => {
-                    args.extend(["-preset".to_string(), "slow".to_string()]);
-                }
-            }
-            // yuv420p ensures broad player compatibility (QuickTime, browsers, etc.)
-            args.extend(["-pix_fmt".to_string(), "yuv420p".to_string()]);
-            // +faststart moves the moov atom to the front for streaming/seeking;
-            // only meaningful for MP4-family containers, harmful for others.
-            if is_mp4_family {
-                args.extend(["-movflags".to_string(), "+faststart".to_string()]);
+            self.push_video_encoder(&mut args, config, codec, rate, pass, hdr10, is_mp4_family)?;
+            // Parts can only begin on a keyframe: force them where
+            // --split-duration cuts, or often enough for --split-size.
+            if let Some(split) = config.split {
+                let interval = match split {
+                    SplitMode::Duration(seconds) => seconds,
+                    SplitMode::Size(_) => SPLIT_KEYFRAME_SECONDS,
+                };
+                args.extend([
+                    "-force_key_frames".to_string(),
+                    format!("expr:gte(t,n_forced*{})", interval),
+                ]);
             }
         }
 
+        // paletteuse hands the GIF encoder ready-made 8-bit palette frames.
+        let pix_fmt = match hdr {
+            Some(HdrMode::Keep) => Some("yuv420p10le"),
+            _ if output_format == "gif" => None,
+            _ => Some("yuv420p"),
+        };
+        if let Some(pix_fmt) = pix_fmt {
+            args.extend(["-pix_fmt".to_string(), pix_fmt.to_string()]);
+        }
+        if let Some(hdr) = hdr {
+            Self::push_color_args(&mut args, hdr, video_info);
+        }
+        // +faststart moves the moov atom to the front for streaming/seeking;
+        // only meaningful for MP4-family containers, harmful for others.
+        if is_mp4_family && is_final_pass {
+            args.extend(["-movflags".to_string(), Self::movflags(config)]);
+        }
+
         // Build video filters
-        let filters = self.build_filters(config);
-        if !filters.is_empty() {
+        // A target-size downscale is planned from the already scaled frame,
+        // so it replaces the requested scale.
+        let scale = target.and_then(|plan| plan.scale).or(requested_scale);
+        let offset = config.trim.map_or(0.0, |trim| trim.start);
+        let mut post_filters = Vec::new();
+        if hdr == Some(HdrMode::Tonemap) {
+            post_filters.push(TONEMAP_FILTERS.to_string());
+        }
+        post_filters.extend(Self::burn_subtitle_filters(
+            config, video_info, input_path, offset,
+        )?);
+        post_filters.extend(text_overlay_filter(&config.overlay));
+        post_filters.extend(Self::speed_filters(config, video_info));
+        let filters = self.build_filters(config, scale, post_filters);
+        // A GIF palette is built from the finished frames.
+        let palette = (output_format == "gif").then(|| gif_palette_filters(config.gif_dither));
+        let audio_filters = if config.mute || !is_final_pass || animated {
+            None
+        } else {
+            Some(Self::audio_filters(config, video_info, output_format)?)
+        };
+        // The watermark image comes after the video inputs.
+        let image_input = match concat {
+            Some(ConcatMode::Filter) => 1 + config.concat.len(),
+            _ => 1,
+        };
+        if concat == Some(ConcatMode::Filter) {
+            // Everything happens in one graph after the inputs are joined.
+            let with_audio = Self::concat_audio(config, video_info, output_format, is_final_pass);
+            let mut graph = Self::concat_graph(video_info, with_audio, offset)?;
+            graph.push(';');
+            if config.overlay.image.is_some() {
+                graph.push_str(&watermark_graph(
+                    "[cv]",
+                    &filters,
+                    &config.overlay,
+                    image_input,
+                    palette.as_deref(),
+                ));
+            } else {
+                let mut chain = if filters.is_empty() {
+                    "null".to_string()
+                } else {
+                    filters.clone()
+                };
+                if let Some(palette) = &palette {
+                    chain = format!("{},{}", chain, palette);
+                }
+                graph.push_str(&format!("[cv]{}[v]", chain));
+            }
+            if with_audio {
+                let chain = audio_filters
+                    .as_deref()
+                    .filter(|f| !f.is_empty())
+                    .map_or_else(|| "anull".to_string(), |f| f.join(","));
+                graph.push_str(&format!(";[ca]{}[a]", chain));
+            }
+            args.extend(["-filter_complex".to_string(), graph]);
+        } else if config.overlay.image.is_some() {
+            // A second input needs a filter graph rather than a -vf chain.
+            args.extend([
+                "-filter_complex".to_string(),
+                watermark_graph(
+                    "[0:V:0]",
+                    &filters,
+                    &config.overlay,
+                    image_input,
+                    palette.as_deref(),
+                ),
+            ]);
+        } else if let Some(palette) = palette {
+            args.extend(["-vf".to_string(), format!("{},{}", filters, palette)]);
+        } else if !filters.is_empty() {
             args.extend(["-vf".to_string(), filters]);
         }
 
@@ -950,238 +2260,5076 @@ impl FFmpeg {
             args.extend(["-r".to_string(), fps.to_string()]);
         }
 
-        // Mute audio
-        if config.mute {
-            args.push("-an".to_string());
+        // Audio
+        match audio_filters {
+            None => args.push("-an".to_string()),
+            // The audio filters are already part of the concat graph.
+            Some(_) if concat == Some(ConcatMode::Filter) => {
+                if config.audio.codec == Some(AudioCodec::Copy) {
+                    return Err(CompressoError::IncompatibleOptions(
+                        "--audio-codec copy needs --concat inputs with the same formats"
+                            .to_string(),
+                    ));
+                }
+                Self::push_audio_args(&mut args, &config.audio, &[], output_format)?;
+            }
+            Some(filters) => {
+                Self::push_audio_args(&mut args, &config.audio, &filters, output_format)?
+            }
         }
+        args.extend(subtitle_codecs);
 
-        // Output path
-        args.push(output_path.to_string());
+        // Output path (the analysis pass discards its output)
+        if is_final_pass {
+            Self::push_metadata_args(&mut args, config, video_info);
+            args.push(output_path.to_string());
+        } else {
+            args.extend(["-f".to_string(), "null".to_string(), "-".to_string()]);
+        }
 
         // Overwrite
         if config.overwrite {
             args.push("-y".to_string());
         }
 
-        args
+        Ok(args)
+    }
+
+    /// Encoder options for `codec`: rate control, two-pass statistics and
+    /// the preset's speed setting. `hdr10` is the source video when its
+    /// HDR10 metadata is carried over.
+    #[allow(clippy::too_many_arguments)]
+    fn push_video_encoder(
+        &self,
+        args: &mut Vec<String>,
+        config: &CompressionConfig,
+        codec: VideoCodec,
+        rate: RateControl,
+        pass: Option<EncodePass<'_>>,
+        hdr10: Option<&StreamInfo>,
+        is_mp4_family: bool,
+    ) -> Result<()> {
+        match codec {
+            VideoCodec::Vp9 => {
+                args.extend(["-c:v".to_string(), "libvpx-vp9".to_string()]);
+                Self::push_rate_control(args, rate, true, true);
+                Self::push_pass(args, pass);
+                // VP9 speed/quality is controlled via -deadline and -cpu-used,
+                // not the libx264 -preset option.
+                match config.preset {
+                    Preset::Thunderbolt => {
+                        args.extend(["-deadline".to_string(), "good".to_string()]);
+                        args.extend(["-cpu-used".to_string(), "5".to_string()]);
+                    }
+                    Preset::Ironclad => {
+                        args.extend(["-deadline".to_string(), "best".to_string()]);
+                    }
+                }
+                args.extend(["-row-mt".to_string(), "1".to_string()]);
+            }
+            VideoCodec::H264 => {
+                args.extend(["-c:v".to_string(), "libx264".to_string()]);
+                Self::push_rate_control(args, rate, false, true);
+                Self::push_pass(args, pass);
+                match config.preset {
+                    Preset::Thunderbolt => {
+                        args.extend(["-preset".to_string(), "ultrafast".to_string()]);
+                        args.extend(["-tune".to_string(), "fastdecode".to_string()]);
+                    }
+                    Preset::Ironclad => {
+                        args.extend(["-preset".to_string(), "slow".to_string()]);
+                    }
+                }
+            }
+            VideoCodec::Av1 => {
+                // SVT-AV1 is an order of magnitude faster than libaom at
+                // comparable quality, so prefer it whenever it is compiled in.
+                if self.has_encoder("libsvtav1") {
+                    if pass.is_some() {
+                        return Err(CompressoError::IncompatibleOptions(
+                            "--two-pass is not supported by the libsvtav1 encoder".to_string(),
+                        ));
+                    }
+                    args.extend(["-c:v".to_string(), "libsvtav1".to_string()]);
+                    // SVT-AV1 rejects -maxrate outside CRF mode, so no VBV.
+                    Self::push_rate_control(args, rate, false, false);
+                    // SVT-AV1 presets run from 0 (slowest) to 13 (fastest).
+                    let svt_preset = match config.preset {
+                        Preset::Thunderbolt => "10",
+                        Preset::Ironclad => "6",
+                    };
+                    args.extend(["-preset".to_string(), svt_preset.to_string()]);
+                } else if self.has_encoder("libaom-av1") {
+                    // libaom, like VP9, needs -b:v 0 to run in pure CRF mode.
+                    args.extend(["-c:v".to_string(), "libaom-av1".to_string()]);
+                    Self::push_rate_control(args, rate, true, true);
+                    Self::push_pass(args, pass);
+                    let cpu_used = match config.preset {
+                        Preset::Thunderbolt => "8",
+                        Preset::Ironclad => "4",
+                    };
+                    args.extend(["-cpu-used".to_string(), cpu_used.to_string()]);
+                    args.extend(["-row-mt".to_string(), "1".to_string()]);
+                } else {
+                    return Err(CompressoError::IncompatibleOptions(
+                        "this FFmpeg build has no AV1 encoder (libsvtav1 or libaom-av1)"
+                            .to_string(),
+                    ));
+                }
+            }
+            VideoCodec::Hevc => {
+                args.extend(["-c:v".to_string(), "libx265".to_string()]);
+                Self::push_rate_control(args, rate, false, true);
+                match config.preset {
+                    Preset::Thunderbolt => {
+                        args.extend(["-preset".to_string(), "ultrafast".to_string()]);
+                        args.extend(["-tune".to_string(), "fastdecode".to_string()]);
+                    }
+                    Preset::Ironclad => {
+                        args.extend(["-preset".to_string(), "slow".to_string()]);
+                    }
+                }
+                // x265 writes its own banner/stats straight to stderr,
+                // ignoring -loglevel; silence it so the piped stderr only
+                // carries real errors. x265 also takes its pass options here
+                // rather than through -pass/-passlogfile.
+                let mut x265_params = "log-level=error".to_string();
+                if let Some(pass) = pass {
+                    x265_params.push_str(&format!(
+                        ":pass={}:stats={}",
+                        pass.number,
+                        Self::escape_x265_param(&format!("{}.log", pass.passlog))
+                    ));
+                }
+                // x265 only writes the mastering display and light level
+                // SEI it is given, so pass on the source's.
+                if let Some(video) = hdr10 {
+                    x265_params.push_str(":hdr10=1");
+                    if let Some(display) = &video.mastering_display {
+                        x265_params.push_str(&format!(":master-display={}", display));
+                    }
+                    if let Some(cll) = &video.max_cll {
+                        x265_params.push_str(&format!(":max-cll={}", cll));
+                    }
+                }
+                args.extend(["-x265-params".to_string(), x265_params]);
+                // Apple players (QuickTime, iOS, Safari) only accept HEVC in
+                // MP4/MOV when the sample entry is tagged `hvc1` instead of
+                // FFmpeg's default `hev1`.
+                if is_mp4_family {
+                    args.extend(["-tag:v".to_string(), "hvc1".to_string()]);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Encoder options for GIF and animated WebP. GIF has no quality knob
+    /// beyond its palette; WebP takes `quality` directly (0-100).
+    fn push_animation_encoder(
+        &self,
+        args: &mut Vec<String>,
+        config: &CompressionConfig,
+        output_format: &str,
+    ) -> Result<()> {
+        if output_format == "gif" {
+            args.extend(["-c:v".to_string(), "gif".to_string()]);
+        } else {
+            let encoder = ["libwebp_anim", "libwebp"]
+                .into_iter()
+                .find(|e| self.has_encoder(e))
+                .ok_or_else(|| {
+                    CompressoError::IncompatibleOptions(
+                        "this FFmpeg build has no WebP encoder (libwebp)".to_string(),
+                    )
+                })?;
+            // compression_level trades encoding time for size (0-6).
+            let level = match config.preset {
+                Preset::Thunderbolt => "2",
+                Preset::Ironclad => "6",
+            };
+            args.extend([
+                "-c:v".to_string(),
+                encoder.to_string(),
+                "-quality".to_string(),
+                config.quality.to_string(),
+                "-compression_level".to_string(),
+                level.to_string(),
+            ]);
+        }
+        // Loop forever, like GIFs on the web.
+        args.extend(["-loop".to_string(), "0".to_string()]);
+        Ok(())
+    }
+
+    /// GIF and WebP previews at the source size and frame rate are huge, so
+    /// unless a size or frame rate is given they are capped at
+    /// `ANIMATION_MAX_EDGE` px on the long edge and `ANIMATION_MAX_FPS`.
+    /// Neither format carries sound.
+    pub fn apply_animation_defaults(
+        config: &mut CompressionConfig,
+        video_info: &VideoInfo,
+        output_format: &str,
+    ) {
+        if !is_animation(output_format) {
+            return;
+        }
+        config.mute = true;
+        if config.width.is_none() && config.height.is_none() && config.max_resolution.is_none() {
+            config.max_resolution = Some(MaxResolution {
+                long: ANIMATION_MAX_EDGE,
+                short: ANIMATION_MAX_EDGE,
+            });
+        }
+        if config.fps.is_none()
+            && video_info
+                .fps
+                .map_or(true, |fps| fps > ANIMATION_MAX_FPS as f32)
+        {
+            config.fps = Some(ANIMATION_MAX_FPS);
+        }
+    }
+
+    /// GIF and WebP have their own encoders and no bitrate control, so the
+    /// options that pick a codec or plan a bitrate do not apply.
+    fn check_animation(config: &CompressionConfig, output_format: &str) -> Result<()> {
+        let option = if config.codec.is_some() {
+            "--codec"
+        } else if config.target_size.is_some() {
+            "--target-size"
+        } else if config.target_quality.is_some() {
+            "--target-quality"
+        } else if config.two_pass {
+            "--two-pass"
+        } else if let Some(split) = config.split {
+            match split {
+                SplitMode::Size(_) => "--split-size",
+                SplitMode::Duration(_) => "--split-duration",
+            }
+        } else {
+            return Ok(());
+        };
+        Err(CompressoError::IncompatibleOptions(format!(
+            "{} does not apply to .{} output",
+            option, output_format
+        )))
+    }
+
+    /// Cut the finished encode `encoded` into `<output>_partNN` files with the
+    /// segment muxer, on the keyframes build_args forced. `--split-size`
+    /// picks the cut points from the packet sizes of the encode.
+    fn split_output(
+        &self,
+        split: SplitMode,
+        encoded: &str,
+        output_path: &str,
+        config: &CompressionConfig,
+        temp_guard: &mut TempFileGuard,
+    ) -> Result<Vec<OutputPart>> {
+        let times = match split {
+            SplitMode::Duration(seconds) => Some(("-segment_time", format!("{:.3}", seconds))),
+            SplitMode::Size(bytes) => {
+                let budget = (bytes as f64 * SPLIT_SIZE_USABLE_FRACTION) as u64;
+                let cuts = Self::split_times(&self.get_packets(encoded)?, budget)?;
+                // A cut point is the time of a keyframe; aim just before it
+                // so that rounding cannot push the cut to the next one.
+                let cuts: Vec<String> = cuts.iter().map(|t| format!("{:.3}", t - 0.001)).collect();
+                (!cuts.is_empty()).then(|| ("-segment_times", cuts.join(",")))
+            }
+        };
+
+        // The parts are numbered next to the temp file first, so a failure
+        // leaves no half-written set behind.
+        let stem = Path::new(encoded)
+            .file_stem()
+            .map(|s| s.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        temp_guard.set_parts_prefix(Path::new(encoded).with_file_name(format!("{}_part", stem)));
+        match times {
+            Some((option, value)) => {
+                let mut args: Vec<String> = [
+                    "-hide_banner",
+                    "-loglevel",
+                    "error",
+                    "-i",
+                    encoded,
+                    "-map",
+                    "0",
+                    "-c",
+                    "copy",
+                    "-f",
+                    "segment",
+                    option,
+                    &value,
+                    "-reset_timestamps",
+                    "1",
+                    "-segment_start_number",
+                    "1",
+                ]
+                .map(String::from)
+                .to_vec();
+                if let Some(extension) = Path::new(output_path).extension() {
+                    if matches!(extension.to_str(), Some("mp4" | "mov" | "m4v")) {
+                        args.extend([
+                            "-segment_format_options".to_string(),
+                            format!("movflags={}", Self::movflags(config)),
+                        ]);
+                    }
+                }
+                args.extend(["-y".to_string(), crate::fs::part_pattern(encoded)]);
+                self.run_analysis(args)?;
+            }
+            // Everything fits into one part.
+            None => std::fs::rename(encoded, crate::fs::part_path(encoded, 1))?,
+        }
+        Self::move_parts(encoded, output_path, config.overwrite)
+    }
+
+    /// Move the parts numbered next to `encoded` to `<output>_partNN`.
+    /// Higher-numbered parts left by an earlier run would make the set
+    /// inconsistent, so they are removed with `-y` and refused without it.
+    /// When a move fails, the parts already moved are taken back.
+    fn move_parts(encoded: &str, output_path: &str, overwrite: bool) -> Result<Vec<OutputPart>> {
+        let exists = |path: &str| Path::new(path).exists();
+        let mut moves = Vec::new();
+        loop {
+            let number = moves.len() + 1;
+            let written = crate::fs::part_path(encoded, number);
+            if !exists(&written) {
+                break;
+            }
+            moves.push((written, crate::fs::part_path(output_path, number)));
+        }
+        if moves.is_empty() {
+            return Err(CompressoError::FfmpegError(
+                "the segment muxer wrote no parts".to_string(),
+            ));
+        }
+        let stale: Vec<String> = (moves.len() + 1..)
+            .map(|number| crate::fs::part_path(output_path, number))
+            .take_while(|part| exists(part))
+            .collect();
+        if !overwrite {
+            let mut taken = moves.iter().map(|(_, part)| part).chain(&stale);
+            if let Some(part) = taken.find(|part| exists(part)) {
+                return Err(CompressoError::InvalidOutput(format!(
+                    "File already exists: {}. Use -y to overwrite.",
+                    part
+                )));
+            }
+        }
+
+        for (i, (from, to)) in moves.iter().enumerate() {
+            if let Err(e) = std::fs::rename(from, to) {
+                for (from, to) in &moves[..i] {
+                    let _ = std::fs::rename(to, from);
+                }
+                return Err(e.into());
+            }
+        }
+        for part in &stale {
+            std::fs::remove_file(part)?;
+        }
+        let mut parts = Vec::new();
+        for (_, path) in moves {
+            let size = std::fs::metadata(&path)?.len();
+            parts.push(OutputPart { path, size });
+        }
+        Ok(parts)
+    }
+
+    /// Where `--split-size` cuts: on the last video keyframe before a part
+    /// would hold more than `budget` bytes of packets. Fails when there is
+    /// no such keyframe, i.e. a single GOP is larger than the budget.
+    fn split_times(packets: &[PacketInfo], budget: u64) -> Result<Vec<f64>> {
+        let mut cuts = Vec::new();
+        // Time and running byte count at the start of the current part and
+        // at its latest keyframe
+        let mut part_start = (0.0, 0u64);
+        let mut last_keyframe: Option<(f64, u64)> = None;
+        let mut total = 0u64;
+        for packet in packets {
+            if packet.keyframe && packet.time > part_start.0 {
+                last_keyframe = Some((packet.time, total));
+            }
+            total += packet.size;
+            while total - part_start.1 > budget {
+                let Some(cut) = last_keyframe.take() else {
+                    return Err(CompressoError::IncompatibleOptions(format!(
+                        "--split-size is smaller than the video between two keyframes after {}; \
+                         use a larger size or a lower quality",
+                        format_timestamp(part_start.0)
+                    )));
+                };
+                cuts.push(cut.0);
+                part_start = cut;
+            }
+        }
+        Ok(cuts)
+    }
+
+    /// Give `to` the modification time of `from`.
+    fn copy_mtime(from: &str, to: &str) -> std::io::Result<()> {
+        let modified = std::fs::metadata(from)?.modified()?;
+        filetime::set_file_mtime(to, filetime::FileTime::from_system_time(modified))
+    }
+
+    /// Seconds of the source that end up in the output (the trimmed length
+    /// when `--start/--end/--duration` are set).
+    fn section_duration(config: &CompressionConfig, video_info: &VideoInfo) -> Option<f64> {
+        match config.trim {
+            Some(trim) => trim.length(video_info.duration_seconds),
+            None => video_info.duration_seconds,
+        }
+    }
+
+    /// Length of the output, i.e. the section played back at `--speed`.
+    fn output_duration(config: &CompressionConfig, video_info: &VideoInfo) -> Option<f64> {
+        Self::section_duration(config, video_info).map(|d| d / config.speed.unwrap_or(1.0))
+    }
+
+    /// `--speed` for the video: retime the frames, then hold the frame rate
+    /// by dropping (or repeating) frames. Goes last, after filters that
+    /// work on source timestamps such as subtitles.
+    fn speed_filters(config: &CompressionConfig, video_info: &VideoInfo) -> Vec<String> {
+        let Some(speed) = config.speed else {
+            return Vec::new();
+        };
+        let mut filters = vec![format!("setpts=PTS/{}", speed)];
+        let fps = config.fps.map(|f| f as f32).or(video_info.fps);
+        if let Some(fps) = fps {
+            filters.push(format!("fps={}", fps));
+        }
+        filters
+    }
+
+    /// `--speed` for the audio: atempo keeps the pitch but only takes factors
+    /// from 0.5 to 2.0, so larger changes are chained (8x = 2 * 2 * 2).
+    fn atempo_filters(speed: f64) -> Vec<String> {
+        let mut filters = Vec::new();
+        let mut remaining = speed;
+        while remaining > 2.0 {
+            filters.push("atempo=2".to_string());
+            remaining /= 2.0;
+        }
+        while remaining < 0.5 {
+            filters.push("atempo=0.5".to_string());
+            remaining /= 0.5;
+        }
+        if (remaining - 1.0).abs() > 1e-9 {
+            filters.push(format!("atempo={}", (remaining * 1e6).round() / 1e6));
+        }
+        filters
+    }
+
+    /// The audio filter chain: the `--speed` change, then loudness
+    /// normalization, volume and fades. Fades are timed on the output.
+    fn audio_filters(
+        config: &CompressionConfig,
+        video_info: &VideoInfo,
+        output_format: &str,
+    ) -> Result<Vec<String>> {
+        let mut filters = config.speed.map(Self::atempo_filters).unwrap_or_default();
+        let audio_filters = &config.audio_filters;
+        if audio_filters.normalize {
+            filters.push(Self::loudnorm_filter(audio_filters.loudness.as_ref()));
+            // loudnorm resamples to 192 kHz; come back down unless -ar will.
+            if config.audio.sample_rate.is_none() {
+                let source_rate = video_info
+                    .streams
+                    .iter()
+                    .find(|s| s.kind == StreamKind::Audio)
+                    .and_then(|s| s.sample_rate);
+                let codec = config
+                    .audio
+                    .codec
+                    .unwrap_or_else(|| AudioCodec::default_for_format(output_format));
+                let rate = match source_rate {
+                    Some(rate)
+                        if codec != AudioCodec::Opus || OPUS_SAMPLE_RATES.contains(&rate) =>
+                    {
+                        rate
+                    }
+                    _ => 48000,
+                };
+                filters.push(format!("aresample={}", rate));
+            }
+        }
+        if let Some(db) = audio_filters.volume_db {
+            filters.push(format!("volume={}dB", db));
+        }
+        if let Some(length) = audio_filters.fade_in {
+            filters.push(format!("afade=t=in:st=0:d={}", length));
+        }
+        if let Some(length) = audio_filters.fade_out {
+            let Some(duration) = Self::output_duration(config, video_info) else {
+                return Err(CompressoError::IncompatibleOptions(
+                    "--audio-fade-out needs the length of the video, which could not be read"
+                        .to_string(),
+                ));
+            };
+            filters.push(format!(
+                "afade=t=out:st={:.3}:d={}",
+                (duration - length).max(0.0),
+                length
+            ));
+        }
+        Ok(filters)
+    }
+
+    /// loudnorm for the encode: a linear gain from the first-pass
+    /// measurement, or a single dynamic pass without one.
+    fn loudnorm_filter(stats: Option<&LoudnessStats>) -> String {
+        match stats {
+            Some(stats) => format!(
+                "loudnorm={}:measured_I={}:measured_TP={}:measured_LRA={}:measured_thresh={}:offset={}:linear=true",
+                LOUDNORM_TARGET,
+                stats.input_i,
+                stats.input_tp,
+                stats.input_lra,
+                stats.input_thresh,
+                stats.target_offset
+            ),
+            None => format!("loudnorm={}", LOUDNORM_TARGET),
+        }
+    }
+
+    /// First pass of `--normalize-audio`: run loudnorm over the first kept
+    /// audio track (trimmed and retimed like the encode) and read back its
+    /// statistics. None when there is nothing to measure: no audio, or only
+    /// silence.
+    pub fn measure_loudness(
+        &self,
+        config: &CompressionConfig,
+        video_info: &VideoInfo,
+    ) -> Result<Option<LoudnessStats>> {
+        if video_info.audio_codec.is_none() {
+            return Ok(None);
+        }
+        let tracks = Self::select_tracks(&config.audio_tracks, StreamKind::Audio, video_info)?;
+        let Some((track, _)) = tracks.into_iter().next() else {
+            return Ok(None);
+        };
+        let input = Self::validate_input_path(&config.input_path)?;
+        let stderr = self.run_analysis(Self::build_loudness_args(
+            config, video_info, &input, &track,
+        ))?;
+        Ok(Self::parse_loudnorm(&stderr))
+    }
+
+    fn build_loudness_args(
+        config: &CompressionConfig,
+        video_info: &VideoInfo,
+        input_path: &str,
+        track: &str,
+    ) -> Vec<String> {
+        let mut args = vec!["-hide_banner".to_string(), "-nostats".to_string()];
+        if let Some(trim) = config.trim.filter(|trim| trim.start > 0.0) {
+            args.extend(["-ss".to_string(), format!("{:.3}", trim.start)]);
+        }
+        args.extend(["-i".to_string(), input_path.to_string()]);
+        if let Some(length) = config
+            .trim
+            .and_then(|trim| trim.length(video_info.duration_seconds))
+        {
+            let length = length / config.speed.unwrap_or(1.0);
+            args.extend(["-t".to_string(), format!("{:.3}", length)]);
+        }
+        let mut filters = config.speed.map(Self::atempo_filters).unwrap_or_default();
+        filters.push(format!("loudnorm={}:print_format=json", LOUDNORM_TARGET));
+        args.extend([
+            "-map".to_string(),
+            track.to_string(),
+            "-af".to_string(),
+            filters.join(","),
+            "-vn".to_string(),
+            "-sn".to_string(),
+            "-dn".to_string(),
+            "-f".to_string(),
+            "null".to_string(),
+            "-".to_string(),
+        ]);
+        args
+    }
+
+    /// Read the JSON block loudnorm prints at the end of its first pass.
+    /// Silence measures as `-inf`, which leaves nothing to normalize.
+    fn parse_loudnorm(stderr: &str) -> Option<LoudnessStats> {
+        #[derive(serde::Deserialize)]
+        struct Measurement {
+            input_i: String,
+            input_tp: String,
+            input_lra: String,
+            input_thresh: String,
+            target_offset: String,
+        }
+
+        let start = stderr.rfind('{')?;
+        let end = start + stderr[start..].find('}')?;
+        let m: Measurement = serde_json::from_str(&stderr[start..=end]).ok()?;
+        let value = |v: &str| v.trim().parse::<f64>().ok().filter(|v| v.is_finite());
+        Some(LoudnessStats {
+            input_i: value(&m.input_i)?,
+            input_tp: value(&m.input_tp)?,
+            input_lra: value(&m.input_lra)?,
+            input_thresh: value(&m.input_thresh)?,
+            target_offset: value(&m.target_offset)?,
+        })
+    }
+
+    /// Thumbnail mode: extract the requested stills and return every file
+    /// written. All paths are checked before FFmpeg runs, so an existing
+    /// file (without `-y`) stops the run before anything is written.
+    pub fn generate_stills(
+        &self,
+        request: &ThumbnailRequest,
+        video_info: &VideoInfo,
+    ) -> Result<Vec<GeneratedFile>> {
+        let input = Self::validate_input_path(&request.input_path)?;
+        let duration = || {
+            video_info.duration_seconds.ok_or_else(|| {
+                CompressoError::InvalidInput(format!(
+                    "cannot space frames evenly: the duration of {} is unknown",
+                    request.input_path
+                ))
+            })
+        };
+        if let (Some(poster), Some(total)) = (request.poster, video_info.duration_seconds) {
+            if poster >= total {
+                return Err(CompressoError::InvalidInput(format!(
+                    "--poster {} is past the end of the video ({})",
+                    format_timestamp(poster),
+                    format_timestamp(total)
+                )));
+            }
+        }
+        if request.format == ImageFormat::Webp && !self.has_encoder("libwebp") {
+            return Err(CompressoError::IncompatibleOptions(
+                "--image-format webp needs an FFmpeg build with libwebp".to_string(),
+            ));
+        }
+
+        let extension = request.format.extension();
+        let still = |kind, name: &str, extension: &str, timestamp| -> Result<GeneratedFile> {
+            Ok(GeneratedFile {
+                kind,
+                path: Self::still_path(request, &input, name, extension)?,
+                timestamp,
+            })
+        };
+        let mut files = Vec::new();
+        let mut thumbnail_times = Vec::new();
+        if let Some(count) = request.thumbnails {
+            thumbnail_times = Self::spaced_timestamps(duration()?, count);
+            for (i, t) in thumbnail_times.iter().enumerate() {
+                let name = format!("thumb_{:03}", i + 1);
+                files.push(still(StillKind::Thumbnail, &name, extension, Some(*t))?);
+            }
+            if request.sprite {
+                // The WebVTT offsets need the tile size up front.
+                Self::tile_size(video_info, request.width)?;
+                files.push(still(StillKind::Sprite, "sprite", extension, None)?);
+                files.push(still(StillKind::Vtt, "thumbnails", "vtt", None)?);
+            }
+        }
+        if let Some(t) = request.poster {
+            files.push(still(StillKind::Poster, "poster", extension, Some(t))?);
+        }
+        if request.contact_sheet.is_some() {
+            duration()?;
+            files.push(still(
+                StillKind::ContactSheet,
+                "contact_sheet",
+                extension,
+                None,
+            )?);
+        }
+
+        for file in &files {
+            Self::validate_output_path(&file.path)?;
+            if !request.overwrite && Path::new(&file.path).exists() {
+                return Err(CompressoError::InvalidOutput(format!(
+                    "{} already exists (use -y to overwrite)",
+                    file.path
+                )));
+            }
+        }
+
+        let scaled = self.still_filters(video_info, Some(request.width), None);
+        let full_size = self.still_filters(video_info, None, None);
+        for file in &files {
+            let at = file.timestamp.unwrap_or(0.0);
+            match file.kind {
+                StillKind::Thumbnail => self.extract_still(&input, at, &scaled, &file.path)?,
+                StillKind::Poster => self.extract_still(&input, at, &full_size, &file.path)?,
+                StillKind::Sprite => {
+                    let grid = Self::sprite_grid(thumbnail_times.len() as u32);
+                    self.tile_stills(
+                        &input,
+                        video_info,
+                        &thumbnail_times,
+                        request.width,
+                        grid,
+                        false,
+                        &file.path,
+                    )?
+                }
+                StillKind::Vtt => {
+                    let tile = Self::tile_size(video_info, request.width)?;
+                    let sprite = files
+                        .iter()
+                        .find(|f| f.kind == StillKind::Sprite)
+                        .and_then(|f| Path::new(&f.path).file_name())
+                        .map(|name| name.to_string_lossy().into_owned())
+                        .unwrap_or_default();
+                    let (cols, _) = Self::sprite_grid(thumbnail_times.len() as u32);
+                    let vtt = Self::sprite_vtt(
+                        &sprite,
+                        duration()?,
+                        thumbnail_times.len() as u32,
+                        cols,
+                        tile,
+                    );
+                    std::fs::write(&file.path, vtt)?;
+                }
+                StillKind::ContactSheet => {
+                    let (cols, rows) = request.contact_sheet.unwrap_or((1, 1));
+                    let times = Self::spaced_timestamps(duration()?, cols * rows);
+                    self.tile_stills(
+                        &input,
+                        video_info,
+                        &times,
+                        request.width,
+                        (cols, rows),
+                        true,
+                        &file.path,
+                    )?
+                }
+            }
+        }
+        Ok(files)
+    }
+
+    /// Where a still goes: `<stem>_<name>.<extension>` next to the input, or
+    /// in the `-o` directory.
+    fn still_path(
+        request: &ThumbnailRequest,
+        input: &str,
+        name: &str,
+        extension: &str,
+    ) -> Result<String> {
+        let path = crate::fs::generate_sibling_path(input, name, extension)?;
+        Ok(match &request.output_dir {
+            Some(dir) => Path::new(dir)
+                .join(Path::new(&path).file_name().unwrap_or_default())
+                .to_string_lossy()
+                .into_owned(),
+            None => path,
+        })
+    }
+
+    /// `count` timestamps at the middle of equal stretches of the video, so
+    /// neither the first nor the last frame (often black) is picked.
+    fn spaced_timestamps(duration: f64, count: u32) -> Vec<f64> {
+        (0..count)
+            .map(|i| duration * (i as f64 + 0.5) / count as f64)
+            .collect()
+    }
+
+    /// Columns and rows of a roughly square sprite holding `count` tiles
+    fn sprite_grid(count: u32) -> (u32, u32) {
+        let count = count.max(1);
+        let cols = (count as f64).sqrt().ceil() as u32;
+        (cols, (count + cols - 1) / cols)
+    }
+
+    /// Size of one tile `width` pixels wide, keeping the aspect ratio and an
+    /// even height
+    fn tile_size(video_info: &VideoInfo, width: u32) -> Result<(u32, u32)> {
+        let (w, h) = video_info.dimensions.ok_or_else(|| {
+            CompressoError::InvalidInput(
+                "cannot size the sprite tiles: no video stream".to_string(),
+            )
+        })?;
+        let height = ((h as f64 * width as f64 / w.max(1) as f64 / 2.0).round() as u32 * 2).max(2);
+        Ok((width, height))
+    }
+
+    /// Tone-mapping for HDR sources, a scale to `width` and a timestamp
+    /// label, as far as each applies.
+    fn still_filters(
+        &self,
+        video_info: &VideoInfo,
+        width: Option<u32>,
+        label: Option<f64>,
+    ) -> String {
+        let mut filters = Vec::new();
+        if video_info.hdr_format().is_some() && self.has_filter("zscale") {
+            filters.push(TONEMAP_FILTERS.to_string());
+        }
+        if let Some(width) = width {
+            match Self::tile_size(video_info, width) {
+                Ok((w, h)) => filters.push(format!("scale={}:{}", w, h)),
+                Err(_) => filters.push(format!("scale={}:-2", width)),
+            }
+        }
+        // Unquoted, like --text-overlay: both escape levels are undone.
+        if let Some(t) = label {
+            filters.push(format!(
+                "drawtext=text={}:expansion=none:fontcolor=white:fontsize=h/10:box=1:boxcolor=black@0.6:\
+                 boxborderw=4:x=w-tw-8:y=h-th-8",
+                escape_filter_value(&format_clock(t))
+            ));
+        }
+        filters.join(",")
+    }
+
+    /// Decode one frame at `timestamp` and write it as an image whose
+    /// format follows the extension of `output`.
+    fn still_args(input_path: &str, timestamp: f64, filters: &str, output: &str) -> Vec<String> {
+        let mut args: Vec<String> = [
+            "-hide_banner",
+            "-loglevel",
+            "error",
+            "-ss",
+            &format!("{:.3}", timestamp),
+            "-i",
+            input_path,
+            "-frames:v",
+            "1",
+        ]
+        .iter()
+        .map(|s| s.to_string())
+        .collect();
+        if !filters.is_empty() {
+            args.extend(["-vf".to_string(), filters.to_string()]);
+        }
+        args.extend(["-an", "-sn", "-dn"].map(String::from));
+        Self::push_image_encoder(&mut args, output);
+        args
+    }
+
+    /// Tile numbered PNG frames (`%04d.png` in `dir`) into one image.
+    /// Contact sheets get a small gap between tiles; sprites must not, so
+    /// that the WebVTT offsets are exact multiples of the tile size.
+    fn tile_args(dir: &Path, (cols, rows): (u32, u32), padded: bool, output: &str) -> Vec<String> {
+        let tile = if padded {
+            format!("tile={}x{}:padding=4:margin=4", cols, rows)
+        } else {
+            format!("tile={}x{}", cols, rows)
+        };
+        let mut args = vec![
+            "-hide_banner".to_string(),
+            "-loglevel".to_string(),
+            "error".to_string(),
+            "-framerate".to_string(),
+            "1".to_string(),
+            "-i".to_string(),
+            dir.join("%04d.png").to_string_lossy().into_owned(),
+            "-vf".to_string(),
+            tile,
+            "-frames:v".to_string(),
+            "1".to_string(),
+        ];
+        Self::push_image_encoder(&mut args, output);
+        args
+    }
+
+    /// Encoder options for a single image, chosen by the file extension
+    fn push_image_encoder(args: &mut Vec<String>, output: &str) {
+        let extension = Path::new(output)
+            .extension()
+            .and_then(|e| e.to_str())
+            .unwrap_or_default()
+            .to_ascii_lowercase();
+        let encoder: &[&str] = match extension.as_str() {
+            "webp" => &["-c:v", "libwebp", "-quality", "80"],
+            "png" => &[],
+            // mjpeg: 2 is near the top of its 2-31 scale
+            _ => &["-q:v", "2"],
+        };
+        args.extend(encoder.iter().map(|s| s.to_string()));
+        args.extend(["-update", "1", "-y", output].map(String::from));
+    }
+
+    fn extract_still(
+        &self,
+        input: &str,
+        timestamp: f64,
+        filters: &str,
+        output: &str,
+    ) -> Result<()> {
+        self.run_analysis(Self::still_args(input, timestamp, filters, output))?;
+        // Seeking to the very end decodes nothing, and FFmpeg still exits 0.
+        if !Path::new(output).exists() {
+            return Err(CompressoError::FfmpegError(format!(
+                "no frame could be decoded at {}",
+                format_timestamp(timestamp)
+            )));
+        }
+        Ok(())
+    }
+
+    /// Extract a frame at each of `timestamps` into a temporary directory,
+    /// then tile them row by row into `output`. A contact sheet labels each
+    /// tile with its timestamp when FFmpeg can draw text; without drawtext
+    /// and a default font it is still useful unlabelled.
+    #[allow(clippy::too_many_arguments)]
+    fn tile_stills(
+        &self,
+        input: &str,
+        video_info: &VideoInfo,
+        timestamps: &[f64],
+        width: u32,
+        grid: (u32, u32),
+        contact_sheet: bool,
+        output: &str,
+    ) -> Result<()> {
+        let labels = contact_sheet && self.has_filter("drawtext") && self.has_fontconfig();
+        let dir = std::env::temp_dir().join(format!("compresso-stills-{}", nanoid::nanoid!(8)));
+        std::fs::create_dir(&dir)?;
+        let result = (|| {
+            for (i, t) in timestamps.iter().enumerate() {
+                let frame = dir.join(format!("{:04}.png", i + 1));
+                let filters = self.still_filters(video_info, Some(width), labels.then_some(*t));
+                self.extract_still(input, *t, &filters, &frame.to_string_lossy())?;
+            }
+            self.run_analysis(Self::tile_args(&dir, grid, contact_sheet, output))
+        })();
+        let _ = std::fs::remove_dir_all(&dir);
+        result.map(|_| ())
+    }
+
+    /// WebVTT track that points each stretch of the video at its tile in
+    /// the sprite (`sprite.jpg#xywh=x,y,w,h`)
+    fn sprite_vtt(
+        sprite: &str,
+        duration: f64,
+        count: u32,
+        cols: u32,
+        (w, h): (u32, u32),
+    ) -> String {
+        let mut vtt = String::from("WEBVTT\n");
+        for i in 0..count {
+            let start = duration * i as f64 / count as f64;
+            let end = duration * (i + 1) as f64 / count as f64;
+            vtt.push_str(&format!(
+                "\n{} --> {}\n{}#xywh={},{},{},{}\n",
+                format_timestamp(start),
+                format_timestamp(end),
+                sprite,
+                i % cols * w,
+                i / cols * h,
+                w,
+                h
+            ));
+        }
+        vtt
+    }
+
+    /// Reject trims that leave nothing to encode.
+    fn check_trim(trim: &TrimRange, video_info: &VideoInfo) -> Result<()> {
+        if let Some(total) = video_info.duration_seconds {
+            if trim.start >= total {
+                return Err(CompressoError::IncompatibleOptions(format!(
+                    "--start {} is past the end of the video ({})",
+                    format_timestamp(trim.start),
+                    format_timestamp(total)
+                )));
+            }
+        }
+        if trim.end.is_some_and(|end| end <= trim.start) {
+            return Err(CompressoError::IncompatibleOptions(
+                "--end must be after --start".to_string(),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Decide whether to copy the streams instead of re-encoding them.
+    ///
+    /// `--remux` insists and rejects anything that needs decoded frames. Auto
+    /// mode only copies for a bare container change, and only when the probed
+    /// source codecs are known to fit the new container; anything else falls
+    /// back to a normal encode.
+    fn should_remux(
+        config: &CompressionConfig,
+        video_info: &VideoInfo,
+        input_path: &str,
+        output_format: &str,
+    ) -> Result<bool> {
+        let transforms = &config.transforms;
+        let needs_frames = config.width.is_some()
+            || config.height.is_some()
+            || config.max_resolution.is_some()
+            || config.fps.is_some()
+            || transforms.crop.is_some()
+            || transforms.rotate.is_some()
+            || transforms.flip.is_some()
+            || config.burn_subtitles.is_some()
+            || config.filters.is_active()
+            || config.speed.is_some()
+            || config.overlay.is_active()
+            || (config.hdr == Some(HdrMode::Tonemap) && video_info.hdr_format().is_some());
+        let audio_filtered = !config.mute && config.audio_filters.is_active();
+        let video_codec = video_info
+            .video_codec
+            .as_deref()
+            .and_then(|c| c.parse::<VideoCodec>().ok());
+        // Codec names of the kept audio tracks (the main track's when the
+        // file was not probed per stream); None means no audio.
+        let audio_codecs: Vec<Option<String>> = if config.mute {
+            Vec::new()
+        } else {
+            Self::select_tracks(&config.audio_tracks, StreamKind::Audio, video_info)?
+                .into_iter()
+                .map(|(_, stream)| match stream {
+                    Some(stream) => stream.codec.clone(),
+                    None => video_info.audio_codec.clone(),
+                })
+                .collect()
+        };
+        let audio_fits = |name: &str| {
+            name.parse::<AudioCodec>()
+                .is_ok_and(|codec| codec.supports_format(output_format))
+        };
+
+        match config.remux {
+            RemuxMode::Never => Ok(false),
+            RemuxMode::Always => {
+                if Self::concat_mode(video_info) == Some(ConcatMode::Filter) {
+                    return Err(CompressoError::IncompatibleOptions(
+                        "--remux can only join inputs whose streams have the same codecs, \
+                         size, frame rate and audio format"
+                            .to_string(),
+                    ));
+                }
+                if needs_frames {
+                    return Err(CompressoError::IncompatibleOptions(
+                        "--remux copies the streams as-is, so it cannot be combined with \
+                         options that change the picture (size, --fps, --speed, transforms, \
+                         filters, burned-in subtitles, overlays or --hdr tonemap)"
+                            .to_string(),
+                    ));
+                }
+                if config.two_pass
+                    || config.target_size.is_some()
+                    || config.target_quality.is_some()
+                    || !config.audio.is_default()
+                    || audio_filtered
+                {
+                    return Err(CompressoError::IncompatibleOptions(
+                        "--remux cannot be combined with encoding options".to_string(),
+                    ));
+                }
+                if let Some(codec) = video_codec.filter(|c| !c.supports_format(output_format)) {
+                    return Err(CompressoError::IncompatibleOptions(format!(
+                        "the source {} video cannot be copied into a .{} file",
+                        codec, output_format
+                    )));
+                }
+                // Only reject codecs known not to fit; FFmpeg has the final say
+                // on the rest.
+                if let Some(codec) = audio_codecs
+                    .iter()
+                    .flatten()
+                    .filter_map(|name| name.parse::<AudioCodec>().ok())
+                    .find(|codec| !codec.supports_format(output_format))
+                {
+                    return Err(CompressoError::IncompatibleOptions(format!(
+                        "the source {} audio cannot be copied into a .{} file (use --mute)",
+                        codec, output_format
+                    )));
+                }
+                Ok(true)
+            }
+            RemuxMode::Auto => {
+                let input_format = Path::new(input_path)
+                    .extension()
+                    .and_then(|e| e.to_str())
+                    .map(|e| e.to_lowercase());
+                let container_changes = input_format.as_deref() != Some(output_format);
+                // Copied streams can only be cut on keyframes, so trimming
+                // re-encodes unless --remux asks for the copy explicitly.
+                let needs_frames = needs_frames || config.trim.is_some();
+                let video_fits = video_codec.is_some_and(|c| c.supports_format(output_format));
+                let audio_fits = audio_codecs
+                    .iter()
+                    .all(|name| name.as_deref().map_or(true, audio_fits));
+                // Joining always encodes unless --remux asks for a copy.
+                Ok(container_changes
+                    && video_info.parts.is_empty()
+                    && !needs_frames
+                    && !audio_filtered
+                    && video_fits
+                    && audio_fits)
+            }
+        }
+    }
+
+    /// Arguments for a stream copy into the output container. Subtitles are
+    /// still converted when the container needs another format.
+    fn push_remux_args(
+        args: &mut Vec<String>,
+        config: &CompressionConfig,
+        video_info: &VideoInfo,
+        output_path: &str,
+        output_format: &str,
+        subtitle_codecs: Vec<String>,
+    ) {
+        args.extend(["-c".to_string(), "copy".to_string()]);
+        if config.mute {
+            args.push("-an".to_string());
+        }
+        // Per-stream codecs must follow the blanket `-c copy` to win.
+        args.extend(subtitle_codecs);
+        if matches!(output_format, "mp4" | "mov" | "m4v") {
+            // Same Apple `hvc1` tagging rule as for encoded HEVC.
+            if video_info.video_codec.as_deref() == Some("hevc") {
+                args.extend(["-tag:v".to_string(), "hvc1".to_string()]);
+            }
+            args.extend(["-movflags".to_string(), Self::movflags(config)]);
+        }
+        Self::push_metadata_args(args, config, video_info);
+        args.push(output_path.to_string());
+        if config.overwrite {
+            args.push("-y".to_string());
+        }
+    }
+
+    /// MP4/MOV muxer flags. Keeping metadata also keeps custom tags such as
+    /// Apple's GPS location, which the muxer otherwise drops.
+    fn movflags(config: &CompressionConfig) -> String {
+        match config.metadata {
+            MetadataMode::Keep => "+faststart+use_metadata_tags".to_string(),
+            MetadataMode::Strip | MetadataMode::Minimal => "+faststart".to_string(),
+        }
+    }
+
+    /// `--metadata`: FFmpeg copies container metadata by default and stream
+    /// metadata along with the streams; each kind is switched off separately.
+    fn push_metadata_args(
+        args: &mut Vec<String>,
+        config: &CompressionConfig,
+        video_info: &VideoInfo,
+    ) {
+        match config.metadata {
+            MetadataMode::Keep => {
+                args.extend(["-map_metadata".to_string(), "0".to_string()]);
+            }
+            MetadataMode::Strip => {
+                args.extend([
+                    "-map_metadata".to_string(),
+                    "-1".to_string(),
+                    "-map_metadata:s".to_string(),
+                    "-1".to_string(),
+                    "-map_chapters".to_string(),
+                    "-1".to_string(),
+                ]);
+            }
+            // Stream tags (language, title) are kept; GPS, camera and
+            // software tags live in the container metadata.
+            MetadataMode::Minimal => {
+                args.extend(["-map_metadata".to_string(), "-1".to_string()]);
+                if let Some(created) = &video_info.creation_time {
+                    args.extend([
+                        "-metadata".to_string(),
+                        format!("creation_time={}", created),
+                    ]);
+                }
+            }
+        }
+    }
+
+    /// Map the output streams explicitly instead of relying on FFmpeg's pick
+    /// (one video, one audio, sometimes one subtitle track): the main video
+    /// stream, never cover art (`V` skips attached pictures), then the
+    /// selected audio and subtitle tracks. Pass 1 of a two-pass encode maps
+    /// only the video.
+    ///
+    /// Returns the subtitle codec options, which have to come after any
+    /// blanket `-c` option.
+    fn push_stream_maps(
+        args: &mut Vec<String>,
+        config: &CompressionConfig,
+        video_info: &VideoInfo,
+        output_format: &str,
+        is_final_pass: bool,
+    ) -> Result<Vec<String>> {
+        // A watermark or concat graph labels its output; otherwise map the
+        // main video (capital V skips cover art).
+        let joined = Self::concat_mode(video_info) == Some(ConcatMode::Filter);
+        let video = if config.overlay.image.is_some() || joined {
+            "[v]"
+        } else {
+            "0:V:0"
+        };
+        args.extend(["-map".to_string(), video.to_string()]);
+        if !is_final_pass {
+            return Ok(Vec::new());
+        }
+        if joined {
+            if Self::concat_audio(config, video_info, output_format, is_final_pass) {
+                args.extend(["-map".to_string(), "[a]".to_string()]);
+            }
+            return Ok(Vec::new());
+        }
+
+        if !config.mute {
+            for (spec, _) in
+                Self::select_tracks(&config.audio_tracks, StreamKind::Audio, video_info)?
+            {
+                args.extend(["-map".to_string(), spec]);
+            }
+        }
+
+        let mut codecs = Vec::new();
+        let subtitles = Self::select_tracks(&config.subtitles, StreamKind::Subtitle, video_info)?;
+        for (i, (spec, stream)) in subtitles.into_iter().enumerate() {
+            args.extend(["-map".to_string(), spec]);
+            let codec = Self::subtitle_codec(stream, output_format)?;
+            // Unprobed specifiers may match several streams; use one codec for all.
+            let option = match stream {
+                Some(_) => format!("-c:s:{}", i),
+                None => "-c:s".to_string(),
+            };
+            codecs.extend([option, codec.to_string()]);
+        }
+        Ok(codecs)
+    }
+
+    /// Input streams (`-map` specifiers) picked by `--audio-tracks` or
+    /// `--subtitles`, with the probed stream for each.
+    ///
+    /// Without per-stream probe data the selection is left to FFmpeg's
+    /// stream specifiers, with `?` so a missing track is not an error.
+    fn select_tracks<'a>(
+        selection: &TrackSelection,
+        kind: StreamKind,
+        video_info: &'a VideoInfo,
+    ) -> Result<Vec<(String, Option<&'a StreamInfo>)>> {
+        let letter = if kind == StreamKind::Audio { "a" } else { "s" };
+
+        if video_info.streams.is_empty() {
+            let specs: Vec<String> = match selection {
+                TrackSelection::Default if kind == StreamKind::Audio => {
+                    vec![format!("0:{}:0?", letter)]
+                }
+                TrackSelection::Default | TrackSelection::None => Vec::new(),
+                TrackSelection::All => vec![format!("0:{}?", letter)],
+                TrackSelection::Numbers(numbers) => numbers
+                    .iter()
+                    .map(|n| format!("0:{}:{}", letter, n.saturating_sub(1)))
+                    .collect(),
+                TrackSelection::Languages(languages) => languages
+                    .iter()
+                    .map(|l| format!("0:{}:m:language:{}?", letter, l))
+                    .collect(),
+            };
+            return Ok(specs.into_iter().map(|spec| (spec, None)).collect());
+        }
+
+        let streams: Vec<&StreamInfo> = video_info
+            .streams
+            .iter()
+            .filter(|s| s.kind == kind)
+            .collect();
+        let chosen: Vec<&StreamInfo> = match selection {
+            TrackSelection::Default if kind == StreamKind::Audio => streams
+                .iter()
+                .find(|s| s.default)
+                .or(streams.first())
+                .copied()
+                .into_iter()
+                .collect(),
+            TrackSelection::Default | TrackSelection::None => Vec::new(),
+            TrackSelection::All => streams,
+            TrackSelection::Numbers(numbers) => numbers
+                .iter()
+                .map(|&n| {
+                    (n as usize)
+                        .checked_sub(1)
+                        .and_then(|i| streams.get(i).copied())
+                        .ok_or_else(|| {
+                            CompressoError::IncompatibleOptions(format!(
+                                "there is no {} track {} (the file has {})",
+                                kind,
+                                n,
+                                streams.len()
+                            ))
+                        })
+                })
+                .collect::<Result<_>>()?,
+            TrackSelection::Languages(languages) => {
+                let chosen: Vec<&StreamInfo> = streams
+                    .into_iter()
+                    .filter(|s| {
+                        s.language.as_deref().is_some_and(|l| {
+                            languages.iter().any(|want| want.eq_ignore_ascii_case(l))
+                        })
+                    })
+                    .collect();
+                if chosen.is_empty() {
+                    return Err(CompressoError::IncompatibleOptions(format!(
+                        "no {} track in {}",
+                        kind,
+                        languages.join(", ")
+                    )));
+                }
+                chosen
+            }
+        };
+        Ok(chosen
+            .into_iter()
+            .map(|s| (format!("0:{}", s.index), Some(s)))
+            .collect())
+    }
+
+    /// Subtitle codec for the output container. Text subtitles are converted
+    /// to what the container supports; image-based ones (Blu-ray/DVD) can
+    /// only be copied, which only MKV accepts.
+    fn subtitle_codec(source: Option<&StreamInfo>, output_format: &str) -> Result<&'static str> {
+        let is_image = source.is_some_and(|s| s.is_image_subtitle());
+        let source = source.and_then(|s| s.codec.as_deref());
+        match output_format {
+            // MKV takes nearly everything as-is, except MP4's own format.
+            "mkv" if source == Some("mov_text") => Ok("srt"),
+            "mkv" => Ok("copy"),
+            "mp4" | "mov" | "m4v" if !is_image => Ok("mov_text"),
+            "webm" if !is_image => Ok("webvtt"),
+            "mp4" | "mov" | "m4v" | "webm" => Err(CompressoError::IncompatibleOptions(format!(
+                "{} subtitles are images and can only be kept in .mkv (use -f mkv or --subtitles drop)",
+                source.unwrap_or("image")
+            ))),
+            _ => Err(CompressoError::IncompatibleOptions(format!(
+                "a .{} file cannot carry subtitles (use -f mkv or --subtitles drop)",
+                output_format
+            ))),
+        }
+    }
+
+    /// Emit the audio encoder options. The codec falls back to the container
+    /// default so the result never depends on FFmpeg's own pick (which fails
+    /// outright for some containers), and the bitrate is always explicit so
+    /// `--target-size` can budget for it.
+    fn push_audio_args(
+        args: &mut Vec<String>,
+        audio: &AudioSettings,
+        filters: &[String],
+        output_format: &str,
+    ) -> Result<()> {
+        let codec = audio
+            .codec
+            .unwrap_or_else(|| AudioCodec::default_for_format(output_format));
+        if !codec.supports_format(output_format) {
+            return Err(CompressoError::IncompatibleOptions(format!(
+                "{} audio cannot be stored in a .{} file",
+                codec, output_format
+            )));
+        }
+
+        let encoder = match codec.encoder() {
+            Some(encoder) => encoder,
+            None => {
+                if audio.bitrate_kbps.is_some()
+                    || audio.channels.is_some()
+                    || audio.sample_rate.is_some()
+                {
+                    return Err(CompressoError::IncompatibleOptions(
+                        "--audio-codec copy cannot change bitrate, channels or sample rate"
+                            .to_string(),
+                    ));
+                }
+                if !filters.is_empty() {
+                    return Err(CompressoError::IncompatibleOptions(
+                        "--audio-codec copy cannot be combined with --speed, \
+                         --normalize-audio, --volume or audio fades"
+                            .to_string(),
+                    ));
+                }
+                args.extend(["-c:a".to_string(), "copy".to_string()]);
+                return Ok(());
+            }
+        };
+
+        // libopus only runs at these rates and refuses anything else.
+        if let (AudioCodec::Opus, Some(rate)) = (codec, audio.sample_rate) {
+            if !OPUS_SAMPLE_RATES.contains(&rate) {
+                return Err(CompressoError::IncompatibleOptions(format!(
+                    "opus does not support a {} Hz sample rate (use 48000, 24000, 16000, 12000 or 8000)",
+                    rate
+                )));
+            }
+        }
+
+        args.extend(["-c:a".to_string(), encoder.to_string()]);
+        let kbps = audio
+            .bitrate_kbps
+            .unwrap_or_else(|| codec.default_bitrate_kbps());
+        args.extend(["-b:a".to_string(), format!("{}k", kbps)]);
+        if let Some(channels) = audio.channels {
+            args.extend(["-ac".to_string(), channels.to_string()]);
+        }
+        if let Some(rate) = audio.sample_rate {
+            args.extend(["-ar".to_string(), rate.to_string()]);
+        }
+        if !filters.is_empty() {
+            args.extend(["-af".to_string(), filters.join(",")]);
+        }
+        Ok(())
+    }
+
+    /// Audio bitrate (kbit/s) the output will carry, for size budgeting.
+    /// None when the output has no audio.
+    fn audio_bitrate_kbps(config: &CompressionConfig, output_format: &str) -> Option<u32> {
+        if config.mute {
+            return None;
+        }
+        let codec = config
+            .audio
+            .codec
+            .unwrap_or_else(|| AudioCodec::default_for_format(output_format));
+        Some(match codec {
+            AudioCodec::Copy => TARGET_SIZE_AUDIO_KBPS,
+            _ => config
+                .audio
+                .bitrate_kbps
+                .unwrap_or_else(|| codec.default_bitrate_kbps()),
+        })
+    }
+
+    /// Map the 0-100 quality setting onto the encoder's CRF scale.
+    ///
+    /// Lower CRF = higher quality. x264 and VP9 share the 24 (best) to 36
+    /// (worst) range; x265's CRF scale sits roughly 4 points higher for the
+    /// same perceived quality, so it uses 28 to 40. AV1 encoders use a 0-63
+    /// scale where 24 to 48 covers "visually transparent" to "heavily
+    /// compressed".
+    fn crf_for_quality(codec: VideoCodec, quality: u8) -> u16 {
+        let (min_crf, max_crf): (u16, u16) = match codec {
+            VideoCodec::H264 | VideoCodec::Vp9 => (24, 36),
+            VideoCodec::Hevc => (28, 40),
+            VideoCodec::Av1 => (24, 48),
+        };
+        let quality = quality.min(100) as u16;
+        min_crf + (max_crf - min_crf) * (100 - quality) / 100
+    }
+
+    /// Emit the encoder's quality/bitrate options.
+    ///
+    /// `crf_needs_zero_bitrate`: VP9 and libaom only honor CRF with `-b:v 0`.
+    /// `vbv`: cap bitrate peaks in bitrate mode with `-maxrate`/`-bufsize` so a
+    /// size-targeted encode does not overshoot on complex scenes.
+    fn push_rate_control(
+        args: &mut Vec<String>,
+        rate: RateControl,
+        crf_needs_zero_bitrate: bool,
+        vbv: bool,
+    ) {
+        match rate {
+            RateControl::Crf(crf) => {
+                if crf_needs_zero_bitrate {
+                    args.extend(["-b:v".to_string(), "0".to_string()]);
+                }
+                args.extend(["-crf".to_string(), crf.to_string()]);
+            }
+            RateControl::Bitrate(kbps) => {
+                args.extend(["-b:v".to_string(), format!("{}k", kbps)]);
+                if vbv {
+                    args.extend(["-maxrate".to_string(), format!("{}k", kbps * 3 / 2)]);
+                    args.extend(["-bufsize".to_string(), format!("{}k", kbps * 2)]);
+                }
+            }
+        }
+    }
+
+    /// Emit `-pass`/`-passlogfile` for encoders that use FFmpeg's generic
+    /// two-pass options (libx264, libvpx-vp9, libaom-av1).
+    fn push_pass(args: &mut Vec<String>, pass: Option<EncodePass<'_>>) {
+        if let Some(pass) = pass {
+            args.extend(["-pass".to_string(), pass.number.to_string()]);
+            args.extend(["-passlogfile".to_string(), pass.passlog.to_string()]);
+        }
+    }
+
+    /// Escape a value for `-x265-params`, whose `key=value:key=value` syntax
+    /// would otherwise split Windows paths (`C:\...`) at the drive colon.
+    fn escape_x265_param(value: &str) -> String {
+        value
+            .replace('\\', "\\\\")
+            .replace(':', "\\:")
+            .replace('\'', "\\'")
+    }
+
+    /// Compute the video bitrate that makes the output fit `target_bytes`.
+    ///
+    /// The audio budget is reserved first, and a small share is left for
+    /// container overhead. When `frame` (the post-transform frame size) is
+    /// given and the resulting bits-per-pixel is too low to be watchable, a
+    /// smaller output resolution is picked as well.
+    fn plan_target_bitrate(
+        target_bytes: u64,
+        duration_seconds: f64,
+        audio_kbps: u32,
+        frame: Option<(u32, u32)>,
+        fps: f64,
+    ) -> Result<TargetBitrate> {
+        if duration_seconds <= 0.0 {
+            return Err(CompressoError::InvalidInput(
+                "could not determine the video duration, which --target-size needs".to_string(),
+            ));
+        }
+
+        let total_kbps =
+            target_bytes as f64 * 8.0 * TARGET_SIZE_USABLE_FRACTION / duration_seconds / 1000.0;
+        let video_kbps = total_kbps - audio_kbps as f64;
+        if video_kbps < MIN_TARGET_VIDEO_KBPS as f64 {
+            let minimum_kbps = (MIN_TARGET_VIDEO_KBPS + audio_kbps) as f64;
+            let minimum =
+                (minimum_kbps * 1000.0 * duration_seconds / 8.0 / TARGET_SIZE_USABLE_FRACTION)
+                    .ceil() as u64;
+            return Err(CompressoError::TargetSizeUnreachable {
+                target: target_bytes,
+                minimum,
+            });
+        }
+        let video_kbps = video_kbps as u32;
+
+        let scale = frame.and_then(|dims| Self::downscale_for_bitrate(video_kbps, dims, fps));
+        Ok(TargetBitrate { video_kbps, scale })
+    }
+
+    /// Pick the largest standard resolution (by short edge) at which
+    /// `video_kbps` still gives at least `MIN_BITS_PER_PIXEL`. Returns `None`
+    /// when the current resolution is already fine. The short edge is used so
+    /// vertical phone videos step down the same way as landscape ones.
+    fn downscale_for_bitrate(video_kbps: u32, (w, h): (u32, u32), fps: f64) -> Option<(i32, i32)> {
+        let bits_per_pixel = |w: f64, h: f64| video_kbps as f64 * 1000.0 / (w * h * fps.max(1.0));
+        if w == 0 || h == 0 || bits_per_pixel(w as f64, h as f64) >= MIN_BITS_PER_PIXEL {
+            return None;
+        }
+
+        let short_edge = w.min(h);
+        let aspect = w.max(h) as f64 / short_edge as f64;
+        let rungs: Vec<u32> = [1080, 720, 540, 480, 360, 240]
+            .into_iter()
+            .filter(|&rung| rung < short_edge)
+            .collect();
+        let rung = rungs
+            .iter()
+            .copied()
+            .find(|&rung| bits_per_pixel(rung as f64 * aspect, rung as f64) >= MIN_BITS_PER_PIXEL)
+            .or_else(|| rungs.last().copied())?;
+
+        Some(if w >= h {
+            (-2, rung as i32)
+        } else {
+            (rung as i32, -2)
+        })
+    }
+
+    /// Frame size after rotation and crop, i.e. what the scale filter sees.
+    fn transformed_dimensions(transforms: &VideoTransforms, dims: (u32, u32)) -> (u32, u32) {
+        match transforms.crop {
+            Some(ref crop) => (crop.width, crop.height),
+            None => Self::rotated_dimensions(transforms, dims),
+        }
+    }
+
+    /// Frame size after `--rotate`, i.e. what the crop filter sees.
+    fn rotated_dimensions(transforms: &VideoTransforms, (w, h): (u32, u32)) -> (u32, u32) {
+        match transforms.rotate.map(|angle| angle % 360) {
+            Some(90 | -90 | 270 | -270) => (h, w),
+            _ => (w, h),
+        }
+    }
+
+    /// Reject a crop that reaches outside the frame, which FFmpeg would only
+    /// report as an obscure filter failure.
+    fn check_crop(crop: &CropCoordinates, frame: (u32, u32)) -> Result<()> {
+        let fits = |offset: u32, size: u32, total: u32| {
+            size > 0 && offset.checked_add(size).is_some_and(|end| end <= total)
+        };
+        if fits(crop.x, crop.width, frame.0) && fits(crop.y, crop.height, frame.1) {
+            Ok(())
+        } else {
+            Err(CompressoError::CropOutOfBounds {
+                crop: crop.clone(),
+                frame,
+            })
+        }
+    }
+
+    /// Work out `--crop auto` / `--crop-aspect` for this file, in the frame
+    /// after `--rotate`. None when there is nothing to crop.
+    pub fn resolve_auto_crop(
+        &self,
+        auto: AutoCrop,
+        config: &CompressionConfig,
+        video_info: &VideoInfo,
+    ) -> Result<Option<CropCoordinates>> {
+        let frame = video_info
+            .dimensions
+            .map(|dims| Self::rotated_dimensions(&config.transforms, dims));
+        let crop = match auto {
+            AutoCrop::Aspect(w, h) => {
+                let frame = frame.ok_or_else(|| {
+                    CompressoError::IncompatibleOptions(
+                        "--crop-aspect needs the video size, which could not be read".to_string(),
+                    )
+                })?;
+                Self::aspect_crop(frame, (w, h))
+            }
+            AutoCrop::Detect => self.detect_crop(config, video_info)?,
+        };
+        // A crop covering the whole frame is no crop.
+        Ok(crop.filter(|c| frame != Some((c.width, c.height))))
+    }
+
+    /// Largest centred crop of `frame` with the given aspect ratio, with even
+    /// sizes and offsets for 4:2:0 video.
+    fn aspect_crop(frame: (u32, u32), (aw, ah): (u32, u32)) -> Option<CropCoordinates> {
+        let (fw, fh) = (frame.0 as u64, frame.1 as u64);
+        let (aw, ah) = (aw as u64, ah as u64);
+        let (width, height) = if fw * ah > fh * aw {
+            (fh * aw / ah, fh)
+        } else {
+            (fw, fw * ah / aw)
+        };
+        let (width, height) = (width as u32 & !1, height as u32 & !1);
+        if width == 0 || height == 0 {
+            return None;
+        }
+        Some(CropCoordinates {
+            width,
+            height,
+            x: ((frame.0 - width) / 2) & !1,
+            y: ((frame.1 - height) / 2) & !1,
+        })
+    }
+
+    /// Run cropdetect on a few short stretches of the video (of the trimmed
+    /// section, if any) and return the area that holds picture in all of
+    /// them, so dark scenes do not cut into the image.
+    fn detect_crop(
+        &self,
+        config: &CompressionConfig,
+        video_info: &VideoInfo,
+    ) -> Result<Option<CropCoordinates>> {
+        let input = Self::validate_input_path(&config.input_path)?;
+        let mut detected: Option<CropCoordinates> = None;
+        for window in Self::analysis_windows(config, video_info, CROPDETECT_SECONDS) {
+            let stderr = self.run_analysis(self.build_cropdetect_args(config, &input, window))?;
+            if let Some(crop) = Self::parse_cropdetect(&stderr) {
+                detected = Some(match detected {
+                    Some(previous) => Self::crop_union(&previous, &crop),
+                    None => crop,
+                });
+            }
+        }
+        Ok(detected)
+    }
+
+    /// cropdetect run over one window, after `--rotate`/`--flip` so the
+    /// result lines up with the crop filter.
+    fn build_cropdetect_args(
+        &self,
+        config: &CompressionConfig,
+        input_path: &str,
+        window: (f64, f64),
+    ) -> Vec<String> {
+        let transforms = VideoTransforms {
+            crop: None,
+            ..config.transforms.clone()
+        };
+        let mut filters = Vec::new();
+        self.apply_transforms(&transforms, &mut filters);
+        // limit is a fraction of the full range, so it suits 10-bit video too.
+        filters.push("cropdetect=limit=0.094:round=2:reset=0".to_string());
+        Self::analysis_args(input_path, window, &filters.join(","))
+    }
+
+    /// Short stretches of the video (of the trimmed section, if any) to run
+    /// a detection filter over.
+    fn analysis_windows(
+        config: &CompressionConfig,
+        video_info: &VideoInfo,
+        seconds: f64,
+    ) -> Vec<(f64, f64)> {
+        let offset = config.trim.map_or(0.0, |trim| trim.start);
+        match Self::section_duration(config, video_info) {
+            Some(duration) if duration > 0.0 => Self::sample_windows(duration)
+                .into_iter()
+                .map(|(start, length)| (offset + start, length.min(seconds)))
+                .collect(),
+            _ => vec![(offset, seconds)],
+        }
+    }
+
+    /// Decode one window through `filters` and discard the frames; the
+    /// filters report what they found on stderr.
+    fn analysis_args(input_path: &str, (start, length): (f64, f64), filters: &str) -> Vec<String> {
+        vec![
+            "-hide_banner".to_string(),
+            "-nostats".to_string(),
+            "-ss".to_string(),
+            format!("{:.3}", start),
+            "-i".to_string(),
+            input_path.to_string(),
+            "-t".to_string(),
+            format!("{:.3}", length),
+            "-vf".to_string(),
+            filters.to_string(),
+            "-an".to_string(),
+            "-sn".to_string(),
+            "-dn".to_string(),
+            "-f".to_string(),
+            "null".to_string(),
+            "-".to_string(),
+        ]
+    }
+
+    /// Run an analysis command and return its stderr.
+    fn run_analysis(&self, args: Vec<String>) -> Result<String> {
+        let output = Command::new(&self.ffmpeg_path)
+            .args(args)
+            .stdout(Stdio::null())
+            .stderr(Stdio::piped())
+            .output()?;
+        let stderr = String::from_utf8_lossy(&output.stderr).into_owned();
+        if !output.status.success() {
+            return Err(CompressoError::FfmpegError(stderr.trim().to_string()));
+        }
+        Ok(stderr)
+    }
+
+    /// Settle `--deinterlace auto` to `On` or `Off` by running idet over a
+    /// few samples. A stream copy is never deinterlaced.
+    pub fn resolve_deinterlace(
+        &self,
+        config: &CompressionConfig,
+        video_info: &VideoInfo,
+        output_format: &str,
+    ) -> Result<Deinterlace> {
+        if config.filters.deinterlace != Deinterlace::Auto {
+            return Ok(config.filters.deinterlace);
+        }
+        if Self::should_remux(config, video_info, &config.input_path, output_format)? {
+            return Ok(Deinterlace::Off);
+        }
+        let input = Self::validate_input_path(&config.input_path)?;
+        let (mut interlaced, mut progressive) = (0, 0);
+        for window in Self::analysis_windows(config, video_info, IDET_SECONDS) {
+            let stderr = self.run_analysis(Self::analysis_args(&input, window, "idet"))?;
+            if let Some((i, p)) = Self::parse_idet(&stderr) {
+                interlaced += i;
+                progressive += p;
+            }
+        }
+        Ok(if interlaced > progressive {
+            Deinterlace::On
+        } else {
+            Deinterlace::Off
+        })
+    }
+
+    /// (interlaced, progressive) frame counts from idet's multi-frame
+    /// summary, which is steadier than the single-frame one.
+    fn parse_idet(stderr: &str) -> Option<(u64, u64)> {
+        let re = IDET_REGEX.get_or_init(|| {
+            Regex::new(
+                r"Multi frame detection:\s*TFF:\s*(\d+)\s*BFF:\s*(\d+)\s*Progressive:\s*(\d+)",
+            )
+            .unwrap()
+        });
+        let caps = re.captures_iter(stderr).last()?;
+        let count = |i: usize| caps[i].parse::<u64>().ok();
+        Some((count(1)? + count(2)?, count(3)?))
+    }
+
+    /// Union of every `crop=W:H:X:Y` that cropdetect logged. Entirely black
+    /// frames produce negative sizes and are skipped.
+    fn parse_cropdetect(stderr: &str) -> Option<CropCoordinates> {
+        let re =
+            CROPDETECT_REGEX.get_or_init(|| Regex::new(r"crop=(\d+):(\d+):(\d+):(\d+)").unwrap());
+        re.captures_iter(stderr)
+            .filter_map(|caps| {
+                Some(CropCoordinates {
+                    width: caps[1].parse().ok()?,
+                    height: caps[2].parse().ok()?,
+                    x: caps[3].parse().ok()?,
+                    y: caps[4].parse().ok()?,
+                })
+            })
+            .filter(|crop| crop.width > 0 && crop.height > 0)
+            .reduce(|a, b| Self::crop_union(&a, &b))
+    }
+
+    /// Smallest crop containing both.
+    fn crop_union(a: &CropCoordinates, b: &CropCoordinates) -> CropCoordinates {
+        let x = a.x.min(b.x);
+        let y = a.y.min(b.y);
+        CropCoordinates {
+            width: (a.x + a.width).max(b.x + b.width) - x,
+            height: (a.y + a.height).max(b.y + b.height) - y,
+            x,
+            y,
+        }
+    }
+
+    /// Scale for `--width`/`--height`/`--max-resolution`, or None to keep the
+    /// frame as it is. `-2` derives that side from the aspect ratio, rounded
+    /// to an even size.
+    ///
+    /// `frame` is the probed size after transforms; unless `--allow-upscale`
+    /// is set, nothing is ever scaled beyond it. `--max-resolution` needs the
+    /// probed size to tell the long edge from the short one.
+    fn requested_scale(
+        config: &CompressionConfig,
+        frame: Option<(u32, u32)>,
+    ) -> Option<(i32, i32)> {
+        // True when no requested side would shrink the frame.
+        let only_enlarges = |w: Option<u32>, h: Option<u32>| match frame {
+            Some((fw, fh)) => {
+                !config.allow_upscale && w.map_or(true, |w| w >= fw) && h.map_or(true, |h| h >= fh)
+            }
+            None => false,
+        };
+
+        match (config.width, config.height) {
+            (Some(w), Some(h)) => {
+                (!only_enlarges(Some(w), Some(h))).then_some((w as i32, h as i32))
+            }
+            (Some(w), None) => (!only_enlarges(Some(w), None)).then_some((w as i32, -2)),
+            (None, Some(h)) => (!only_enlarges(None, Some(h))).then_some((-2, h as i32)),
+            (None, None) => {
+                let max = config.max_resolution?;
+                let (fw, fh) = frame?;
+                if fw == 0 || fh == 0 {
+                    return None;
+                }
+                // Orient the box like the video.
+                let (bw, bh) = if fw >= fh {
+                    (max.long, max.short)
+                } else {
+                    (max.short, max.long)
+                };
+                // bw/fw <= bh/fh: the width is the tighter limit.
+                let width_bound = bw as u64 * fh as u64 <= bh as u64 * fw as u64;
+                let already_fits = fw <= bw && fh <= bh;
+                let touches_box = fw == bw || fh == bh;
+                if already_fits && (touches_box || !config.allow_upscale) {
+                    return None;
+                }
+                Some(if width_bound {
+                    (bw as i32, -2)
+                } else {
+                    (-2, bh as i32)
+                })
+            }
+        }
+    }
+
+    /// Frame size produced by a scale from `requested_scale` (or a
+    /// target-size downscale) applied to `frame`.
+    fn scaled_dimensions((w, h): (i32, i32), (fw, fh): (u32, u32)) -> (u32, u32) {
+        let even = |x: f64| ((x / 2.0).round() as u32) * 2;
+        match (w > 0, h > 0) {
+            (true, true) => (w as u32, h as u32),
+            (true, false) => (w as u32, even(w as f64 * fh as f64 / fw.max(1) as f64)),
+            (false, true) => (even(h as f64 * fw as f64 / fh.max(1) as f64), h as u32),
+            (false, false) => (fw, fh),
+        }
+    }
+
+    /// `post` filters (tone-mapping, subtitles) run at the final resolution.
+    fn build_filters(
+        &self,
+        config: &CompressionConfig,
+        scale: Option<(i32, i32)>,
+        post: Vec<String>,
+    ) -> String {
+        let mut filters: Vec<String> = Vec::new();
+
+        // Deinterlace first: the fields only line up in the original frame.
+        if config.filters.deinterlace == Deinterlace::On {
+            filters.push(DEINTERLACE_FILTER.to_string());
+        }
+
+        // Apply transforms
+        self.apply_transforms(&config.transforms, &mut filters);
+
+        // Denoise before scaling, while the grain is still fine
+        if let Some(level) = config.filters.denoise {
+            filters.push(denoise_filter(level).to_string());
+        }
+
+        // Dimensions
+        let padding = "pad=ceil(iw/2)*2:ceil(ih/2)*2";
+        if let Some((w, h)) = scale {
+            filters.push(format!("scale={}:{}", w, h));
+        }
+
+        // Colour works per pixel, so it is cheapest after scaling
+        filters.extend(color_filters(&config.filters));
+
+        // Sharpen at the final size
+        if config.filters.sharpen {
+            filters.push(SHARPEN_FILTER.to_string());
+        }
+        filters.push(padding.to_string());
+
+        filters.extend(post);
+
+        filters.join(",")
+    }
+
+    /// Catch an FFmpeg build that cannot draw `--text-overlay` before it
+    /// fails halfway with a filter error.
+    fn check_text_overlay(&self, config: &CompressionConfig) -> Result<()> {
+        if config.overlay.text.is_none() {
+            return Ok(());
+        }
+        if !self.has_filter("drawtext") {
+            return Err(CompressoError::IncompatibleOptions(
+                "--text-overlay needs an FFmpeg build with the drawtext filter (libfreetype)"
+                    .to_string(),
+            ));
+        }
+        if config.overlay.font.is_none() && !self.has_fontconfig() {
+            return Err(CompressoError::IncompatibleOptions(
+                "this FFmpeg build has no fontconfig to find a default font; \
+                 choose one with --text-font"
+                    .to_string(),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Subtitle, LUT, watermark and font files get the same checks as the
+    /// input.
+    fn check_extra_inputs(config: &CompressionConfig) -> Result<()> {
+        if let Some(BurnSubtitles::File(path)) = &config.burn_subtitles {
+            Self::validate_input_path(path)?;
+        }
+        for path in &config.concat {
+            Self::validate_input_path(path)?;
+        }
+        for path in [&config.overlay.image, &config.overlay.font]
+            .into_iter()
+            .flatten()
+        {
+            Self::validate_input_path(path)?;
+        }
+        if let Some(lut) = &config.filters.lut {
+            Self::validate_input_path(lut)?;
+            let extension = Path::new(lut)
+                .extension()
+                .and_then(|e| e.to_str())
+                .map(|e| e.to_lowercase());
+            if !matches!(
+                extension.as_deref(),
+                Some("cube" | "3dl" | "dat" | "m3d" | "csp")
+            ) {
+                return Err(CompressoError::IncompatibleOptions(format!(
+                    "cannot apply {}: use a .cube, .3dl, .dat, .m3d or .csp LUT",
+                    lut
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    /// With `--concat`, probe the further inputs and describe the joined
+    /// input: the first input's streams with the summed duration (unknown
+    /// when any part's is), and every input in `parts`.
+    pub fn concat_info(&self, config: &CompressionConfig, first: VideoInfo) -> Result<VideoInfo> {
+        if config.concat.is_empty() {
+            return Ok(first);
+        }
+        let mut parts = vec![first.clone()];
+        for path in &config.concat {
+            parts.push(self.get_video_info(&Self::validate_input_path(path)?)?);
+        }
+        let inputs = std::iter::once(&config.input_path).chain(&config.concat);
+        if let Some((path, _)) = inputs.zip(&parts).find(|(_, p)| p.dimensions.is_none()) {
+            return Err(CompressoError::InvalidInput(format!(
+                "{} has no video stream to join",
+                path
+            )));
+        }
+
+        let duration_seconds = parts
+            .iter()
+            .map(|p| p.duration_seconds)
+            .sum::<Option<f64>>();
+        let info = VideoInfo {
+            duration: duration_seconds.map(crate::probe::format_duration),
+            duration_seconds,
+            parts,
+            ..first
+        };
+        // Checked before any analysis of the inputs runs.
+        Self::check_concat(config, Self::concat_mode(&info))?;
+        Ok(info)
+    }
+
+    /// How the inputs of `--concat` are joined; None for a single input.
+    /// The demuxer needs every stream to match the first input's in all but
+    /// bitrate, language and title.
+    fn concat_mode(video_info: &VideoInfo) -> Option<ConcatMode> {
+        let (first, rest) = video_info.parts.split_first()?;
+        let shape = |info: &VideoInfo| -> Vec<StreamInfo> {
+            info.streams
+                .iter()
+                .filter(|s| !s.attached_pic)
+                .map(|s| StreamInfo {
+                    bitrate: None,
+                    language: None,
+                    title: None,
+                    default: false,
+                    fps: s.fps.map(|fps| (fps * 100.0).round() / 100.0),
+                    // Per-clip HDR10 light levels do not stop a join.
+                    mastering_display: None,
+                    max_cll: None,
+                    ..s.clone()
+                })
+                .collect()
+        };
+        // Without per-stream probe data nothing is known to match.
+        let same = !first.streams.is_empty() && rest.iter().all(|p| shape(p) == shape(first));
+        Some(if same {
+            ConcatMode::Demuxer
+        } else {
+            ConcatMode::Filter
+        })
+    }
+
+    /// Options that cannot follow the inputs of `--concat`
+    fn check_concat(config: &CompressionConfig, concat: Option<ConcatMode>) -> Result<()> {
+        let Some(mode) = concat else {
+            return Ok(());
+        };
+        if matches!(config.burn_subtitles, Some(BurnSubtitles::Track(_))) {
+            return Err(CompressoError::IncompatibleOptions(
+                "--concat can only burn in a subtitle file, not a track of the input".to_string(),
+            ));
+        }
+        // Black bars and interlacing are detected in one file, and the
+        // inputs may differ in both.
+        let analysis = match (config.auto_crop, config.filters.deinterlace) {
+            (Some(AutoCrop::Detect), _) => Some("--crop auto"),
+            (Some(AutoCrop::Aspect(..)), _) => Some("--crop-aspect"),
+            (None, Deinterlace::Auto) => Some("--deinterlace auto"),
+            (None, _) => None,
+        };
+        if let Some(option) = analysis {
+            return Err(CompressoError::IncompatibleOptions(format!(
+                "{} cannot be used with --concat; give --crop or --deinterlace on|off instead",
+                option
+            )));
+        }
+        // The loudness pass measures a single file.
+        if config.audio_filters.normalize && !config.mute {
+            return Err(CompressoError::IncompatibleOptions(
+                "--normalize-audio cannot be used with --concat".to_string(),
+            ));
+        }
+        let tracks_chosen = !matches!(config.audio_tracks, TrackSelection::Default)
+            || !matches!(
+                config.subtitles,
+                TrackSelection::Default | TrackSelection::None
+            );
+        if mode == ConcatMode::Filter && tracks_chosen {
+            return Err(CompressoError::IncompatibleOptions(
+                "--concat inputs of different formats are joined with only their main video \
+                 and audio track, so --audio-tracks and --subtitles cannot be used"
+                    .to_string(),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Whether the concat filter joins sound: the output keeps it and at
+    /// least one input has some
+    fn concat_audio(
+        config: &CompressionConfig,
+        video_info: &VideoInfo,
+        output_format: &str,
+        is_final_pass: bool,
+    ) -> bool {
+        !config.mute
+            && is_final_pass
+            && !is_animation(output_format)
+            && video_info.parts.iter().any(|p| p.audio_codec.is_some())
+    }
+
+    /// Start of the filter graph that joins inputs of different formats.
+    /// Each input is fitted into the first one's frame (scaled, padded,
+    /// square pixels, its frame rate) and its audio resampled to one layout;
+    /// inputs without sound get silence. The joined streams, cut to begin at
+    /// `start` with timestamps from zero as after seeking, are labelled
+    /// `[cv]` and, with `audio`, `[ca]`.
+    fn concat_graph(video_info: &VideoInfo, audio: bool, start: f64) -> Result<String> {
+        let (w, h) = video_info
+            .dimensions
+            .map(|(w, h)| (w / 2 * 2, h / 2 * 2))
+            .unwrap_or((1280, 720));
+        let fps = video_info.fps.unwrap_or(30.0);
+        let rate = video_info
+            .parts
+            .iter()
+            .flat_map(|p| &p.streams)
+            .find(|s| s.kind == StreamKind::Audio)
+            .and_then(|s| s.sample_rate)
+            .unwrap_or(48000);
+        // Stereo unless every input with sound is mono
+        let layout = if video_info
+            .parts
+            .iter()
+            .flat_map(|p| &p.streams)
+            .filter(|s| s.kind == StreamKind::Audio)
+            .all(|s| s.channels == Some(1))
+        {
+            "mono"
+        } else {
+            "stereo"
+        };
+
+        let mut graph = Vec::new();
+        let mut joined = String::new();
+        for (i, part) in video_info.parts.iter().enumerate() {
+            graph.push(format!(
+                "[{}:V:0]scale={}:{}:force_original_aspect_ratio=decrease,\
+                 pad={}:{}:(ow-iw)/2:(oh-ih)/2,setsar=1,fps={}[v{}]",
+                i, w, h, w, h, fps, i
+            ));
+            joined.push_str(&format!("[v{}]", i));
+            if !audio {
+                continue;
+            }
+            if part.audio_codec.is_some() {
+                graph.push(format!(
+                    "[{}:a:0]aresample={},aformat=channel_layouts={}[a{}]",
+                    i, rate, layout, i
+                ));
+            } else {
+                let duration = part.duration_seconds.ok_or_else(|| {
+                    CompressoError::InvalidInput(format!(
+                        "cannot add silence to --concat input {}: its duration is unknown",
+                        i + 1
+                    ))
+                })?;
+                graph.push(format!(
+                    "anullsrc=r={}:cl={},atrim=duration={:.3}[a{}]",
+                    rate, layout, duration, i
+                ));
+            }
+            joined.push_str(&format!("[a{}]", i));
+        }
+        let outputs = match (start > 0.0, audio) {
+            (false, false) => "[cv]",
+            (false, true) => "[cv][ca]",
+            (true, false) => "[jv]",
+            (true, true) => "[jv][ja]",
+        };
+        graph.push(format!(
+            "{}concat=n={}:v=1:a={}{}",
+            joined,
+            video_info.parts.len(),
+            u8::from(audio),
+            outputs
+        ));
+        if start > 0.0 {
+            graph.push(format!(
+                "[jv]trim=start={:.3},setpts=PTS-STARTPTS[cv]",
+                start
+            ));
+            if audio {
+                graph.push(format!(
+                    "[ja]atrim=start={:.3},asetpts=PTS-STARTPTS[ca]",
+                    start
+                ));
+            }
+        }
+        Ok(graph.join(";"))
+    }
+
+    /// ffconcat script that lists the inputs for the concat demuxer
+    fn concat_list(paths: &[String]) -> String {
+        let mut list = String::from("ffconcat version 1.0\n");
+        for path in paths {
+            // Quoted, with `'` closed, escaped and reopened as in a shell
+            list.push_str(&format!("file '{}'\n", path.replace('\'', r"'\''")));
+        }
+        list
+    }
+
+    /// Filters that render `--burn-subtitles` into the picture. `offset` is
+    /// where the input was seeked to: seeking restarts timestamps at zero,
+    /// so they are shifted back while the subtitles are drawn.
+    fn burn_subtitle_filters(
+        config: &CompressionConfig,
+        video_info: &VideoInfo,
+        input_path: &str,
+        offset: f64,
+    ) -> Result<Vec<String>> {
+        let filter = match &config.burn_subtitles {
+            None => return Ok(Vec::new()),
+            Some(BurnSubtitles::File(path)) => {
+                let extension = Path::new(path)
+                    .extension()
+                    .and_then(|e| e.to_str())
+                    .map(|e| e.to_lowercase());
+                match extension.as_deref() {
+                    Some("ass" | "ssa") => format!("ass={}", escape_filter_path(path)),
+                    Some("srt" | "vtt") => format!("subtitles={}", escape_filter_path(path)),
+                    _ => {
+                        return Err(CompressoError::IncompatibleOptions(format!(
+                            "cannot burn in {}: use a .srt, .ass, .ssa or .vtt file",
+                            path
+                        )))
+                    }
+                }
+            }
+            Some(BurnSubtitles::Track(number)) => {
+                if !video_info.streams.is_empty() {
+                    let tracks: Vec<&StreamInfo> = video_info
+                        .streams
+                        .iter()
+                        .filter(|s| s.kind == StreamKind::Subtitle)
+                        .collect();
+                    let track = (*number as usize)
+                        .checked_sub(1)
+                        .and_then(|i| tracks.get(i))
+                        .ok_or_else(|| {
+                            CompressoError::IncompatibleOptions(format!(
+                                "there is no subtitle track {} (the file has {})",
+                                number,
+                                tracks.len()
+                            ))
+                        })?;
+                    // The subtitles filter only draws text.
+                    if track.is_image_subtitle() {
+                        return Err(CompressoError::IncompatibleOptions(format!(
+                            "subtitle track {} ({}) is made of images and cannot be burned in",
+                            number,
+                            track.codec.as_deref().unwrap_or("?")
+                        )));
+                    }
+                }
+                format!(
+                    "subtitles={}:si={}",
+                    escape_filter_path(input_path),
+                    number.saturating_sub(1)
+                )
+            }
+        };
+
+        if offset > 0.0 {
+            Ok(vec![
+                format!("setpts=PTS+{:.3}/TB", offset),
+                filter,
+                "setpts=PTS-STARTPTS".to_string(),
+            ])
+        } else {
+            Ok(vec![filter])
+        }
+    }
+
+    fn apply_transforms(&self, transforms: &VideoTransforms, filters: &mut Vec<String>) {
+        // Rotate
+        if let Some(angle) = transforms.rotate {
+            match angle % 360 {
+                90 | -270 => filters.push("transpose=1".to_string()),
+                -90 | 270 => filters.push("transpose=2".to_string()),
+                180 | -180 => filters.push("hflip,vflip".to_string()),
+                _ => {}
+            }
+        }
+
+        // Flip
+        if let Some(ref flip) = transforms.flip {
+            if flip.horizontal {
+                filters.push("hflip".to_string());
+            }
+            if flip.vertical {
+                filters.push("vflip".to_string());
+            }
+        }
+
+        // Crop
+        if let Some(ref crop) = transforms.crop {
+            filters.push(format!(
+                "crop={}:{}:{}:{}",
+                crop.width, crop.height, crop.x, crop.y
+            ));
+        }
+    }
+}
+
+impl Default for FFmpeg {
+    fn default() -> Self {
+        // `FFmpeg` should normally be constructed via `FFmpeg::new()` so the
+        // caller can handle the `FfmpegNotFound` error gracefully. `Default`
+        // is retained only for trait completeness; it panics if FFmpeg is not
+        // installed. This is acceptable because `Default::default()` is not
+        // used anywhere in the codebase today.
+        Self::new().expect("FFmpeg not found")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::{AudioFilters, ColorAdjust};
+
+    // ---- strip_verbatim_prefix -------------------------------------------------
+
+    #[test]
+    fn test_strip_verbatim_prefix_unc() {
+        assert_eq!(
+            strip_verbatim_prefix(r"\\?\UNC\server\share\file"),
+            r"\\server\share\file"
+        );
+    }
+
+    #[test]
+    fn test_strip_verbatim_prefix_drive() {
+        assert_eq!(
+            strip_verbatim_prefix(r"\\?\C:\Windows\System32"),
+            r"C:\Windows\System32"
+        );
+    }
+
+    #[test]
+    fn test_strip_verbatim_prefix_noop() {
+        assert_eq!(
+            strip_verbatim_prefix(r"C:\Windows\System32"),
+            r"C:\Windows\System32"
+        );
+        assert_eq!(strip_verbatim_prefix("/etc/passwd"), "/etc/passwd");
+    }
+
+    // ---- validate_input_path ---------------------------------------------------
+
+    #[test]
+    fn test_validate_input_path_rejects_null_bytes() {
+        let res = FFmpeg::validate_input_path("vi\0deo.mp4");
+        assert!(res.is_err());
+    }
+
+    #[test]
+    fn test_validate_input_path_rejects_traversal() {
+        // `..` must be rejected outright, not merely warned about.
+        let res = FFmpeg::validate_input_path("../secret/video.mp4");
+        assert!(res.is_err(), "expected path traversal to be rejected");
+    }
+
+    #[test]
+    fn test_validate_input_path_rejects_missing_file() {
+        let res = FFmpeg::validate_input_path("definitely_nonexistent_input.mp4");
+        assert!(res.is_err());
+    }
+
+    // ---- validate_output_path --------------------------------------------------
+    //
+    // These are security-critical. Each test documents a bypass that the old
+    // (raw-string denylist) implementation allowed and that the new
+    // canonicalization-based implementation must block.
+
+    #[test]
+    fn test_validate_output_path_rejects_null_bytes() {
+        assert!(FFmpeg::validate_output_path("out\0.mp4").is_err());
+    }
+
+    #[test]
+    fn test_validate_output_path_rejects_traversal() {
+        // `..` must be a hard error, not a warning.
+        assert!(FFmpeg::validate_output_path("../evil/out.mp4").is_err());
+    }
+
+    #[test]
+    fn test_validate_output_path_blocks_missing_parent() {
+        // A non-existent parent directory must be rejected, not silently
+        // accepted (which previously let /usr/lib/... through).
+        assert!(FFmpeg::validate_output_path("/nonexistent_dir_xyz/out.mp4").is_err());
+    }
+
+    #[test]
+    fn test_validate_output_path_accepts_legitimate_tmp() {
+        // A normal temp file under an existing directory must be accepted.
+        let tmp = std::env::temp_dir().join("compresso_test_output.mp4");
+        assert!(FFmpeg::validate_output_path(&tmp.to_string_lossy()).is_ok());
+    }
+
+    // The container/codec routing in build_args is pure and easy to unit-test.
+    #[test]
+    fn test_build_args_ironclad_has_no_lossless_flags() {
+        // Regression test for P0.1: Ironclad must NOT contain `-qp 0` or
+        // `-b:v 0` together with `-crf` (that combination produced lossless
+        // output, larger than the source, ignoring the quality setting).
+        let ffmpeg = make_ffmpeg_for_tests();
+        let cfg = CompressionConfig {
+            input_path: "in.mp4".to_string(),
+            preset: Preset::Ironclad,
+            quality: 70,
+            ..CompressionConfig::default()
+        };
+        let args = ffmpeg
+            .build_args(
+                &cfg,
+                &VideoInfo::default(),
+                "in.mp4",
+                "out.mp4",
+                "mp4",
+                None,
+            )
+            .unwrap();
+        let joined = args.join(" ");
+        assert!(joined.contains("-crf"), "CRF must be present");
+        assert!(
+            !joined.contains("-qp 0"),
+            "lossless -qp 0 must NOT be present"
+        );
+    }
+
+    #[test]
+    fn test_build_args_thunderbolt_uses_crf() {
+        let ffmpeg = make_ffmpeg_for_tests();
+        let cfg = CompressionConfig {
+            input_path: "in.mp4".to_string(),
+            preset: Preset::Thunderbolt,
+            quality: 70,
+            ..CompressionConfig::default()
+        };
+        let args = ffmpeg
+            .build_args(
+                &cfg,
+                &VideoInfo::default(),
+                "in.mp4",
+                "out.mp4",
+                "mp4",
+                None,
+            )
+            .unwrap();
+        let joined = args.join(" ");
+        assert!(joined.contains("-crf"));
+        assert!(joined.contains("libx264"));
+        assert!(joined.contains("ultrafast"));
+    }
+
+    #[test]
+    fn test_build_args_webm_uses_vp9() {
+        let ffmpeg = make_ffmpeg_for_tests();
+        let cfg = CompressionConfig {
+            input_path: "in.mp4".to_string(),
+            preset: Preset::Ironclad,
+            quality: 70,
+            ..CompressionConfig::default()
+        };
+        let args = ffmpeg
+            .build_args(
+                &cfg,
+                &VideoInfo::default(),
+                "in.mp4",
+                "out.webm",
+                "webm",
+                None,
+            )
+            .unwrap();
+        let joined = args.join(" ");
+        assert!(joined.contains("libvpx-vp9"), "WebM output must use VP9");
+        // faststart is MP4-only and harmful for WebM.
+        assert!(
+            !joined.contains("faststart"),
+            "+faststart must not be set for WebM"
+        );
+    }
+
+    #[test]
+    fn test_build_args_mov_gets_faststart() {
+        let ffmpeg = make_ffmpeg_for_tests();
+        let cfg = CompressionConfig {
+            input_path: "in.mp4".to_string(),
+            preset: Preset::Ironclad,
+            quality: 70,
+            ..CompressionConfig::default()
+        };
+        let args = ffmpeg
+            .build_args(
+                &cfg,
+                &VideoInfo::default(),
+                "in.mp4",
+                "out.mov",
+                "mov",
+                None,
+            )
+            .unwrap();
+        assert!(args.join(" ").contains("faststart"));
+    }
+
+    #[test]
+    fn test_build_args_hevc_uses_x265_with_hvc1_tag() {
+        let ffmpeg = make_ffmpeg_for_tests();
+        let cfg = CompressionConfig {
+            input_path: "in.mp4".to_string(),
+            codec: Some(VideoCodec::Hevc),
+            quality: 70,
+            ..CompressionConfig::default()
+        };
+        let args = ffmpeg
+            .build_args(
+                &cfg,
+                &VideoInfo::default(),
+                "in.mp4",
+                "out.mp4",
+                "mp4",
+                None,
+            )
+            .unwrap();
+        let joined = args.join(" ");
+        assert!(joined.contains("-c:v libx265"));
+        assert!(joined.contains("-crf 31"), "x265 uses its own CRF scale");
+        assert!(joined.contains("-preset slow"));
+        assert!(joined.contains("-tag:v hvc1"), "Apple players need hvc1");
+
+        // hvc1 is an MP4-family sample entry; MKV must not get it.
+        let args = ffmpeg
+            .build_args(
+                &cfg,
+                &VideoInfo::default(),
+                "in.mp4",
+                "out.mkv",
+                "mkv",
+                None,
+            )
+            .unwrap();
+        assert!(!args.join(" ").contains("hvc1"));
+    }
+
+    #[test]
+    fn test_build_args_codec_independent_of_container() {
+        // VP9 in MKV and H.264 in MKV must both be reachable via --codec.
+        let ffmpeg = make_ffmpeg_for_tests();
+        let cfg = CompressionConfig {
+            codec: Some(VideoCodec::Vp9),
+            ..CompressionConfig::default()
+        };
+        let args = ffmpeg
+            .build_args(
+                &cfg,
+                &VideoInfo::default(),
+                "in.mp4",
+                "out.mkv",
+                "mkv",
+                None,
+            )
+            .unwrap();
+        assert!(args.join(" ").contains("libvpx-vp9"));
+    }
+
+    #[test]
+    fn test_build_args_rejects_codec_container_mismatch() {
+        let ffmpeg = make_ffmpeg_for_tests();
+        let cfg = CompressionConfig {
+            codec: Some(VideoCodec::Hevc),
+            ..CompressionConfig::default()
+        };
+        let res = ffmpeg.build_args(
+            &cfg,
+            &VideoInfo::default(),
+            "in.mp4",
+            "out.webm",
+            "webm",
+            None,
+        );
+        assert!(matches!(res, Err(CompressoError::IncompatibleOptions(_))));
+    }
+
+    #[test]
+    fn test_build_args_av1_prefers_svtav1() {
+        let ffmpeg = make_ffmpeg_with_encoders(&["libsvtav1", "libaom-av1"]);
+        let mut cfg = CompressionConfig {
+            codec: Some(VideoCodec::Av1),
+            preset: Preset::Ironclad,
+            quality: 70,
+            ..CompressionConfig::default()
+        };
+        let args = ffmpeg
+            .build_args(
+                &cfg,
+                &VideoInfo::default(),
+                "in.mp4",
+                "out.mkv",
+                "mkv",
+                None,
+            )
+            .unwrap();
+        let joined = args.join(" ");
+        assert!(joined.contains("-c:v libsvtav1"));
+        assert!(joined.contains("-crf 31"));
+        assert!(joined.contains("-preset 6"));
+        assert!(!joined.contains("-b:v"), "SVT-AV1 CRF mode needs no -b:v 0");
+
+        cfg.preset = Preset::Thunderbolt;
+        let args = ffmpeg
+            .build_args(
+                &cfg,
+                &VideoInfo::default(),
+                "in.mp4",
+                "out.mkv",
+                "mkv",
+                None,
+            )
+            .unwrap();
+        assert!(args.join(" ").contains("-preset 10"));
+    }
+
+    #[test]
+    fn test_build_args_av1_falls_back_to_libaom() {
+        let ffmpeg = make_ffmpeg_with_encoders(&["libx264", "libaom-av1"]);
+        let cfg = CompressionConfig {
+            codec: Some(VideoCodec::Av1),
+            preset: Preset::Thunderbolt,
+            quality: 100,
+            ..CompressionConfig::default()
+        };
+        let args = ffmpeg
+            .build_args(
+                &cfg,
+                &VideoInfo::default(),
+                "in.mp4",
+                "out.webm",
+                "webm",
+                None,
+            )
+            .unwrap();
+        let joined = args.join(" ");
+        assert!(joined.contains("-c:v libaom-av1"));
+        assert!(joined.contains("-b:v 0 -crf 24"));
+        assert!(joined.contains("-cpu-used 8"));
+        assert!(joined.contains("-row-mt 1"));
+    }
+
+    #[test]
+    fn test_build_args_av1_without_encoder_is_an_error() {
+        let ffmpeg = make_ffmpeg_with_encoders(&["libx264"]);
+        let cfg = CompressionConfig {
+            codec: Some(VideoCodec::Av1),
+            ..CompressionConfig::default()
+        };
+        let res = ffmpeg.build_args(
+            &cfg,
+            &VideoInfo::default(),
+            "in.mp4",
+            "out.mp4",
+            "mp4",
+            None,
+        );
+        assert!(matches!(res, Err(CompressoError::IncompatibleOptions(_))));
+    }
+
+    #[test]
+    fn test_build_args_av1_rejects_avi() {
+        let ffmpeg = make_ffmpeg_with_encoders(&["libsvtav1"]);
+        let cfg = CompressionConfig {
+            codec: Some(VideoCodec::Av1),
+            ..CompressionConfig::default()
+        };
+        assert!(ffmpeg
+            .build_args(
+                &cfg,
+                &VideoInfo::default(),
+                "in.mp4",
+                "out.avi",
+                "avi",
+                None
+            )
+            .is_err());
+    }
+
+    #[test]
+    fn test_parse_encoder_list() {
+        let output = "Encoders:\n V..... = Video\n A..... = Audio\n ------\n \
+                      V....D libx264              libx264 H.264 / AVC\n \
+                      V....D libsvtav1            SVT-AV1(Scalable Video Technology for AV1) encoder\n \
+                      A....D aac                  AAC (Advanced Audio Coding)\n";
+        assert_eq!(
+            FFmpeg::parse_encoder_list(output),
+            vec!["libx264", "libsvtav1", "aac"]
+        );
+    }
+
+    // ---- --target-size ---------------------------------------------------------
+
+    #[test]
+    fn test_plan_target_bitrate_reserves_audio() {
+        // 25 MB over 60 s ≈ 3355 kbit/s total, minus 128k audio.
+        let plan =
+            FFmpeg::plan_target_bitrate(25 * 1024 * 1024, 60.0, 128, Some((1920, 1080)), 30.0)
+                .unwrap();
+        assert_eq!(plan.video_kbps, 3227);
+        assert_eq!(plan.scale, None, "1080p is fine at ~3.2 Mbit/s");
+    }
+
+    #[test]
+    fn test_plan_target_bitrate_downscales_starved_video() {
+        // 8 MB for five minutes leaves ~86 kbit/s: only the lowest rung makes sense.
+        let plan =
+            FFmpeg::plan_target_bitrate(8 * 1024 * 1024, 300.0, 128, Some((1920, 1080)), 30.0)
+                .unwrap();
+        assert_eq!(plan.video_kbps, 86);
+        assert_eq!(plan.scale, Some((-2, 240)));
+
+        // Portrait video scales by width instead.
+        let plan =
+            FFmpeg::plan_target_bitrate(20 * 1024 * 1024, 120.0, 128, Some((1080, 1920)), 30.0)
+                .unwrap();
+        assert_eq!(plan.scale, Some((720, -2)));
+    }
+
+    #[test]
+    fn test_plan_target_bitrate_unreachable() {
+        let err = FFmpeg::plan_target_bitrate(1024 * 1024, 600.0, 128, None, 30.0).unwrap_err();
+        match err {
+            CompressoError::TargetSizeUnreachable { target, minimum } => {
+                assert_eq!(target, 1024 * 1024);
+                assert!(minimum > target);
+            }
+            other => panic!("unexpected error: {other:?}"),
+        }
+        // Without a duration no bitrate can be computed.
+        assert!(FFmpeg::plan_target_bitrate(1024 * 1024, 0.0, 128, None, 30.0).is_err());
+    }
+
+    #[test]
+    fn test_build_args_target_size_uses_bitrate() {
+        let ffmpeg = make_ffmpeg_for_tests();
+        let cfg = CompressionConfig {
+            input_path: "in.mp4".to_string(),
+            target_size: Some(25 * 1024 * 1024),
+            ..CompressionConfig::default()
+        };
+        let info = VideoInfo {
+            duration_seconds: Some(60.0),
+            dimensions: Some((1920, 1080)),
+            fps: Some(30.0),
+            ..VideoInfo::default()
+        };
+        let args = ffmpeg
+            .build_args(&cfg, &info, "in.mp4", "out.mp4", "mp4", None)
+            .unwrap()
+            .join(" ");
+        assert!(args.contains("-b:v 3227k -maxrate 4840k -bufsize 6454k"));
+        assert!(args.contains("-b:a 128k"));
+        assert!(
+            !args.contains("-crf"),
+            "CRF must not be mixed with a bitrate"
+        );
+    }
+
+    #[test]
+    fn test_build_args_target_size_keeps_explicit_dimensions() {
+        let ffmpeg = make_ffmpeg_for_tests();
+        let cfg = CompressionConfig {
+            input_path: "in.mp4".to_string(),
+            target_size: Some(8 * 1024 * 1024),
+            width: Some(1280),
+            height: Some(720),
+            mute: true,
+            ..CompressionConfig::default()
+        };
+        let info = VideoInfo {
+            duration_seconds: Some(300.0),
+            dimensions: Some((1920, 1080)),
+            fps: Some(30.0),
+            ..VideoInfo::default()
+        };
+        let args = ffmpeg
+            .build_args(&cfg, &info, "in.mp4", "out.mp4", "mp4", None)
+            .unwrap()
+            .join(" ");
+        assert!(args.contains("scale=1280:720"));
+        assert!(!args.contains("-b:a"), "muted output reserves no audio");
+    }
+
+    // ---- --two-pass ------------------------------------------------------------
+
+    fn two_pass_args(
+        ffmpeg: &FFmpeg,
+        cfg: &CompressionConfig,
+        info: &VideoInfo,
+        format: &str,
+        number: u8,
+    ) -> Result<String> {
+        let pass = EncodePass {
+            number,
+            passlog: "/tmp/compresso-passlog-x",
+        };
+        let output = format!("out.{}", format);
+        ffmpeg
+            .build_args(cfg, info, "in.mp4", &output, format, Some(pass))
+            .map(|args| args.join(" "))
+    }
+
+    #[test]
+    fn test_build_args_two_pass_h264() {
+        let ffmpeg = make_ffmpeg_for_tests();
+        let cfg = CompressionConfig {
+            input_path: "in.mp4".to_string(),
+            target_size: Some(25 * 1024 * 1024),
+            two_pass: true,
+            ..CompressionConfig::default()
+        };
+        let info = VideoInfo {
+            duration_seconds: Some(60.0),
+            ..VideoInfo::default()
+        };
+
+        let first = two_pass_args(&ffmpeg, &cfg, &info, "mp4", 1).unwrap();
+        assert!(first.contains("-pass 1 -passlogfile /tmp/compresso-passlog-x"));
+        assert!(first.contains("-an"));
+        assert!(first.ends_with("-f null -"));
+        assert!(!first.contains("out.mp4"));
+        assert!(!first.contains("-movflags"), "null muxer rejects movflags");
+
+        let second = two_pass_args(&ffmpeg, &cfg, &info, "mp4", 2).unwrap();
+        assert!(second.contains("-pass 2 -passlogfile /tmp/compresso-passlog-x"));
+        assert!(second.contains("-movflags +faststart"));
+        assert!(second.ends_with("out.mp4"));
+    }
+
+    #[test]
+    fn test_build_args_two_pass_crf_rules() {
+        let ffmpeg = make_ffmpeg_for_tests();
+        let cfg = CompressionConfig {
+            input_path: "in.mp4".to_string(),
+            two_pass: true,
+            ..CompressionConfig::default()
+        };
+        let info = VideoInfo::default();
+
+        // x264 cannot run two passes at a constant rate factor...
+        assert!(matches!(
+            two_pass_args(&ffmpeg, &cfg, &info, "mp4", 1),
+            Err(CompressoError::IncompatibleOptions(_))
+        ));
+        // ...while VP9 uses it as constrained quality.
+        let vp9 = two_pass_args(&ffmpeg, &cfg, &info, "webm", 1).unwrap();
+        assert!(vp9.contains("-b:v 0 -crf 27 -pass 1"));
+    }
+
+    #[test]
+    fn test_build_args_two_pass_x265_stats_escaped() {
+        let ffmpeg = make_ffmpeg_for_tests();
+        let cfg = CompressionConfig {
+            input_path: "in.mp4".to_string(),
+            codec: Some(VideoCodec::Hevc),
+            target_size: Some(25 * 1024 * 1024),
+            two_pass: true,
+            ..CompressionConfig::default()
+        };
+        let info = VideoInfo {
+            duration_seconds: Some(60.0),
+            ..VideoInfo::default()
+        };
+        let pass = EncodePass {
+            number: 2,
+            passlog: r"C:\Temp\pl",
+        };
+        let args = ffmpeg
+            .build_args(&cfg, &info, "in.mp4", "out.mkv", "mkv", Some(pass))
+            .unwrap()
+            .join(" ");
+        assert!(args.contains(r"log-level=error:pass=2:stats=C\:\\Temp\\pl.log"));
+        assert!(!args.contains("-passlogfile"));
+    }
+
+    #[test]
+    fn test_build_args_two_pass_rejects_svtav1() {
+        let ffmpeg = make_ffmpeg_with_encoders(&["libsvtav1"]);
+        let cfg = CompressionConfig {
+            input_path: "in.mp4".to_string(),
+            codec: Some(VideoCodec::Av1),
+            two_pass: true,
+            ..CompressionConfig::default()
+        };
+        assert!(two_pass_args(&ffmpeg, &cfg, &VideoInfo::default(), "mkv", 1).is_err());
+    }
+
+    #[test]
+    fn test_temp_guard_removes_passlog_files() {
+        let dir = std::env::temp_dir();
+        let prefix = dir.join(format!("compresso-passlog-{}", nanoid::nanoid!(8)));
+        let stats = [
+            format!("{}-0.log", prefix.display()),
+            format!("{}-0.log.mbtree", prefix.display()),
+        ];
+        for path in &stats {
+            std::fs::write(path, b"stats").unwrap();
+        }
+
+        let mut guard = TempFileGuard::new(dir.join("compresso-guard-unused.mp4"));
+        guard.set_passlog_prefix(prefix);
+        // A successful encode keeps its output but still drops the statistics.
+        guard.keep();
+        drop(guard);
+
+        for path in &stats {
+            assert!(!Path::new(path).exists(), "{path} should be removed");
+        }
+    }
+
+    // ---- --measure-quality -----------------------------------------------------
+
+    #[test]
+    fn test_parse_filter_list() {
+        let output = "Filters:\n  T.. = Timeline support\n  .S. = Slice threading\n  \
+                      A = Audio input/output\n  | = Source or sink filter\n \
+                      TS. ssim              VV->V      Calculate the SSIM between two video streams.\n \
+                      T.. libvmaf           VV->V      Calculate the VMAF between two video streams.\n \
+                      ... anullsrc          |->A       Null audio source, return empty audio frames.\n";
+        assert_eq!(
+            FFmpeg::parse_filter_list(output),
+            vec!["ssim", "libvmaf", "anullsrc"]
+        );
+    }
+
+    #[test]
+    fn test_parse_quality_metrics() {
+        let stderr = "[Parsed_ssim_6 @ 0x55] SSIM Y:0.985 (18.2) U:0.991 (20.5) V:0.990 (20.1) All:0.987412 (18.98)\n\
+                      [Parsed_psnr_7 @ 0x56] PSNR y:40.12 u:44.01 v:43.80 average:41.203375 min:35.10 max:48.70\n\
+                      [Parsed_libvmaf_8 @ 0x57] VMAF score: 94.318712\n";
+        let metrics = FFmpeg::parse_quality_metrics(stderr);
+        assert_eq!(metrics.ssim, Some(0.987412));
+        assert_eq!(metrics.psnr, Some(41.203375));
+        assert_eq!(metrics.vmaf, Some(94.318712));
+
+        // Identical frames report `average:inf`, which is not a usable score.
+        let metrics = FFmpeg::parse_quality_metrics("PSNR y:inf u:inf v:inf average:inf");
+        assert_eq!(metrics, QualityMetrics::default());
+    }
+
+    #[test]
+    fn test_build_quality_args_aligns_frames() {
+        let ffmpeg = make_ffmpeg_for_tests();
+        let cfg = CompressionConfig {
+            input_path: "in.mp4".to_string(),
+            width: Some(1280),
+            height: Some(720),
+            fps: Some(30),
+            transforms: VideoTransforms {
+                rotate: Some(90),
+                ..VideoTransforms::default()
+            },
+            ..CompressionConfig::default()
+        };
+        let info = VideoInfo {
+            dimensions: Some((1920, 1080)),
+            ..VideoInfo::default()
+        };
+
+        let args = ffmpeg
+            .build_quality_args(&cfg, &info, "in.mp4", "out.mp4", None, false)
+            .unwrap();
+        // Distorted input first, reference second.
+        assert_eq!(&args[2..6], ["-i", "out.mp4", "-i", "in.mp4"]);
+        let graph = &args[7];
+        // The encode is scaled back to the rotated source size...
+        assert!(graph.contains("[0:v]scale=1080:1920:flags=bicubic,format=yuv420p"));
+        // ...and the source gets the same rotation and frame rate.
+        assert!(graph.contains("[1:v]transpose=1,fps=30,format=yuv420p"));
+        assert!(graph.ends_with("[d0][r0]ssim;[d1][r1]psnr"));
+
+        let args = ffmpeg
+            .build_quality_args(&cfg, &info, "in.mp4", "out.mp4", None, true)
+            .unwrap();
+        assert!(args[7].contains("split=3[d0][d1][d2]"));
+        assert!(args[7].ends_with("[d2][r2]libvmaf"));
+    }
+
+    #[test]
+    fn test_build_quality_args_overlays_reference() {
+        let ffmpeg = make_ffmpeg_for_tests();
+        let cfg = CompressionConfig {
+            input_path: "in.mp4".to_string(),
+            overlay: Overlay {
+                image: Some("logo.png".to_string()),
+                text: Some("Demo".to_string()),
+                ..Overlay::default()
+            },
+            ..CompressionConfig::default()
+        };
+        let info = VideoInfo {
+            dimensions: Some((1920, 1080)),
+            ..VideoInfo::default()
+        };
+        let args = ffmpeg
+            .build_quality_args(&cfg, &info, "in.mp4", "out.mp4", None, false)
+            .unwrap();
+        assert_eq!(
+            &args[2..8],
+            ["-i", "out.mp4", "-i", "in.mp4", "-i", "logo.png"]
+        );
+        let graph = &args[9];
+        // The reference carries the same text and watermark as the encode.
+        assert!(graph.contains("[1:v]drawtext=text=Demo:"));
+        assert!(graph.contains("[main];[2:v]format=rgba[wm];[main][wm]overlay=x=W-w-20:y=H-h-20,"));
+        assert!(graph.contains("setpts=PTS-STARTPTS[v];[v]split=2[r0][r1];"));
+        assert!(graph.ends_with("[d0][r0]ssim;[d1][r1]psnr"));
+    }
+
+    // ---- --target-quality ------------------------------------------------------
+
+    #[test]
+    fn test_bisect_crf_picks_highest_passing_crf() {
+        // Score falls by one point per CRF step: CRF 25 scores exactly 95.
+        let mut calls = 0;
+        let (crf, score, met) = FFmpeg::bisect_crf(16, 38, 95.0, |crf| {
+            calls += 1;
+            Ok(120.0 - crf as f64)
+        })
+        .unwrap();
+        assert_eq!((crf, score, met), (25, 95.0, true));
+        assert!(calls <= 5, "binary search, not a sweep ({calls} encodes)");
+    }
+
+    #[test]
+    fn test_bisect_crf_unreachable_target() {
+        let (crf, score, met) =
+            FFmpeg::bisect_crf(16, 38, 99.0, |crf| Ok(90.0 - crf as f64)).unwrap();
+        assert_eq!((crf, met), (16, false));
+        assert_eq!(score, 74.0);
+    }
+
+    #[test]
+    fn test_bisect_crf_propagates_errors() {
+        let res = FFmpeg::bisect_crf(16, 38, 95.0, |_| Err(CompressoError::Cancelled));
+        assert!(matches!(res, Err(CompressoError::Cancelled)));
+    }
+
+    #[test]
+    fn test_sample_windows() {
+        // Short clips are measured whole.
+        assert_eq!(FFmpeg::sample_windows(20.0), vec![(0.0, 20.0)]);
+
+        let windows = FFmpeg::sample_windows(100.0);
+        assert_eq!(windows.len(), CRF_SEARCH_SAMPLES);
+        assert_eq!(windows[0], (18.0, CRF_SEARCH_SAMPLE_SECONDS));
+        assert!(windows
+            .iter()
+            .all(|&(start, len)| start >= 0.0 && start + len <= 100.0));
+    }
+
+    #[test]
+    fn test_build_args_explicit_crf_overrides_quality() {
+        let ffmpeg = make_ffmpeg_for_tests();
+        let cfg = CompressionConfig {
+            input_path: "in.mp4".to_string(),
+            quality: 70,
+            crf: Some(19),
+            ..CompressionConfig::default()
+        };
+        let args = ffmpeg
+            .build_args(
+                &cfg,
+                &VideoInfo::default(),
+                "in.mp4",
+                "out.mp4",
+                "mp4",
+                None,
+            )
+            .unwrap()
+            .join(" ");
+        assert!(args.contains("-crf 19"));
+    }
+
+    #[test]
+    fn test_build_quality_args_window_trims_reference() {
+        let ffmpeg = make_ffmpeg_for_tests();
+        let cfg = CompressionConfig::default();
+        let args = ffmpeg
+            .build_quality_args(
+                &cfg,
+                &VideoInfo::default(),
+                "in.mp4",
+                "sample.mp4",
+                Some((18.0, 4.0)),
+                false,
+            )
+            .unwrap();
+        // The trim applies to the reference (second) input only.
+        assert_eq!(
+            &args[2..10],
+            [
+                "-i",
+                "sample.mp4",
+                "-ss",
+                "18.000",
+                "-t",
+                "4.000",
+                "-i",
+                "in.mp4"
+            ]
+        );
+    }
+
+    // ---- audio -----------------------------------------------------------------
+
+    fn audio_args(audio: AudioSettings, format: &str) -> Result<String> {
+        let cfg = CompressionConfig {
+            input_path: "in.mp4".to_string(),
+            audio,
+            ..CompressionConfig::default()
+        };
+        let output = format!("out.{}", format);
+        make_ffmpeg_for_tests()
+            .build_args(&cfg, &VideoInfo::default(), "in.mp4", &output, format, None)
+            .map(|args| args.join(" "))
+    }
+
+    #[test]
+    fn test_build_args_audio_container_defaults() {
+        let defaults = AudioSettings::default();
+        assert!(audio_args(defaults, "mp4")
+            .unwrap()
+            .contains("-c:a aac -b:a 128k"));
+        assert!(audio_args(defaults, "webm")
+            .unwrap()
+            .contains("-c:a libopus -b:a 96k"));
+        assert!(audio_args(defaults, "avi")
+            .unwrap()
+            .contains("-c:a libmp3lame -b:a 128k"));
+    }
+
+    #[test]
+    fn test_build_args_audio_options() {
+        let audio = AudioSettings {
+            codec: Some(AudioCodec::Opus),
+            bitrate_kbps: Some(64),
+            channels: Some(1),
+            sample_rate: Some(48000),
+        };
+        let args = audio_args(audio, "mkv").unwrap();
+        assert!(args.contains("-c:a libopus -b:a 64k -ac 1 -ar 48000"));
+    }
+
+    #[test]
+    fn test_build_args_audio_validation() {
+        // Opus cannot go into MOV, AAC cannot go into WebM.
+        let opus = AudioSettings {
+            codec: Some(AudioCodec::Opus),
+            ..AudioSettings::default()
+        };
+        assert!(matches!(
+            audio_args(opus, "mov"),
+            Err(CompressoError::IncompatibleOptions(_))
+        ));
+        let aac = AudioSettings {
+            codec: Some(AudioCodec::Aac),
+            ..AudioSettings::default()
+        };
+        assert!(audio_args(aac, "webm").is_err());
+
+        // libopus only accepts its own sample rates.
+        let opus_44k = AudioSettings {
+            sample_rate: Some(44100),
+            ..opus
+        };
+        assert!(audio_args(opus_44k, "webm").is_err());
+
+        // Stream copy cannot be re-parameterised.
+        let copy_mono = AudioSettings {
+            codec: Some(AudioCodec::Copy),
+            channels: Some(1),
+            ..AudioSettings::default()
+        };
+        assert!(audio_args(copy_mono, "mp4").is_err());
+        let copy = AudioSettings {
+            codec: Some(AudioCodec::Copy),
+            ..AudioSettings::default()
+        };
+        let args = audio_args(copy, "mp4").unwrap();
+        assert!(args.contains("-c:a copy") && !args.contains("-b:a"));
+    }
+
+    #[test]
+    fn test_target_size_budgets_actual_audio_bitrate() {
+        let cfg = CompressionConfig {
+            input_path: "in.mp4".to_string(),
+            target_size: Some(25 * 1024 * 1024),
+            audio: AudioSettings {
+                bitrate_kbps: Some(64),
+                ..AudioSettings::default()
+            },
+            ..CompressionConfig::default()
+        };
+        let info = VideoInfo {
+            duration_seconds: Some(60.0),
+            ..VideoInfo::default()
+        };
+        let args = make_ffmpeg_for_tests()
+            .build_args(&cfg, &info, "in.mp4", "out.mp4", "mp4", None)
+            .unwrap()
+            .join(" ");
+        // 3355 kbit/s total minus 64k of audio.
+        assert!(args.contains("-b:v 3291k"));
+        assert!(args.contains("-b:a 64k"));
+    }
+
+    fn remux_info(video: &str, audio: Option<&str>) -> VideoInfo {
+        VideoInfo {
+            duration_seconds: Some(60.0),
+            video_codec: Some(video.to_string()),
+            audio_codec: audio.map(str::to_string),
+            ..VideoInfo::default()
+        }
+    }
+
+    #[test]
+    fn test_build_args_auto_remux_on_container_change() {
+        let cfg = CompressionConfig {
+            input_path: "in.mkv".to_string(),
+            remux: RemuxMode::Auto,
+            ..CompressionConfig::default()
+        };
+        let ffmpeg = make_ffmpeg_for_tests();
+        let args = ffmpeg
+            .build_args(
+                &cfg,
+                &remux_info("hevc", Some("aac")),
+                "in.mkv",
+                "out.mp4",
+                "mp4",
+                None,
+            )
+            .unwrap()
+            .join(" ");
+        assert!(args
+            .contains("-map 0:V:0 -map 0:a:0? -c copy -tag:v hvc1 -movflags +faststart+use_metadata_tags -map_metadata 0 out.mp4"));
+        assert!(!args.contains("libx26"));
+
+        // Same container: this is a compression run, not a remux.
+        let args = ffmpeg
+            .build_args(
+                &cfg,
+                &remux_info("h264", Some("aac")),
+                "in.mp4",
+                "out.mp4",
+                "mp4",
+                None,
+            )
+            .unwrap()
+            .join(" ");
+        assert!(args.contains("-c:v libx264"));
+
+        // Opus audio does not fit in MOV, so fall back to encoding.
+        let args = ffmpeg
+            .build_args(
+                &cfg,
+                &remux_info("h264", Some("opus")),
+                "in.mkv",
+                "out.mov",
+                "mov",
+                None,
+            )
+            .unwrap()
+            .join(" ");
+        assert!(args.contains("-c:v libx264"));
+
+        // Any filter needs decoded frames.
+        let scaled = CompressionConfig {
+            width: Some(1280),
+            ..cfg.clone()
+        };
+        let args = ffmpeg
+            .build_args(
+                &scaled,
+                &remux_info("h264", None),
+                "in.mkv",
+                "out.mp4",
+                "mp4",
+                None,
+            )
+            .unwrap()
+            .join(" ");
+        assert!(args.contains("-c:v libx264"));
+    }
+
+    #[test]
+    fn test_build_args_forced_remux_validation() {
+        let cfg = CompressionConfig {
+            input_path: "in.mkv".to_string(),
+            remux: RemuxMode::Always,
+            mute: true,
+            ..CompressionConfig::default()
+        };
+        let ffmpeg = make_ffmpeg_for_tests();
+        let args = ffmpeg
+            .build_args(
+                &cfg,
+                &remux_info("vp9", Some("opus")),
+                "in.mkv",
+                "out.webm",
+                "webm",
+                None,
+            )
+            .unwrap()
+            .join(" ");
+        assert!(args.contains("-map 0:V:0 -c copy -an -map_metadata 0 out.webm"));
+
+        let rotated = CompressionConfig {
+            transforms: VideoTransforms {
+                rotate: Some(90),
+                ..VideoTransforms::default()
+            },
+            ..cfg.clone()
+        };
+        assert!(ffmpeg
+            .build_args(
+                &rotated,
+                &remux_info("vp9", None),
+                "in.mkv",
+                "out.webm",
+                "webm",
+                None
+            )
+            .is_err());
+        // H.264 cannot live in WebM.
+        assert!(ffmpeg
+            .build_args(
+                &cfg,
+                &remux_info("h264", None),
+                "in.mkv",
+                "out.webm",
+                "webm",
+                None
+            )
+            .is_err());
+    }
+
+    #[test]
+    fn test_build_args_trim_seeks_input_and_limits_output() {
+        let cfg = CompressionConfig {
+            input_path: "in.mp4".to_string(),
+            trim: Some(TrimRange {
+                start: 10.0,
+                end: Some(15.5),
+            }),
+            ..CompressionConfig::default()
+        };
+        let info = VideoInfo {
+            duration_seconds: Some(60.0),
+            ..VideoInfo::default()
+        };
+        let args = make_ffmpeg_for_tests()
+            .build_args(&cfg, &info, "in.mp4", "out.mp4", "mp4", None)
+            .unwrap();
+        assert_eq!(args[..4], ["-ss", "10.000", "-i", "in.mp4"]);
+        assert!(args.join(" ").contains("-t 5.500"));
+        assert_eq!(FFmpeg::output_duration(&cfg, &info), Some(5.5));
+
+        // An end past the source is clamped to the real duration.
+        let open_ended = CompressionConfig {
+            trim: Some(TrimRange {
+                start: 50.0,
+                end: Some(90.0),
+            }),
+            ..cfg.clone()
+        };
+        assert_eq!(FFmpeg::output_duration(&open_ended, &info), Some(10.0));
+    }
+
+    #[test]
+    fn test_build_args_trim_validation() {
+        let ffmpeg = make_ffmpeg_for_tests();
+        let info = VideoInfo {
+            duration_seconds: Some(60.0),
+            ..VideoInfo::default()
+        };
+        for trim in [
+            TrimRange {
+                start: 60.0,
+                end: None,
+            },
+            TrimRange {
+                start: 20.0,
+                end: Some(10.0),
+            },
+        ] {
+            let cfg = CompressionConfig {
+                input_path: "in.mp4".to_string(),
+                trim: Some(trim),
+                ..CompressionConfig::default()
+            };
+            assert!(ffmpeg
+                .build_args(&cfg, &info, "in.mp4", "out.mp4", "mp4", None)
+                .is_err());
+        }
+    }
+
+    #[test]
+    fn test_target_size_budgets_trimmed_duration() {
+        let cfg = CompressionConfig {
+            input_path: "in.mp4".to_string(),
+            target_size: Some(25 * 1024 * 1024),
+            trim: Some(TrimRange {
+                start: 0.0,
+                end: Some(30.0),
+            }),
+            ..CompressionConfig::default()
+        };
+        let info = VideoInfo {
+            duration_seconds: Some(600.0),
+            ..VideoInfo::default()
+        };
+        let args = make_ffmpeg_for_tests()
+            .build_args(&cfg, &info, "in.mp4", "out.mp4", "mp4", None)
+            .unwrap()
+            .join(" ");
+        // 25 MB over 30 s, not over the full 10 minutes.
+        assert!(args.contains("-b:v 6582k"), "{}", args);
+    }
+
+    #[test]
+    fn test_auto_remux_skipped_when_trimming() {
+        let cfg = CompressionConfig {
+            input_path: "in.mkv".to_string(),
+            remux: RemuxMode::Auto,
+            trim: Some(TrimRange {
+                start: 5.0,
+                end: None,
+            }),
+            ..CompressionConfig::default()
+        };
+        let args = make_ffmpeg_for_tests()
+            .build_args(
+                &cfg,
+                &remux_info("h264", None),
+                "in.mkv",
+                "out.mp4",
+                "mp4",
+                None,
+            )
+            .unwrap()
+            .join(" ");
+        assert!(args.contains("-c:v libx264"));
+    }
+
+    // ---- scaling ----------------------------------------------------------------
+
+    fn scale_for(cfg: CompressionConfig, frame: (u32, u32)) -> Option<(i32, i32)> {
+        FFmpeg::requested_scale(&cfg, Some(frame))
+    }
+
+    #[test]
+    fn test_requested_scale_single_dimension_keeps_aspect() {
+        let width = CompressionConfig {
+            width: Some(1280),
+            ..CompressionConfig::default()
+        };
+        assert_eq!(scale_for(width.clone(), (1920, 1080)), Some((1280, -2)));
+        // Never upscale by default...
+        assert_eq!(scale_for(width.clone(), (640, 360)), None);
+        // ...unless asked to.
+        let upscale = CompressionConfig {
+            allow_upscale: true,
+            ..width
+        };
+        assert_eq!(scale_for(upscale, (640, 360)), Some((1280, -2)));
+
+        let height = CompressionConfig {
+            height: Some(720),
+            ..CompressionConfig::default()
+        };
+        assert_eq!(scale_for(height, (1920, 1080)), Some((-2, 720)));
+        assert_eq!(
+            FFmpeg::scaled_dimensions((-2, 720), (1920, 1080)),
+            (1280, 720)
+        );
+        assert_eq!(
+            FFmpeg::scaled_dimensions((1280, -2), (1080, 1920)),
+            (1280, 2276)
+        );
+    }
+
+    #[test]
+    fn test_requested_scale_fits_long_edge() {
+        let fit = CompressionConfig {
+            max_resolution: Some("720p".parse().unwrap()),
+            ..CompressionConfig::default()
+        };
+        assert_eq!(scale_for(fit.clone(), (1920, 1080)), Some((1280, -2)));
+        // Vertical phone video: the long edge is the height.
+        assert_eq!(scale_for(fit.clone(), (1080, 1920)), Some((720, -2)));
+        // Ultra-wide: the width is the limit.
+        assert_eq!(scale_for(fit.clone(), (2560, 1080)), Some((1280, -2)));
+        // 4:3 is bounded by the short edge.
+        assert_eq!(scale_for(fit.clone(), (1440, 1080)), Some((-2, 720)));
+        // Already small enough.
+        assert_eq!(scale_for(fit.clone(), (854, 480)), None);
+        assert_eq!(scale_for(fit, (1280, 720)), None);
+    }
+
+    #[test]
+    fn test_build_args_fit_before_target_size_downscale() {
+        let cfg = CompressionConfig {
+            input_path: "in.mp4".to_string(),
+            max_resolution: Some("1080p".parse().unwrap()),
+            ..CompressionConfig::default()
+        };
+        let info = VideoInfo {
+            duration_seconds: Some(60.0),
+            dimensions: Some((2160, 3840)),
+            fps: Some(30.0),
+            ..VideoInfo::default()
+        };
+        let ffmpeg = make_ffmpeg_for_tests();
+        let args = ffmpeg
+            .build_args(&cfg, &info, "in.mp4", "out.mp4", "mp4", None)
+            .unwrap()
+            .join(" ");
+        assert!(args.contains("scale=1080:-2,pad="));
+
+        // A tight size budget shrinks the fitted frame further.
+        let tight = CompressionConfig {
+            target_size: Some(4 * 1024 * 1024),
+            mute: true,
+            ..cfg
+        };
+        let args = ffmpeg
+            .build_args(&tight, &info, "in.mp4", "out.mp4", "mp4", None)
+            .unwrap()
+            .join(" ");
+        assert!(!args.contains("scale=1080:-2"), "{}", args);
+        assert!(args.contains("scale="));
+    }
+
+    /// Build an FFmpeg handle without probing PATH (the ffmpeg_path is never
+    /// actually executed by the pure build_args/validate_* functions under test).
+    fn track(index: u32, kind: StreamKind, codec: &str, language: Option<&str>) -> StreamInfo {
+        StreamInfo {
+            index,
+            kind,
+            codec: Some(codec.to_string()),
+            profile: None,
+            bitrate: None,
+            language: language.map(str::to_string),
+            title: None,
+            default: false,
+            width: None,
+            height: None,
+            fps: None,
+            pix_fmt: None,
+            sample_aspect_ratio: None,
+            color_range: None,
+            color_space: None,
+            color_transfer: None,
+            color_primaries: None,
+            rotation: None,
+            mastering_display: None,
+            max_cll: None,
+            attached_pic: false,
+            channels: None,
+            channel_layout: None,
+            sample_rate: None,
+        }
+    }
+
+    /// A film with cover art, two audio tracks and two subtitle tracks.
+    fn multi_track_info() -> VideoInfo {
+        let mut cover = track(5, StreamKind::Video, "mjpeg", None);
+        cover.attached_pic = true;
+        let mut commentary = track(2, StreamKind::Audio, "ac3", Some("rus"));
+        commentary.default = true;
+        VideoInfo {
+            duration_seconds: Some(60.0),
+            video_codec: Some("h264".to_string()),
+            audio_codec: Some("aac".to_string()),
+            streams: vec![
+                track(0, StreamKind::Video, "h264", None),
+                track(1, StreamKind::Audio, "aac", Some("eng")),
+                commentary,
+                track(3, StreamKind::Subtitle, "subrip", Some("eng")),
+                track(4, StreamKind::Subtitle, "hdmv_pgs_subtitle", Some("rus")),
+                cover,
+            ],
+            ..VideoInfo::default()
+        }
+    }
+
+    #[test]
+    fn test_build_args_maps_default_audio_track() {
+        let args = make_ffmpeg_for_tests()
+            .build_args(
+                &CompressionConfig::default(),
+                &multi_track_info(),
+                "in.mkv",
+                "out.mp4",
+                "mp4",
+                None,
+            )
+            .unwrap()
+            .join(" ");
+        // The track flagged default wins over the first; cover art is skipped.
+        assert!(args.contains("-map 0:V:0 -map 0:2 -c:v"));
+        assert!(!args.contains("0:5"));
+        assert!(!args.contains("-c:s"));
+    }
+
+    #[test]
+    fn test_build_args_selects_audio_and_subtitle_tracks() {
+        let ffmpeg = make_ffmpeg_for_tests();
+        let info = multi_track_info();
+        let cfg = CompressionConfig {
+            audio_tracks: TrackSelection::All,
+            subtitles: TrackSelection::Languages(vec!["ENG".to_string()]),
+            ..CompressionConfig::default()
+        };
+        let args = ffmpeg
+            .build_args(&cfg, &info, "in.mkv", "out.mp4", "mp4", None)
+            .unwrap()
+            .join(" ");
+        assert!(args.contains("-map 0:V:0 -map 0:1 -map 0:2 -map 0:3 "));
+        assert!(args.contains("-c:s:0 mov_text"));
+
+        // Image subtitles only fit in MKV, where they are copied.
+        let cfg = CompressionConfig {
+            subtitles: TrackSelection::All,
+            ..CompressionConfig::default()
+        };
+        assert!(ffmpeg
+            .build_args(&cfg, &info, "in.mkv", "out.mp4", "mp4", None)
+            .is_err());
+        let args = ffmpeg
+            .build_args(&cfg, &info, "in.mkv", "out.mkv", "mkv", None)
+            .unwrap()
+            .join(" ");
+        assert!(args.contains("-c:s:0 copy -c:s:1 copy"));
+
+        let cfg = CompressionConfig {
+            audio_tracks: TrackSelection::Numbers(vec![3]),
+            ..CompressionConfig::default()
+        };
+        let err = ffmpeg
+            .build_args(&cfg, &info, "in.mkv", "out.mkv", "mkv", None)
+            .unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("no audio track 3 (the file has 2)"));
+
+        let cfg = CompressionConfig {
+            audio_tracks: TrackSelection::Languages(vec!["deu".to_string()]),
+            ..CompressionConfig::default()
+        };
+        assert!(ffmpeg
+            .build_args(&cfg, &info, "in.mkv", "out.mkv", "mkv", None)
+            .is_err());
+    }
+
+    #[test]
+    fn test_build_args_track_specifiers_without_probe_data() {
+        let cfg = CompressionConfig {
+            audio_tracks: TrackSelection::Languages(vec!["eng".to_string()]),
+            subtitles: TrackSelection::All,
+            ..CompressionConfig::default()
+        };
+        let args = make_ffmpeg_for_tests()
+            .build_args(
+                &cfg,
+                &remux_info("h264", Some("aac")),
+                "in.mkv",
+                "out.webm",
+                "webm",
+                None,
+            )
+            .unwrap()
+            .join(" ");
+        assert!(args.contains("-map 0:V:0 -map 0:a:m:language:eng? -map 0:s? "));
+        assert!(args.contains("-c:s webvtt"));
+
+        let cfg = CompressionConfig {
+            subtitles: TrackSelection::All,
+            ..CompressionConfig::default()
+        };
+        assert!(make_ffmpeg_for_tests()
+            .build_args(
+                &cfg,
+                &remux_info("h264", Some("aac")),
+                "in.mkv",
+                "out.avi",
+                "avi",
+                None,
+            )
+            .is_err());
+    }
+
+    #[test]
+    fn test_remux_keeps_subtitles_after_copy() {
+        let cfg = CompressionConfig {
+            remux: RemuxMode::Always,
+            audio_tracks: TrackSelection::All,
+            subtitles: TrackSelection::Numbers(vec![1]),
+            ..CompressionConfig::default()
+        };
+        let args = make_ffmpeg_for_tests()
+            .build_args(&cfg, &multi_track_info(), "in.mkv", "out.mkv", "mkv", None)
+            .unwrap()
+            .join(" ");
+        assert!(args.contains("-map 0:1 -map 0:2 -map 0:3 -c copy -c:s:0 copy"));
+
+        // AC-3 does not fit in WebM, so every kept track is checked.
+        assert!(make_ffmpeg_for_tests()
+            .build_args(
+                &cfg,
+                &multi_track_info(),
+                "in.mkv",
+                "out.webm",
+                "webm",
+                None
+            )
+            .is_err());
+    }
+
+    #[test]
+    fn test_escape_filter_path() {
+        assert_eq!(escape_filter_path("subs.srt"), "subs.srt");
+        assert_eq!(
+            escape_filter_path(r"C:\Users\Ира\фильм [1].srt"),
+            r"C\\:\\\\Users\\\\Ира\\\\фильм \[1\].srt"
+        );
+        assert_eq!(
+            escape_filter_path(r"\\?\D:\it's.ass"),
+            r"D\\:\\\\it\\\'s.ass"
+        );
+        assert_eq!(escape_filter_path("/tmp/a,b;c.srt"), r"/tmp/a\,b\;c.srt");
+    }
+
+    #[test]
+    fn test_build_args_burns_subtitles_after_scaling() {
+        let cfg = CompressionConfig {
+            width: Some(1280),
+            burn_subtitles: Some(BurnSubtitles::File("subs/film.ass".to_string())),
+            transforms: VideoTransforms {
+                crop: Some(crate::domain::CropCoordinates {
+                    width: 1920,
+                    height: 800,
+                    x: 0,
+                    y: 140,
+                }),
+                ..VideoTransforms::default()
+            },
+            ..CompressionConfig::default()
+        };
+        let info = VideoInfo {
+            dimensions: Some((1920, 1080)),
+            ..VideoInfo::default()
+        };
+        let args = make_ffmpeg_for_tests()
+            .build_args(&cfg, &info, "in.mkv", "out.mp4", "mp4", None)
+            .unwrap();
+        let vf = &args[args.iter().position(|a| a == "-vf").unwrap() + 1];
+        assert_eq!(
+            vf,
+            "crop=1920:800:0:140,scale=1280:-2,pad=ceil(iw/2)*2:ceil(ih/2)*2,ass=subs/film.ass"
+        );
+
+        let cfg = CompressionConfig {
+            burn_subtitles: Some(BurnSubtitles::File("notes.txt".to_string())),
+            ..CompressionConfig::default()
+        };
+        assert!(make_ffmpeg_for_tests()
+            .build_args(&cfg, &info, "in.mkv", "out.mp4", "mp4", None)
+            .is_err());
+    }
+
+    #[test]
+    fn test_build_args_burns_embedded_subtitle_track() {
+        let ffmpeg = make_ffmpeg_for_tests();
+        let info = multi_track_info();
+        let cfg = CompressionConfig {
+            burn_subtitles: Some(BurnSubtitles::Track(1)),
+            trim: Some(TrimRange {
+                start: 30.0,
+                end: None,
+            }),
+            ..CompressionConfig::default()
+        };
+        let args = ffmpeg
+            .build_args(&cfg, &info, "C:\\in.mkv", "out.mp4", "mp4", None)
+            .unwrap()
+            .join(" ");
+        // Seeking resets timestamps, so the subtitle clock is shifted back.
+        assert!(args
+            .contains(r"setpts=PTS+30.000/TB,subtitles=C\\:\\\\in.mkv:si=0,setpts=PTS-STARTPTS"));
+
+        // Track 2 is a PGS (image) track; track 3 does not exist.
+        for number in [2, 3] {
+            let cfg = CompressionConfig {
+                burn_subtitles: Some(BurnSubtitles::Track(number)),
+                ..CompressionConfig::default()
+            };
+            assert!(ffmpeg
+                .build_args(&cfg, &info, "in.mkv", "out.mp4", "mp4", None)
+                .is_err());
+        }
+
+        // Burning in needs decoded frames, so it rules out stream copy.
+        let cfg = CompressionConfig {
+            remux: RemuxMode::Always,
+            burn_subtitles: Some(BurnSubtitles::Track(1)),
+            ..CompressionConfig::default()
+        };
+        assert!(ffmpeg
+            .build_args(&cfg, &info, "in.mkv", "out.mkv", "mkv", None)
+            .is_err());
+    }
+
+    #[test]
+    fn test_build_args_metadata_modes() {
+        let ffmpeg = make_ffmpeg_for_tests();
+        let info = VideoInfo {
+            creation_time: Some("2024-06-01T18:22:05.000000Z".to_string()),
+            ..remux_info("h264", Some("aac"))
+        };
+        let build = |metadata: MetadataMode, format: &str| {
+            let cfg = CompressionConfig {
+                metadata,
+                ..CompressionConfig::default()
+            };
+            ffmpeg
+                .build_args(
+                    &cfg,
+                    &info,
+                    "in.mov",
+                    &format!("out.{}", format),
+                    format,
+                    None,
+                )
+                .unwrap()
+                .join(" ")
+        };
+
+        let keep = build(MetadataMode::Keep, "mp4");
+        assert!(keep.contains("-movflags +faststart+use_metadata_tags"));
+        assert!(keep.contains("-map_metadata 0 out.mp4"));
+
+        let strip = build(MetadataMode::Strip, "mp4");
+        assert!(strip.contains("-movflags +faststart "));
+        assert!(strip.contains("-map_metadata -1 -map_metadata:s -1 -map_chapters -1 out.mp4"));
+
+        let minimal = build(MetadataMode::Minimal, "mkv");
+        assert!(minimal.contains(
+            "-map_metadata -1 -metadata creation_time=2024-06-01T18:22:05.000000Z out.mkv"
+        ));
+        assert!(!minimal.contains("-map_metadata:s"));
+    }
+
+    #[test]
+    fn test_copy_mtime() {
+        let dir = std::env::temp_dir();
+        let from = dir.join(format!("compresso-mtime-{}.mp4", nanoid::nanoid!(8)));
+        let to = dir.join(format!("compresso-mtime-{}.mp4", nanoid::nanoid!(8)));
+        std::fs::write(&from, b"source").unwrap();
+        std::fs::write(&to, b"output").unwrap();
+        let past = filetime::FileTime::from_unix_time(1_600_000_000, 0);
+        filetime::set_file_mtime(&from, past).unwrap();
+
+        FFmpeg::copy_mtime(&from.to_string_lossy(), &to.to_string_lossy()).unwrap();
+        let modified =
+            filetime::FileTime::from_last_modification_time(&std::fs::metadata(&to).unwrap());
+        assert_eq!(modified, past);
+
+        let _ = std::fs::remove_file(&from);
+        let _ = std::fs::remove_file(&to);
+    }
+
+    fn hdr_info(transfer: &str) -> VideoInfo {
+        let mut video = track(0, StreamKind::Video, "hevc", None);
+        video.color_primaries = Some("bt2020".to_string());
+        video.color_transfer = Some(transfer.to_string());
+        video.color_space = Some("bt2020nc".to_string());
+        VideoInfo {
+            duration_seconds: Some(60.0),
+            dimensions: Some((3840, 2160)),
+            video_codec: Some("hevc".to_string()),
+            streams: vec![video],
+            ..VideoInfo::default()
+        }
+    }
+
+    fn make_ffmpeg_with_filters(filters: &[&str]) -> FFmpeg {
+        let ffmpeg = make_ffmpeg_for_tests();
+        let _ = ffmpeg
+            .filters
+            .set(filters.iter().map(|f| f.to_string()).collect());
+        ffmpeg
+    }
+
+    #[test]
+    fn test_build_args_hdr_defaults_by_codec() {
+        let ffmpeg = make_ffmpeg_with_filters(&["zscale", "tonemap"]);
+        let info = hdr_info("smpte2084");
+
+        // H.264 cannot carry HDR: tone-map to BT.709.
+        let cfg = CompressionConfig::default();
+        let args = ffmpeg
+            .build_args(&cfg, &info, "in.mov", "out.mp4", "mp4", None)
+            .unwrap()
+            .join(" ");
+        assert!(args.contains(
+            "-pix_fmt yuv420p -color_primaries bt709 -color_trc bt709 -colorspace bt709"
+        ));
+        assert!(args.contains(&format!(
+            "pad=ceil(iw/2)*2:ceil(ih/2)*2,{}",
+            TONEMAP_FILTERS
+        )));
+
+        // HEVC keeps 10-bit HDR with the source tags.
+        let cfg = CompressionConfig {
+            codec: Some(VideoCodec::Hevc),
+            ..CompressionConfig::default()
+        };
+        let args = ffmpeg
+            .build_args(&cfg, &info, "in.mov", "out.mp4", "mp4", None)
+            .unwrap()
+            .join(" ");
+        assert!(args.contains(
+            "-pix_fmt yuv420p10le -color_primaries bt2020 -color_trc smpte2084 -colorspace bt2020nc"
+        ));
+        assert!(!args.contains("zscale"));
+        // Without mastering data x265 still flags the stream as HDR10.
+        assert!(args.contains("-x265-params log-level=error:hdr10=1 "));
+
+        // SDR input is left alone.
+        let args = ffmpeg
+            .build_args(
+                &CompressionConfig::default(),
+                &multi_track_info(),
+                "in.mkv",
+                "out.mp4",
+                "mp4",
+                None,
+            )
+            .unwrap()
+            .join(" ");
+        assert!(!args.contains("-color_trc"));
+    }
+
+    #[test]
+    fn test_build_args_hdr10_metadata_for_x265() {
+        let ffmpeg = make_ffmpeg_with_filters(&["zscale", "tonemap"]);
+        let mut info = hdr_info("smpte2084");
+        info.streams[0].mastering_display = Some(
+            "G(13250,34500)B(7500,3000)R(34000,16000)WP(15635,16450)L(10000000,50)".to_string(),
+        );
+        info.streams[0].max_cll = Some("1000,400".to_string());
+        let cfg = CompressionConfig {
+            codec: Some(VideoCodec::Hevc),
+            ..CompressionConfig::default()
+        };
+        let args = ffmpeg
+            .build_args(&cfg, &info, "in.mkv", "out.mkv", "mkv", None)
+            .unwrap();
+        let x265_params = args
+            .iter()
+            .position(|a| a == "-x265-params")
+            .map(|i| args[i + 1].as_str());
+        assert_eq!(
+            x265_params,
+            Some(
+                "log-level=error:hdr10=1\
+                 :master-display=G(13250,34500)B(7500,3000)R(34000,16000)WP(15635,16450)L(10000000,50)\
+                 :max-cll=1000,400"
+            )
+        );
+
+        // Tone-mapped output is SDR and HLG has no HDR10 metadata.
+        let tonemap = CompressionConfig {
+            hdr: Some(HdrMode::Tonemap),
+            ..cfg.clone()
+        };
+        let args = ffmpeg
+            .build_args(&tonemap, &info, "in.mkv", "out.mkv", "mkv", None)
+            .unwrap()
+            .join(" ");
+        assert!(!args.contains("hdr10"), "{}", args);
+        let mut hlg = info.clone();
+        hlg.streams[0].color_transfer = Some("arib-std-b67".to_string());
+        let args = ffmpeg
+            .build_args(&cfg, &hlg, "in.mkv", "out.mkv", "mkv", None)
+            .unwrap()
+            .join(" ");
+        assert!(!args.contains("hdr10"), "{}", args);
+    }
+
+    #[test]
+    fn test_hdr_mode_validation() {
+        let info = hdr_info("arib-std-b67");
+        let with_zscale = make_ffmpeg_with_filters(&["zscale"]);
+        let without_zscale = make_ffmpeg_with_filters(&[]);
+        let keep = CompressionConfig {
+            hdr: Some(HdrMode::Keep),
+            ..CompressionConfig::default()
+        };
+        let tonemap = CompressionConfig {
+            codec: Some(VideoCodec::Hevc),
+            hdr: Some(HdrMode::Tonemap),
+            ..CompressionConfig::default()
+        };
+
+        assert!(with_zscale.hdr_mode(&keep, &info, "mp4").is_err());
+        assert_eq!(
+            with_zscale.hdr_mode(&tonemap, &info, "mp4").unwrap(),
+            Some(HdrMode::Tonemap)
+        );
+        assert!(without_zscale.hdr_mode(&tonemap, &info, "mp4").is_err());
+        // Without zscale the default H.264 encode can neither keep nor
+        // tone-map HDR, so it is refused rather than washed out.
+        assert!(without_zscale
+            .hdr_mode(&CompressionConfig::default(), &info, "mp4")
+            .is_err());
+        let hevc = CompressionConfig {
+            codec: Some(VideoCodec::Hevc),
+            ..CompressionConfig::default()
+        };
+        assert_eq!(
+            without_zscale.hdr_mode(&hevc, &info, "mp4").unwrap(),
+            Some(HdrMode::Keep)
+        );
+
+        // A stream copy keeps HDR untouched; --hdr tonemap rules it out.
+        let remux = CompressionConfig {
+            input_path: "in.mov".to_string(),
+            remux: RemuxMode::Auto,
+            ..CompressionConfig::default()
+        };
+        assert_eq!(
+            without_zscale.hdr_mode(&remux, &info, "mkv").unwrap(),
+            Some(HdrMode::Keep)
+        );
+        let remux_tonemap = CompressionConfig {
+            hdr: Some(HdrMode::Tonemap),
+            ..remux
+        };
+        assert!(!FFmpeg::should_remux(&remux_tonemap, &info, "in.mov", "mkv").unwrap());
+    }
+
+    #[test]
+    fn test_build_args_crop_bounds_follow_rotation() {
+        let ffmpeg = make_ffmpeg_for_tests();
+        let info = VideoInfo {
+            dimensions: Some((1920, 1080)),
+            ..VideoInfo::default()
+        };
+        let crop = |width, height, x, y| CropCoordinates {
+            width,
+            height,
+            x,
+            y,
+        };
+        let config = |crop, rotate| CompressionConfig {
+            input_path: "in.mp4".to_string(),
+            transforms: VideoTransforms {
+                crop: Some(crop),
+                rotate,
+                flip: None,
+            },
+            ..CompressionConfig::default()
+        };
+        let build = |cfg: &CompressionConfig| {
+            ffmpeg.build_args(cfg, &info, "in.mp4", "out.mp4", "mp4", None)
+        };
+
+        assert!(build(&config(crop(1920, 800, 0, 140), None)).is_ok());
+        match build(&config(crop(1920, 800, 0, 300), None)) {
+            Err(CompressoError::CropOutOfBounds { frame, .. }) => {
+                assert_eq!(frame, (1920, 1080))
+            }
+            other => panic!("expected CropOutOfBounds, got {:?}", other),
+        }
+        assert!(build(&config(crop(0, 800, 0, 0), None)).is_err());
+        // The crop applies after --rotate, so a portrait crop fits a turned frame.
+        assert!(build(&config(crop(1080, 1600, 0, 0), None)).is_err());
+        assert!(build(&config(crop(1080, 1600, 0, 0), Some(90))).is_ok());
+    }
+
+    #[test]
+    fn test_aspect_crop() {
+        let square = FFmpeg::aspect_crop((1920, 1080), (1, 1)).unwrap();
+        assert_eq!(
+            (square.width, square.height, square.x, square.y),
+            (1080, 1080, 420, 0)
+        );
+        let vertical = FFmpeg::aspect_crop((1920, 1080), (9, 16)).unwrap();
+        assert_eq!(
+            (vertical.width, vertical.height, vertical.x, vertical.y),
+            (606, 1080, 656, 0)
+        );
+        let cinema = FFmpeg::aspect_crop((1080, 1080), (21, 9)).unwrap();
+        assert_eq!(
+            (cinema.width, cinema.height, cinema.x, cinema.y),
+            (1080, 462, 0, 308)
+        );
+    }
+
+    #[test]
+    fn test_parse_cropdetect() {
+        let stderr = "\
+[Parsed_cropdetect_0 @ 0x1] x1:0 x2:1919 y1:140 y2:939 w:1920 h:800 x:0 y:140 pts:0 t:0.000000 limit:0.094000 crop=1920:800:0:140
+[Parsed_cropdetect_0 @ 0x1] x1:0 x2:1919 y1:1079 y2:0 w:-1904 h:-1072 x:8 y:4 pts:1 t:0.040000 limit:0.094000 crop=-1904:-1072:8:4
+[Parsed_cropdetect_0 @ 0x1] x1:0 x2:1919 y1:132 y2:947 w:1920 h:816 x:0 y:132 pts:2 t:0.080000 limit:0.094000 crop=1920:816:0:132
+";
+        let crop = FFmpeg::parse_cropdetect(stderr).unwrap();
+        assert_eq!(
+            (crop.width, crop.height, crop.x, crop.y),
+            (1920, 816, 0, 132)
+        );
+        assert!(FFmpeg::parse_cropdetect("no crop lines").is_none());
+    }
+
+    #[test]
+    fn test_cropdetect_args_apply_rotation() {
+        let cfg = CompressionConfig {
+            input_path: "in.mp4".to_string(),
+            transforms: VideoTransforms {
+                crop: None,
+                rotate: Some(90),
+                flip: None,
+            },
+            ..CompressionConfig::default()
+        };
+        let args = make_ffmpeg_for_tests().build_cropdetect_args(&cfg, "in.mp4", (12.0, 2.0));
+        let joined = args.join(" ");
+        assert!(joined.starts_with("-hide_banner -nostats -ss 12.000 -i in.mp4 -t 2.000"));
+        assert!(joined.contains("transpose=1,cropdetect=limit=0.094:round=2:reset=0"));
+        assert!(joined.ends_with("-f null -"));
+    }
+
+    #[test]
+    fn test_build_args_cleanup_filter_order() {
+        let cfg = CompressionConfig {
+            input_path: "in.mkv".to_string(),
+            width: Some(1280),
+            filters: VideoFilters {
+                denoise: Some(Denoise::Medium),
+                sharpen: true,
+                deinterlace: Deinterlace::On,
+                ..VideoFilters::default()
+            },
+            transforms: VideoTransforms {
+                rotate: Some(180),
+                ..VideoTransforms::default()
+            },
+            ..CompressionConfig::default()
+        };
+        let info = VideoInfo {
+            dimensions: Some((1920, 1080)),
+            ..VideoInfo::default()
+        };
+        let args = make_ffmpeg_for_tests()
+            .build_args(&cfg, &info, "in.mkv", "out.mp4", "mp4", None)
+            .unwrap();
+        let vf = &args[args.iter().position(|a| a == "-vf").unwrap() + 1];
+        assert_eq!(
+            vf,
+            "yadif,hflip,vflip,hqdn3d=4:3:6:4.5,scale=1280:-2,\
+             unsharp=5:5:0.8:5:5:0.0,pad=ceil(iw/2)*2:ceil(ih/2)*2"
+        );
+
+        // An unresolved auto adds nothing and does not rule out a copy.
+        let auto = CompressionConfig {
+            filters: VideoFilters {
+                deinterlace: Deinterlace::Auto,
+                ..VideoFilters::default()
+            },
+            remux: RemuxMode::Always,
+            ..CompressionConfig::default()
+        };
+        assert!(FFmpeg::should_remux(&auto, &info, "in.mkv", "mp4").unwrap());
+        let denoise = CompressionConfig {
+            filters: VideoFilters {
+                denoise: Some(Denoise::Light),
+                ..VideoFilters::default()
+            },
+            ..auto
+        };
+        assert!(FFmpeg::should_remux(&denoise, &info, "in.mkv", "mp4").is_err());
+    }
+
+    #[test]
+    fn test_build_args_color_filters_after_scaling() {
+        let cfg = CompressionConfig {
+            input_path: "in.mp4".to_string(),
+            width: Some(1280),
+            filters: VideoFilters {
+                sharpen: true,
+                color: ColorAdjust {
+                    brightness: Some(-0.05),
+                    saturation: Some(1.3),
+                    ..ColorAdjust::default()
+                },
+                lut: Some("luts/log-to-709.cube".to_string()),
+                ..VideoFilters::default()
+            },
+            ..CompressionConfig::default()
+        };
+        let info = VideoInfo {
+            dimensions: Some((1920, 1080)),
+            ..VideoInfo::default()
+        };
+        let args = make_ffmpeg_for_tests()
+            .build_args(&cfg, &info, "in.mp4", "out.mp4", "mp4", None)
+            .unwrap();
+        let vf = &args[args.iter().position(|a| a == "-vf").unwrap() + 1];
+        assert_eq!(
+            vf,
+            "scale=1280:-2,lut3d=luts/log-to-709.cube,eq=brightness=-0.05:saturation=1.3,\
+             unsharp=5:5:0.8:5:5:0.0,pad=ceil(iw/2)*2:ceil(ih/2)*2"
+        );
     }
 
-    fn build_filters(&self, config: &CompressionConfig) -> String {
-        let mut filters: Vec<String> = Vec::new();
-
-        // Apply transforms
-        self.apply_transforms(&config.transforms, &mut filters);
+    #[test]
+    fn test_check_extra_inputs_lut() {
+        let missing = CompressionConfig {
+            filters: VideoFilters {
+                lut: Some("definitely_nonexistent.cube".to_string()),
+                ..VideoFilters::default()
+            },
+            ..CompressionConfig::default()
+        };
+        assert!(FFmpeg::check_extra_inputs(&missing).is_err());
+
+        let dir = std::env::temp_dir();
+        let cube = dir.join(format!("compresso-lut-{}.cube", nanoid::nanoid!(8)));
+        let png = dir.join(format!("compresso-lut-{}.png", nanoid::nanoid!(8)));
+        std::fs::write(&cube, b"LUT_3D_SIZE 2").unwrap();
+        std::fs::write(&png, b"not a lut").unwrap();
+        let with_lut = |path: &std::path::Path| CompressionConfig {
+            filters: VideoFilters {
+                lut: Some(path.to_string_lossy().into_owned()),
+                ..VideoFilters::default()
+            },
+            ..CompressionConfig::default()
+        };
+        assert!(FFmpeg::check_extra_inputs(&with_lut(&cube)).is_ok());
+        assert!(FFmpeg::check_extra_inputs(&with_lut(&png)).is_err());
 
-        // Dimensions
-        let padding = "pad=ceil(iw/2)*2:ceil(ih/2)*2";
-        if let (Some(w), Some(h)) = (config.width, config.height) {
-            filters.push(format!("scale={}:{}", w, h));
-        }
-        filters.push(padding.to_string());
+        let _ = std::fs::remove_file(&cube);
+        let _ = std::fs::remove_file(&png);
+    }
 
-        filters.join(",")
+    #[test]
+    fn test_atempo_filters_chain() {
+        assert_eq!(FFmpeg::atempo_filters(1.5), ["atempo=1.5"]);
+        assert_eq!(
+            FFmpeg::atempo_filters(8.0),
+            ["atempo=2", "atempo=2", "atempo=2"]
+        );
+        assert_eq!(
+            FFmpeg::atempo_filters(5.0),
+            ["atempo=2", "atempo=2", "atempo=1.25"]
+        );
+        assert_eq!(FFmpeg::atempo_filters(0.25), ["atempo=0.5", "atempo=0.5"]);
+        assert!(FFmpeg::atempo_filters(1.0).is_empty());
     }
 
-    fn apply_transforms(&self, transforms: &VideoTransforms, filters: &mut Vec<String>) {
-        // Rotate
-        if let Some(angle) = transforms.rotate {
-            match angle % 360 {
-                90 | -270 => filters.push("transpose=1".to_string()),
-                -90 | 270 => filters.push("transpose=2".to_string()),
-                180 | -180 => filters.push("hflip,vflip".to_string()),
-                _ => {}
-            }
-        }
+    #[test]
+    fn test_build_args_speed() {
+        let cfg = CompressionConfig {
+            input_path: "in.mp4".to_string(),
+            speed: Some(4.0),
+            trim: Some(TrimRange {
+                start: 10.0,
+                end: Some(30.0),
+            }),
+            ..CompressionConfig::default()
+        };
+        let info = VideoInfo {
+            duration_seconds: Some(60.0),
+            fps: Some(30.0),
+            ..VideoInfo::default()
+        };
+        let ffmpeg = make_ffmpeg_for_tests();
+        let args = ffmpeg
+            .build_args(&cfg, &info, "in.mp4", "out.mp4", "mp4", None)
+            .unwrap();
+        let joined = args.join(" ");
+        assert!(joined.contains("pad=ceil(iw/2)*2:ceil(ih/2)*2,setpts=PTS/4,fps=30"));
+        assert!(joined.contains("-af atempo=2,atempo=2"));
+        // 20 s of source play back in 5 s; progress follows the output.
+        assert!(joined.contains("-t 5.000"));
+        assert_eq!(FFmpeg::output_duration(&cfg, &info), Some(5.0));
+        assert_eq!(FFmpeg::section_duration(&cfg, &info), Some(20.0));
+
+        let copy_audio = CompressionConfig {
+            audio: AudioSettings {
+                codec: Some(AudioCodec::Copy),
+                ..AudioSettings::default()
+            },
+            ..cfg.clone()
+        };
+        assert!(ffmpeg
+            .build_args(&copy_audio, &info, "in.mp4", "out.mp4", "mp4", None)
+            .is_err());
+        let keep_subtitles = CompressionConfig {
+            subtitles: TrackSelection::All,
+            ..cfg.clone()
+        };
+        assert!(ffmpeg
+            .build_args(&keep_subtitles, &info, "in.mp4", "out.mkv", "mkv", None)
+            .is_err());
+        let timelapse = CompressionConfig { mute: true, ..cfg };
+        let args = ffmpeg
+            .build_args(&timelapse, &info, "in.mp4", "out.mp4", "mp4", None)
+            .unwrap();
+        assert!(args.contains(&"-an".to_string()));
+        assert!(!args.contains(&"-af".to_string()));
+    }
 
-        // Flip
-        if let Some(ref flip) = transforms.flip {
-            if flip.horizontal {
-                filters.push("hflip".to_string());
-            }
-            if flip.vertical {
-                filters.push("vflip".to_string());
-            }
-        }
+    #[test]
+    fn test_build_args_audio_filters() {
+        let mut audio = track(1, StreamKind::Audio, "aac", None);
+        audio.sample_rate = Some(44100);
+        let info = VideoInfo {
+            duration_seconds: Some(60.0),
+            audio_codec: Some("aac".to_string()),
+            streams: vec![audio],
+            ..VideoInfo::default()
+        };
+        let cfg = CompressionConfig {
+            input_path: "in.mp4".to_string(),
+            speed: Some(2.0),
+            audio_filters: AudioFilters {
+                normalize: true,
+                loudness: Some(LoudnessStats {
+                    input_i: -27.61,
+                    input_tp: -4.47,
+                    input_lra: 18.06,
+                    input_thresh: -39.2,
+                    target_offset: 0.39,
+                }),
+                volume_db: Some(-3.0),
+                fade_in: Some(1.0),
+                fade_out: Some(2.5),
+            },
+            ..CompressionConfig::default()
+        };
+        let ffmpeg = make_ffmpeg_for_tests();
+        let args = ffmpeg
+            .build_args(&cfg, &info, "in.mp4", "out.mp4", "mp4", None)
+            .unwrap();
+        let af = &args[args.iter().position(|a| a == "-af").unwrap() + 1];
+        assert_eq!(
+            af,
+            "atempo=2,loudnorm=I=-16:TP=-1.5:LRA=11:measured_I=-27.61:measured_TP=-4.47:\
+             measured_LRA=18.06:measured_thresh=-39.2:offset=0.39:linear=true,\
+             aresample=44100,volume=-3dB,afade=t=in:st=0:d=1,afade=t=out:st=27.500:d=2.5"
+        );
 
-        // Crop
-        if let Some(ref crop) = transforms.crop {
-            filters.push(format!(
-                "crop={}:{}:{}:{}",
-                crop.width, crop.height, crop.x, crop.y
-            ));
-        }
+        // Opus cannot run at 44.1 kHz; without a measurement loudnorm runs once.
+        let webm = CompressionConfig {
+            speed: None,
+            audio_filters: AudioFilters {
+                normalize: true,
+                ..AudioFilters::default()
+            },
+            ..cfg.clone()
+        };
+        let args = ffmpeg
+            .build_args(&webm, &info, "in.mp4", "out.webm", "webm", None)
+            .unwrap()
+            .join(" ");
+        assert!(args.contains("-af loudnorm=I=-16:TP=-1.5:LRA=11,aresample=48000"));
+
+        let muted = CompressionConfig { mute: true, ..cfg };
+        let args = ffmpeg
+            .build_args(&muted, &info, "in.mp4", "out.mp4", "mp4", None)
+            .unwrap();
+        assert!(!args.contains(&"-af".to_string()));
     }
-}
 
-impl Default for FFmpeg {
-    fn default() -> Self {
-        // `FFmpeg` should normally be constructed via `FFmpeg::new()` so the
-        // caller can handle the `FfmpegNotFound` error gracefully. `Default`
-        // is retained only for trait completeness; it panics if FFmpeg is not
-        // installed. This is acceptable because `Default::default()` is not
-        // used anywhere in the codebase today.
-        Self::new().expect("FFmpeg not found")
+    #[test]
+    fn test_build_args_audio_filters_need_encoding() {
+        let info = VideoInfo {
+            video_codec: Some("h264".to_string()),
+            audio_codec: Some("aac".to_string()),
+            ..VideoInfo::default()
+        };
+        let cfg = CompressionConfig {
+            input_path: "in.mov".to_string(),
+            audio_filters: AudioFilters {
+                volume_db: Some(6.0),
+                ..AudioFilters::default()
+            },
+            ..CompressionConfig::default()
+        };
+        // A plain container change would otherwise be a stream copy.
+        assert!(!FFmpeg::should_remux(&cfg, &info, "in.mov", "mp4").unwrap());
+        let forced = CompressionConfig {
+            remux: RemuxMode::Always,
+            ..cfg.clone()
+        };
+        assert!(FFmpeg::should_remux(&forced, &info, "in.mov", "mp4").is_err());
+        let copy_audio = CompressionConfig {
+            audio: AudioSettings {
+                codec: Some(AudioCodec::Copy),
+                ..AudioSettings::default()
+            },
+            ..cfg.clone()
+        };
+        let ffmpeg = make_ffmpeg_for_tests();
+        assert!(ffmpeg
+            .build_args(&copy_audio, &info, "in.mov", "out.mp4", "mp4", None)
+            .is_err());
+        // The fade-out is timed from the end, which must be known.
+        let fade_out = CompressionConfig {
+            audio_filters: AudioFilters {
+                fade_out: Some(2.0),
+                ..AudioFilters::default()
+            },
+            ..cfg
+        };
+        assert!(ffmpeg
+            .build_args(&fade_out, &info, "in.mov", "out.mp4", "mp4", None)
+            .is_err());
     }
+
+    #[test]
+    fn test_parse_loudnorm() {
+        let stderr = r#"[Parsed_loudnorm_0 @ 0x600000d6c000]
+{
+	"input_i" : "-27.61",
+	"input_tp" : "-4.47",
+	"input_lra" : "18.06",
+	"input_thresh" : "-39.20",
+	"output_i" : "-16.58",
+	"output_tp" : "-1.50",
+	"output_lra" : "14.78",
+	"output_thresh" : "-27.71",
+	"normalization_type" : "dynamic",
+	"target_offset" : "0.58"
 }
+[out#0/null @ 0x600001a64000] video:0KiB audio:1500KiB"#;
+        let stats = FFmpeg::parse_loudnorm(stderr).unwrap();
+        assert_eq!(stats.input_i, -27.61);
+        assert_eq!(stats.input_thresh, -39.2);
+        assert_eq!(stats.target_offset, 0.58);
+
+        let silence = stderr.replace("\"-27.61\"", "\"-inf\"");
+        assert_eq!(FFmpeg::parse_loudnorm(&silence), None);
+        assert_eq!(FFmpeg::parse_loudnorm("no measurement"), None);
+    }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+    #[test]
+    fn test_still_args() {
+        let ffmpeg = make_ffmpeg_for_tests();
+        let info = VideoInfo {
+            dimensions: Some((1920, 1080)),
+            ..VideoInfo::default()
+        };
+        let filters = ffmpeg.still_filters(&info, Some(320), None);
+        assert_eq!(filters, "scale=320:180");
+        let args = FFmpeg::still_args("in.mp4", 12.5, &filters, "in_thumb_001.jpg").join(" ");
+        assert!(args.starts_with("-hide_banner -loglevel error -ss 12.500 -i in.mp4 -frames:v 1"));
+        assert!(args.contains("-vf scale=320:180 -an -sn -dn -q:v 2 -update 1 -y in_thumb_001.jpg"));
+
+        // The poster keeps the full size, so an SDR source needs no filters.
+        let poster = FFmpeg::still_args("in.mp4", 3.0, "", "in_poster.webp").join(" ");
+        assert!(!poster.contains("-vf"));
+        assert!(poster.contains("-c:v libwebp -quality 80 -update 1 -y in_poster.webp"));
+
+        let labelled = ffmpeg.still_filters(&info, Some(320), Some(83.5));
+        // `\\:` in the graph becomes `\:` for the option parser, i.e. a
+        // literal colon.
+        assert!(labelled.contains("drawtext=text=00\\\\:01\\\\:23:expansion=none:fontcolor"));
+        // Long recordings get a wider hours field, not a cut-off label.
+        let labelled = ffmpeg.still_filters(&info, Some(320), Some(360_005.9));
+        assert!(labelled.contains("drawtext=text=100\\\\:00\\\\:05:expansion"));
+    }
 
-    // ---- strip_verbatim_prefix -------------------------------------------------
+    #[test]
+    fn test_tile_args() {
+        let dir = Path::new("frames");
+        let sheet = FFmpeg::tile_args(dir, (4, 3), true, "in_contact_sheet.jpg").join(" ");
+        assert!(sheet.contains("-framerate 1 -i "));
+        assert!(sheet.contains("-vf tile=4x3:padding=4:margin=4 -frames:v 1"));
+        let sprite = FFmpeg::tile_args(dir, (4, 3), false, "in_sprite.jpg").join(" ");
+        assert!(sprite.contains("-vf tile=4x3 -frames:v 1"));
+    }
 
     #[test]
-    fn test_strip_verbatim_prefix_unc() {
+    fn test_spaced_timestamps_and_sprite_grid() {
         assert_eq!(
-            strip_verbatim_prefix(r"\\?\UNC\server\share\file"),
-            r"\\server\share\file"
+            FFmpeg::spaced_timestamps(100.0, 4),
+            vec![12.5, 37.5, 62.5, 87.5]
         );
+        assert_eq!(FFmpeg::sprite_grid(10), (4, 3));
+        assert_eq!(FFmpeg::sprite_grid(9), (3, 3));
+        assert_eq!(FFmpeg::sprite_grid(1), (1, 1));
+
+        let info = VideoInfo {
+            dimensions: Some((1080, 1920)),
+            ..VideoInfo::default()
+        };
+        assert_eq!(FFmpeg::tile_size(&info, 160).unwrap(), (160, 284));
+        assert!(FFmpeg::tile_size(&VideoInfo::default(), 160).is_err());
     }
 
     #[test]
-    fn test_strip_verbatim_prefix_drive() {
+    fn test_sprite_vtt() {
+        let vtt = FFmpeg::sprite_vtt("in_sprite.jpg", 40.0, 4, 2, (160, 90));
         assert_eq!(
-            strip_verbatim_prefix(r"\\?\C:\Windows\System32"),
-            r"C:\Windows\System32"
+            vtt,
+            "WEBVTT\n\
+             \n00:00:00.000 --> 00:00:10.000\nin_sprite.jpg#xywh=0,0,160,90\n\
+             \n00:00:10.000 --> 00:00:20.000\nin_sprite.jpg#xywh=160,0,160,90\n\
+             \n00:00:20.000 --> 00:00:30.000\nin_sprite.jpg#xywh=0,90,160,90\n\
+             \n00:00:30.000 --> 00:00:40.000\nin_sprite.jpg#xywh=160,90,160,90\n"
         );
     }
 
     #[test]
-    fn test_strip_verbatim_prefix_noop() {
+    fn test_generate_stills_checks_before_writing() {
+        let ffmpeg = make_ffmpeg_for_tests();
+        let dir = std::env::temp_dir();
+        let stem = format!("compresso-stills-{}", nanoid::nanoid!(8));
+        let input = dir.join(format!("{}.mp4", stem));
+        let poster = dir.join(format!("{}_poster.jpg", stem));
+        std::fs::write(&input, b"").unwrap();
+        std::fs::write(&poster, b"").unwrap();
+
+        let info = VideoInfo {
+            duration_seconds: Some(10.0),
+            dimensions: Some((1280, 720)),
+            ..VideoInfo::default()
+        };
+        let mut request = ThumbnailRequest {
+            input_path: input.to_string_lossy().into_owned(),
+            output_dir: None,
+            thumbnails: None,
+            sprite: false,
+            poster: Some(2.0),
+            contact_sheet: None,
+            format: ImageFormat::Jpeg,
+            width: 320,
+            overwrite: false,
+        };
+        let existing = ffmpeg.generate_stills(&request, &info);
+        assert!(matches!(existing, Err(CompressoError::InvalidOutput(_))));
+
+        request.poster = Some(12.0);
+        let past_end = ffmpeg.generate_stills(&request, &info);
+        assert!(matches!(past_end, Err(CompressoError::InvalidInput(_))));
+
+        request.poster = None;
+        request.thumbnails = Some(4);
+        let no_duration = ffmpeg.generate_stills(&request, &VideoInfo::default());
+        assert!(matches!(no_duration, Err(CompressoError::InvalidInput(_))));
+
+        let _ = std::fs::remove_file(&input);
+        let _ = std::fs::remove_file(&poster);
+    }
+
+    #[test]
+    fn test_build_args_watermark_uses_filter_graph() {
+        let cfg = CompressionConfig {
+            input_path: "in.mp4".to_string(),
+            width: Some(1280),
+            overlay: Overlay {
+                image: Some("logo.png".to_string()),
+                position: OverlayPosition::TopRight,
+                margin: 24,
+                opacity: 0.7,
+                ..Overlay::default()
+            },
+            ..CompressionConfig::default()
+        };
+        let info = VideoInfo {
+            dimensions: Some((1920, 1080)),
+            ..VideoInfo::default()
+        };
+        let args = make_ffmpeg_for_tests()
+            .build_args(&cfg, &info, "in.mp4", "out.mp4", "mp4", None)
+            .unwrap();
+        assert_eq!(args[..4], ["-i", "in.mp4", "-i", "logo.png"]);
+        assert!(!args.contains(&"-vf".to_string()));
+        let graph = &args[args.iter().position(|a| a == "-filter_complex").unwrap() + 1];
         assert_eq!(
-            strip_verbatim_prefix(r"C:\Windows\System32"),
-            r"C:\Windows\System32"
+            graph,
+            "[0:V:0]scale=1280:-2,pad=ceil(iw/2)*2:ceil(ih/2)*2[main];\
+             [1:v]format=rgba,colorchannelmixer=aa=0.7[wm];\
+             [main][wm]overlay=x=W-w-24:y=24[v]"
         );
-        assert_eq!(strip_verbatim_prefix("/etc/passwd"), "/etc/passwd");
+        assert!(args.join(" ").contains("-map [v] -map 0:a:0?"));
     }
 
-    // ---- validate_input_path ---------------------------------------------------
+    /// A phone clip (1080p, 30 fps, stereo AAC) as probed
+    fn clip_info(dims: (u32, u32), fps: f32, audio: bool) -> VideoInfo {
+        let mut video = track(0, StreamKind::Video, "h264", None);
+        (video.width, video.height, video.fps) = (Some(dims.0), Some(dims.1), Some(fps));
+        let mut sound = track(1, StreamKind::Audio, "aac", Some("eng"));
+        (sound.channels, sound.sample_rate) = (Some(2), Some(44100));
+        VideoInfo {
+            duration_seconds: Some(5.0),
+            dimensions: Some(dims),
+            fps: Some(fps),
+            video_codec: Some("h264".to_string()),
+            audio_codec: audio.then(|| "aac".to_string()),
+            streams: if audio {
+                vec![video, sound]
+            } else {
+                vec![video]
+            },
+            ..VideoInfo::default()
+        }
+    }
 
-    #[test]
-    fn test_validate_input_path_rejects_null_bytes() {
-        let res = FFmpeg::validate_input_path("vi\0deo.mp4");
-        assert!(res.is_err());
+    /// `--concat` of `parts`, described as concat_info would
+    fn joined(parts: Vec<VideoInfo>) -> (CompressionConfig, VideoInfo) {
+        let config = CompressionConfig {
+            input_path: "a.mp4".to_string(),
+            concat: (1..parts.len()).map(|i| format!("part{}.mp4", i)).collect(),
+            ..CompressionConfig::default()
+        };
+        let info = VideoInfo {
+            duration_seconds: Some(5.0 * parts.len() as f64),
+            parts: parts.clone(),
+            ..parts[0].clone()
+        };
+        (config, info)
     }
 
     #[test]
-    fn test_validate_input_path_rejects_traversal() {
-        // `..` must be rejected outright, not merely warned about.
-        let res = FFmpeg::validate_input_path("../secret/video.mp4");
-        assert!(res.is_err(), "expected path traversal to be rejected");
+    fn test_concat_mode() {
+        assert_eq!(
+            FFmpeg::concat_mode(&clip_info((1920, 1080), 30.0, true)),
+            None
+        );
+
+        let mut retagged = clip_info((1920, 1080), 29.999, true);
+        retagged.streams[1].language = Some("rus".to_string());
+        retagged.streams[1].bitrate = Some(128_000);
+        let (_, info) = joined(vec![clip_info((1920, 1080), 30.0, true), retagged]);
+        assert_eq!(FFmpeg::concat_mode(&info), Some(ConcatMode::Demuxer));
+
+        for other in [
+            clip_info((1280, 720), 30.0, true),
+            clip_info((1920, 1080), 25.0, true),
+            clip_info((1920, 1080), 30.0, false),
+        ] {
+            let (_, info) = joined(vec![clip_info((1920, 1080), 30.0, true), other]);
+            assert_eq!(FFmpeg::concat_mode(&info), Some(ConcatMode::Filter));
+        }
     }
 
     #[test]
-    fn test_validate_input_path_rejects_missing_file() {
-        let res = FFmpeg::validate_input_path("definitely_nonexistent_input.mp4");
-        assert!(res.is_err());
+    fn test_build_args_concat_demuxer() {
+        let (mut config, info) = joined(vec![clip_info((1920, 1080), 30.0, true); 3]);
+        config.trim = Some(TrimRange {
+            start: 2.0,
+            end: None,
+        });
+        let args = make_ffmpeg_for_tests()
+            .build_args(&config, &info, "list.ffconcat", "out.mp4", "mp4", None)
+            .unwrap()
+            .join(" ");
+        assert!(args.starts_with("-ss 2.000 -f concat -safe 0 -i list.ffconcat "));
+        assert!(args.contains("-map 0:V:0 -map 0:1 "));
+        assert!(!args.contains("-filter_complex"));
+        // Progress and -t follow the joined length.
+        assert!(args.contains("-t 13.000"));
     }
 
-    // ---- validate_output_path --------------------------------------------------
-    //
-    // These are security-critical. Each test documents a bypass that the old
-    // (raw-string denylist) implementation allowed and that the new
-    // canonicalization-based implementation must block.
-
     #[test]
-    fn test_validate_output_path_rejects_null_bytes() {
-        assert!(FFmpeg::validate_output_path("out\0.mp4").is_err());
+    fn test_build_args_concat_filter_graph() {
+        let (mut config, info) = joined(vec![
+            clip_info((1920, 1080), 30.0, true),
+            clip_info((720, 1280), 60.0, false),
+        ]);
+        config.trim = Some(TrimRange {
+            start: 2.0,
+            end: None,
+        });
+        config.audio_filters.volume_db = Some(-3.0);
+        let ffmpeg = make_ffmpeg_for_tests();
+        let args = ffmpeg
+            .build_args(&config, &info, "a.mp4", "out.mp4", "mp4", None)
+            .unwrap()
+            .join(" ");
+        assert!(args.starts_with("-i a.mp4 -i part1.mp4 "));
+        assert!(!args.contains("-ss"));
+        assert!(args.contains("-t 8.000"));
+        assert!(args.contains(
+            "[0:V:0]scale=1920:1080:force_original_aspect_ratio=decrease,\
+             pad=1920:1080:(ow-iw)/2:(oh-ih)/2,setsar=1,fps=30[v0];\
+             [0:a:0]aresample=44100,aformat=channel_layouts=stereo[a0];"
+        ));
+        assert!(args.contains("anullsrc=r=44100:cl=stereo,atrim=duration=5.000[a1]"));
+        assert!(args.contains(
+            "[v0][a0][v1][a1]concat=n=2:v=1:a=1[jv][ja];\
+             [jv]trim=start=2.000,setpts=PTS-STARTPTS[cv];\
+             [ja]atrim=start=2.000,asetpts=PTS-STARTPTS[ca];[cv]"
+        ));
+        assert!(args.contains(";[ca]volume=-3dB[a]"));
+        assert!(args.contains("-map [v] -map [a]"));
+        assert!(!args.contains("-af"));
+
+        // The first pass of a two-pass encode joins only the video.
+        config.two_pass = true;
+        config.target_size = Some(10 * 1024 * 1024);
+        let pass1 = ffmpeg
+            .build_args(
+                &config,
+                &info,
+                "a.mp4",
+                "out.mp4",
+                "mp4",
+                Some(EncodePass {
+                    number: 1,
+                    passlog: "log",
+                }),
+            )
+            .unwrap()
+            .join(" ");
+        assert!(pass1.contains("[v0][v1]concat=n=2:v=1:a=0[jv];"));
+        assert!(!pass1.contains("[a]"));
     }
 
     #[test]
-    fn test_validate_output_path_rejects_traversal() {
-        // `..` must be a hard error, not a warning.
-        assert!(FFmpeg::validate_output_path("../evil/out.mp4").is_err());
+    fn test_build_args_concat_filter_rejects_copies() {
+        let (config, info) = joined(vec![
+            clip_info((1920, 1080), 30.0, true),
+            clip_info((1280, 720), 30.0, true),
+        ]);
+        let ffmpeg = make_ffmpeg_for_tests();
+        let build = |config: &CompressionConfig| {
+            ffmpeg.build_args(config, &info, "a.mp4", "out.mp4", "mp4", None)
+        };
+        let remux = CompressionConfig {
+            remux: RemuxMode::Always,
+            ..config.clone()
+        };
+        assert!(matches!(
+            build(&remux),
+            Err(CompressoError::IncompatibleOptions(_))
+        ));
+        let mut audio_copy = config.clone();
+        audio_copy.audio.codec = Some(AudioCodec::Copy);
+        assert!(matches!(
+            build(&audio_copy),
+            Err(CompressoError::IncompatibleOptions(_))
+        ));
+        let all_tracks = CompressionConfig {
+            audio_tracks: TrackSelection::All,
+            ..config
+        };
+        assert!(matches!(
+            build(&all_tracks),
+            Err(CompressoError::IncompatibleOptions(_))
+        ));
     }
 
     #[test]
-    fn test_validate_output_path_blocks_missing_parent() {
-        // A non-existent parent directory must be rejected, not silently
-        // accepted (which previously let /usr/lib/... through).
-        assert!(FFmpeg::validate_output_path("/nonexistent_dir_xyz/out.mp4").is_err());
+    fn test_check_concat_refuses_single_file_analysis() {
+        let (config, _) = joined(vec![
+            clip_info((1920, 1080), 30.0, true),
+            clip_info((1920, 1080), 30.0, true),
+        ]);
+        let refused = |config: CompressionConfig| {
+            matches!(
+                FFmpeg::check_concat(&config, Some(ConcatMode::Demuxer)),
+                Err(CompressoError::IncompatibleOptions(_))
+            )
+        };
+        assert!(refused(CompressionConfig {
+            auto_crop: Some(AutoCrop::Detect),
+            ..config.clone()
+        }));
+        assert!(refused(CompressionConfig {
+            auto_crop: Some(AutoCrop::Aspect(16, 9)),
+            ..config.clone()
+        }));
+        let mut deinterlace = config.clone();
+        deinterlace.filters.deinterlace = Deinterlace::Auto;
+        assert!(refused(deinterlace));
+        let mut normalize = config.clone();
+        normalize.audio_filters.normalize = true;
+        assert!(refused(normalize.clone()));
+        normalize.mute = true;
+        assert!(FFmpeg::check_concat(&normalize, Some(ConcatMode::Demuxer)).is_ok());
+
+        // Settled values are fine, as is a single input.
+        let mut settled = config.clone();
+        settled.filters.deinterlace = Deinterlace::On;
+        assert!(FFmpeg::check_concat(&settled, Some(ConcatMode::Demuxer)).is_ok());
+        let single = CompressionConfig {
+            auto_crop: Some(AutoCrop::Detect),
+            ..config
+        };
+        assert!(FFmpeg::check_concat(&single, None).is_ok());
     }
 
     #[test]
-    fn test_validate_output_path_accepts_legitimate_tmp() {
-        // A normal temp file under an existing directory must be accepted.
-        let tmp = std::env::temp_dir().join("compresso_test_output.mp4");
-        assert!(FFmpeg::validate_output_path(&tmp.to_string_lossy()).is_ok());
+    fn test_concat_list_quotes_paths() {
+        let list = FFmpeg::concat_list(&["/v/a.mp4".to_string(), "/v/it's.mp4".to_string()]);
+        assert_eq!(
+            list,
+            "ffconcat version 1.0\nfile '/v/a.mp4'\nfile '/v/it'\\''s.mp4'\n"
+        );
     }
 
-    // The container/codec routing in build_args is pure and easy to unit-test.
     #[test]
-    fn test_build_args_ironclad_has_no_lossless_flags() {
-        // Regression test for P0.1: Ironclad must NOT contain `-qp 0` or
-        // `-b:v 0` together with `-crf` (that combination produced lossless
-        // output, larger than the source, ignoring the quality setting).
+    fn test_build_args_split_forces_keyframes() {
         let ffmpeg = make_ffmpeg_for_tests();
-        let cfg = CompressionConfig {
+        let info = clip_info((1280, 720), 30.0, true);
+        let mut cfg = CompressionConfig {
             input_path: "in.mp4".to_string(),
-            preset: Preset::Ironclad,
-            quality: 70,
+            split: Some(SplitMode::Duration(600.0)),
             ..CompressionConfig::default()
         };
-        let args = ffmpeg.build_args(&cfg, "in.mp4", "out.mp4", "mp4");
-        let joined = args.join(" ");
-        assert!(joined.contains("-crf"), "CRF must be present");
-        assert!(
-            !joined.contains("-qp 0"),
-            "lossless -qp 0 must NOT be present"
+        let args = ffmpeg
+            .build_args(&cfg, &info, "in.mp4", "out.mp4", "mp4", None)
+            .unwrap()
+            .join(" ");
+        assert!(args.contains("-force_key_frames expr:gte(t,n_forced*600)"));
+
+        cfg.split = Some(SplitMode::Size(25 * 1024 * 1024));
+        let args = ffmpeg
+            .build_args(&cfg, &info, "in.mp4", "out.mp4", "mp4", None)
+            .unwrap()
+            .join(" ");
+        assert!(args.contains("-force_key_frames expr:gte(t,n_forced*2)"));
+
+        // GIF and WebP previews are not cut into parts.
+        assert!(ffmpeg
+            .build_args(&cfg, &info, "in.mp4", "out.gif", "gif", None)
+            .is_err());
+    }
+
+    #[test]
+    fn test_split_times() {
+        // One 100-byte packet per second, a keyframe every two seconds.
+        let packets: Vec<PacketInfo> = (0..10)
+            .map(|t| PacketInfo {
+                time: t as f64,
+                size: 100,
+                keyframe: t % 2 == 0,
+            })
+            .collect();
+        assert_eq!(FFmpeg::split_times(&packets, 450).unwrap(), [4.0, 8.0]);
+        assert_eq!(
+            FFmpeg::split_times(&packets, 1000).unwrap(),
+            Vec::<f64>::new()
         );
+        // Two seconds between keyframes do not fit into 150 bytes.
+        assert!(FFmpeg::split_times(&packets, 150).is_err());
     }
 
     #[test]
-    fn test_build_args_thunderbolt_uses_crf() {
-        let ffmpeg = make_ffmpeg_for_tests();
-        let cfg = CompressionConfig {
+    fn test_move_parts_replaces_the_whole_set() {
+        let dir = std::env::temp_dir().join(format!("compresso-parts-{}", nanoid::nanoid!(8)));
+        std::fs::create_dir(&dir).unwrap();
+        let path = |name: &str| dir.join(name).to_string_lossy().into_owned();
+        let encoded = path("out.tmp.x1.mp4");
+        let output = path("out.mp4");
+        let write = |name: &str, bytes: &[u8]| std::fs::write(dir.join(name), bytes).unwrap();
+        // A new run with two parts, over three parts of an earlier one
+        write("out.tmp.x1_part01.mp4", b"new1");
+        write("out.tmp.x1_part02.mp4", b"new22");
+        for old in ["out_part01.mp4", "out_part02.mp4", "out_part03.mp4"] {
+            write(old, b"old");
+        }
+
+        assert!(FFmpeg::move_parts(&encoded, &output, false).is_err());
+        assert!(dir.join("out.tmp.x1_part01.mp4").exists(), "nothing moved");
+
+        let parts = FFmpeg::move_parts(&encoded, &output, true).unwrap();
+        assert_eq!(
+            parts,
+            [
+                OutputPart {
+                    path: path("out_part01.mp4"),
+                    size: 4
+                },
+                OutputPart {
+                    path: path("out_part02.mp4"),
+                    size: 5
+                },
+            ]
+        );
+        assert!(!dir.join("out_part03.mp4").exists(), "stale part removed");
+        assert!(!dir.join("out.tmp.x1_part01.mp4").exists());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_build_args_gif_preview() {
+        let info = VideoInfo {
+            dimensions: Some((1920, 1080)),
+            fps: Some(29.97),
+            video_codec: Some("h264".to_string()),
+            audio_codec: Some("aac".to_string()),
+            ..VideoInfo::default()
+        };
+        let mut cfg = CompressionConfig {
             input_path: "in.mp4".to_string(),
-            preset: Preset::Thunderbolt,
-            quality: 70,
+            gif_dither: GifDither::Bayer,
             ..CompressionConfig::default()
         };
-        let args = ffmpeg.build_args(&cfg, "in.mp4", "out.mp4", "mp4");
+        FFmpeg::apply_animation_defaults(&mut cfg, &info, "gif");
+        assert!(cfg.mute);
+        assert_eq!(cfg.fps, Some(15));
+        assert!(!FFmpeg::should_remux(&cfg, &info, "in.mp4", "gif").unwrap());
+
+        let ffmpeg = make_ffmpeg_for_tests();
+        let args = ffmpeg
+            .build_args(&cfg, &info, "in.mp4", "out.gif", "gif", None)
+            .unwrap();
         let joined = args.join(" ");
-        assert!(joined.contains("-crf"));
-        assert!(joined.contains("libx264"));
-        assert!(joined.contains("ultrafast"));
+        assert!(joined.contains(
+            "-vf scale=480:-2,pad=ceil(iw/2)*2:ceil(ih/2)*2,split[g1][g2];\
+             [g1]palettegen=stats_mode=diff[pal];\
+             [g2][pal]paletteuse=dither=bayer:bayer_scale=3:diff_mode=rectangle"
+        ));
+        assert!(joined.contains("-c:v gif -loop 0"));
+        assert!(joined.contains("-r 15"));
+        assert!(!args.contains(&"-pix_fmt".to_string()));
+        assert!(args.contains(&"-an".to_string()));
+
+        // The palette stage follows the watermark.
+        let watermarked = CompressionConfig {
+            overlay: Overlay {
+                image: Some("logo.png".to_string()),
+                ..Overlay::default()
+            },
+            ..cfg.clone()
+        };
+        let args = ffmpeg
+            .build_args(&watermarked, &info, "in.mp4", "out.gif", "gif", None)
+            .unwrap();
+        let graph = &args[args.iter().position(|a| a == "-filter_complex").unwrap() + 1];
+        assert!(graph.contains("overlay=x=W-w-20:y=H-h-20,split[g1][g2];"));
+        assert!(graph.ends_with("diff_mode=rectangle[v]"));
+
+        let with_codec = CompressionConfig {
+            codec: Some(VideoCodec::Vp9),
+            ..cfg
+        };
+        assert!(ffmpeg
+            .build_args(&with_codec, &info, "in.mp4", "out.gif", "gif", None)
+            .is_err());
     }
 
     #[test]
-    fn test_build_args_webm_uses_vp9() {
-        let ffmpeg = make_ffmpeg_for_tests();
-        let cfg = CompressionConfig {
+    fn test_build_args_animated_webp() {
+        let info = VideoInfo {
+            dimensions: Some((1080, 1920)),
+            fps: Some(12.0),
+            ..VideoInfo::default()
+        };
+        let mut cfg = CompressionConfig {
             input_path: "in.mp4".to_string(),
-            preset: Preset::Ironclad,
-            quality: 70,
+            quality: 60,
+            width: Some(360),
             ..CompressionConfig::default()
         };
-        let args = ffmpeg.build_args(&cfg, "in.mp4", "out.webm", "webm");
-        let joined = args.join(" ");
-        assert!(joined.contains("libvpx-vp9"), "WebM output must use VP9");
-        // faststart is MP4-only and harmful for WebM.
-        assert!(
-            !joined.contains("faststart"),
-            "+faststart must not be set for WebM"
+        FFmpeg::apply_animation_defaults(&mut cfg, &info, "webp");
+        // An explicit size and a slow source are left alone.
+        assert_eq!(cfg.max_resolution, None);
+        assert_eq!(cfg.fps, None);
+
+        let args = make_ffmpeg_with_encoders(&["libx264", "libwebp_anim", "libwebp"])
+            .build_args(&cfg, &info, "in.mp4", "out.webp", "webp", None)
+            .unwrap()
+            .join(" ");
+        assert!(args.contains(
+            "-c:v libwebp_anim -quality 60 -compression_level 6 -loop 0 -pix_fmt yuv420p"
+        ));
+        assert!(!args.contains("palettegen"));
+        assert!(make_ffmpeg_for_tests()
+            .build_args(&cfg, &info, "in.mp4", "out.webp", "webp", None)
+            .is_err());
+    }
+
+    #[test]
+    fn test_text_overlay_filter() {
+        let overlay = Overlay {
+            text: Some("ACME: it's live".to_string()),
+            position: OverlayPosition::Center,
+            ..Overlay::default()
+        };
+        assert_eq!(
+            text_overlay_filter(&overlay).unwrap(),
+            "drawtext=text=ACME\\\\: it\\\\\\'s live:expansion=none:fontsize=h/20:\
+             fontcolor=white@1:borderw=2:bordercolor=black@1:x=(w-text_w)/2:y=(h-text_h)/2"
         );
+        assert_eq!(text_overlay_filter(&Overlay::default()), None);
     }
 
     #[test]
-    fn test_build_args_mov_gets_faststart() {
-        let ffmpeg = make_ffmpeg_for_tests();
+    fn test_check_text_overlay_capabilities() {
         let cfg = CompressionConfig {
-            input_path: "in.mp4".to_string(),
-            preset: Preset::Ironclad,
-            quality: 70,
+            overlay: Overlay {
+                text: Some("Draft".to_string()),
+                ..Overlay::default()
+            },
             ..CompressionConfig::default()
         };
-        let args = ffmpeg.build_args(&cfg, "in.mp4", "out.mov", "mov");
-        assert!(args.join(" ").contains("faststart"));
+        let with_font = CompressionConfig {
+            overlay: Overlay {
+                font: Some("font.ttf".to_string()),
+                ..cfg.overlay.clone()
+            },
+            ..CompressionConfig::default()
+        };
+
+        let without_drawtext = make_ffmpeg_with_filters(&[]);
+        assert!(without_drawtext.check_text_overlay(&with_font).is_err());
+
+        let without_fontconfig = make_ffmpeg_with_filters(&["drawtext"]);
+        let _ = without_fontconfig
+            .build_config
+            .set("configuration: --enable-gpl --enable-libfreetype".to_string());
+        assert!(without_fontconfig.check_text_overlay(&cfg).is_err());
+        assert!(without_fontconfig.check_text_overlay(&with_font).is_ok());
+
+        let with_fontconfig = make_ffmpeg_with_filters(&["drawtext"]);
+        let _ = with_fontconfig
+            .build_config
+            .set("configuration: --enable-libfreetype --enable-libfontconfig".to_string());
+        assert!(with_fontconfig.check_text_overlay(&cfg).is_ok());
+    }
+
+    #[test]
+    fn test_parse_idet() {
+        let stderr = "\
+[Parsed_idet_0 @ 0x1] Repeated Fields: Neither:   98 Top:     1 Bottom:     1
+[Parsed_idet_0 @ 0x1] Single frame detection: TFF:    60 BFF:     0 Progressive:    20 Undetermined:    20
+[Parsed_idet_0 @ 0x1] Multi frame detection: TFF:    91 BFF:     0 Progressive:     4 Undetermined:     5
+";
+        assert_eq!(FFmpeg::parse_idet(stderr), Some((91, 4)));
+        assert_eq!(FFmpeg::parse_idet("no summary"), None);
     }
 
-    /// Build an FFmpeg handle without probing PATH (the ffmpeg_path is never
-    /// actually executed by the pure build_args/validate_* functions under test).
     fn make_ffmpeg_for_tests() -> FFmpeg {
-        FFmpeg {
+        make_ffmpeg_with_encoders(&["libx264", "libx265", "libvpx-vp9"])
+    }
+
+    /// Same as `make_ffmpeg_for_tests`, with a pre-seeded encoder list so the
+    /// encoder selection logic never spawns `ffmpeg -encoders`.
+    fn make_ffmpeg_with_encoders(encoders: &[&str]) -> FFmpeg {
+        let ffmpeg = FFmpeg {
             ffmpeg_path: "ffmpeg".to_string(),
-        }
+            ffprobe_path: None,
+            encoders: OnceLock::new(),
+            filters: OnceLock::new(),
+            build_config: OnceLock::new(),
+        };
+        let _ = ffmpeg
+            .encoders
+            .set(encoders.iter().map(|e| e.to_string()).collect());
+        ffmpeg
     }
 }
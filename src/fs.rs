@@ -1,7 +1,7 @@
 use std::fs;
 use std::path::{Path, PathBuf};
 
-use crate::domain::FileMetadata;
+use crate::domain::{FileMetadata, OutputFormat};
 use crate::error::{CompressoError, Result};
 
 /// Get metadata of a file from its path
@@ -48,7 +48,10 @@ pub fn get_file_metadata(path: &str) -> Result<FileMetadata> {
 
 /// Check if file is a valid video file
 pub fn is_video_file(path: &str) -> bool {
-    let valid_extensions = ["mp4", "mov", "webm", "avi", "mkv", "m4v", "wmv", "flv"];
+    // GIF is an image format, but FFmpeg decodes animated GIFs like video.
+    let valid_extensions = [
+        "mp4", "mov", "webm", "avi", "mkv", "m4v", "wmv", "flv", "gif",
+    ];
 
     if let Some(ext) = Path::new(path).extension() {
         if let Some(ext_str) = ext.to_str() {
@@ -94,6 +97,24 @@ pub fn format_size(bytes: u64) -> String {
 /// canonicalized input file, preventing writes to unexpected locations.
 ///
 pub fn generate_output_path(input: &str, format: Option<&str>) -> Result<String> {
+    let extension = format.unwrap_or_else(|| {
+        Path::new(input)
+            .extension()
+            .and_then(|e| e.to_str())
+            .unwrap_or("mp4")
+    });
+
+    // A GIF or WebP made from a video is a preview rather than a smaller copy.
+    let suffix = match OutputFormat::from_extension(extension) {
+        Some(format) if format.is_animation() => "preview",
+        _ => "compressed",
+    };
+    generate_sibling_path(input, suffix, extension)
+}
+
+/// `<stem>_<suffix>.<extension>` next to the input, with the same checks as
+/// [`generate_output_path`]
+pub fn generate_sibling_path(input: &str, suffix: &str, extension: &str) -> Result<String> {
     // Reject obviously malicious input early.
     if input.contains('\0') {
         return Err(CompressoError::InvalidInput(
@@ -117,13 +138,6 @@ pub fn generate_output_path(input: &str, format: Option<&str>) -> Result<String>
         .filter(|s| !s.is_empty())
         .unwrap_or_else(|| "output".to_string());
 
-    let extension = format.unwrap_or_else(|| {
-        input_path
-            .extension()
-            .and_then(|e| e.to_str())
-            .unwrap_or("mp4")
-    });
-
     // Canonicalize the input to resolve symlinks and produce an absolute path.
     // At this call site the input file should already exist, so canonicalize
     // is expected to succeed. If it does not, we deliberately *fail* rather
@@ -140,7 +154,7 @@ pub fn generate_output_path(input: &str, format: Option<&str>) -> Result<String>
         }
     };
 
-    let output_name = format!("{}_compressed.{}", stem, extension);
+    let output_name = format!("{}_{}.{}", stem, suffix, extension);
 
     let result = if parent.as_os_str().is_empty() || parent == Path::new(".") {
         PathBuf::from(output_name)
@@ -151,6 +165,31 @@ pub fn generate_output_path(input: &str, format: Option<&str>) -> Result<String>
     Ok(result.to_string_lossy().into_owned())
 }
 
+/// Path of part `number` (from 1) of an output cut into parts:
+/// `<stem>_part01.<ext>` next to `output`, which comes from
+/// [`generate_output_path`] or `-o`.
+pub fn part_path(output: &str, number: usize) -> String {
+    with_part_label(output, &format!("{:02}", number))
+}
+
+/// [`part_path`] as a pattern for FFmpeg's segment muxer, which numbers the
+/// parts itself (`%` in the path is escaped).
+pub fn part_pattern(output: &str) -> String {
+    with_part_label(&output.replace('%', "%%"), "%02d")
+}
+
+fn with_part_label(output: &str, label: &str) -> String {
+    let path = Path::new(output);
+    let stem = path
+        .file_stem()
+        .map_or_else(|| "output".into(), |s| s.to_string_lossy());
+    let name = match path.extension() {
+        Some(extension) => format!("{}_part{}.{}", stem, label, extension.to_string_lossy()),
+        None => format!("{}_part{}", stem, label),
+    };
+    path.with_file_name(name).to_string_lossy().into_owned()
+}
+
 /// Sanitize filename to prevent path traversal while preserving Unicode.
 ///
 /// This strips characters that are *structurally* dangerous to a filename
@@ -306,5 +345,27 @@ mod tests {
             out.ends_with("_compressed.webm"),
             "expected _compressed.webm suffix, got {out}"
         );
+        let out = generate_output_path(&src, Some("gif")).unwrap();
+        assert!(out.ends_with("fs_preview.gif"), "got {out}");
+    }
+
+    #[test]
+    fn test_part_path() {
+        assert_eq!(
+            part_path("/videos/trip_compressed.mp4", 3),
+            "/videos/trip_compressed_part03.mp4"
+        );
+        assert_eq!(part_path("out.mkv", 120), "out_part120.mkv");
+        assert_eq!(
+            part_pattern("/videos/50% off.tmp.x1.mp4"),
+            "/videos/50%% off.tmp.x1_part%02d.mp4"
+        );
+    }
+
+    #[test]
+    fn test_is_video_file_accepts_gif() {
+        assert!(is_video_file("clip.GIF"));
+        assert!(is_video_file("clip.mkv"));
+        assert!(!is_video_file("notes.txt"));
     }
 }
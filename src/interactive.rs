@@ -2,8 +2,11 @@ use colored::*;
 use dialoguer::{theme::ColorfulTheme, Input, Select};
 use std::io::{self, IsTerminal, Write};
 
+use crate::cli::CropArg;
 use crate::domain::{
-    CompressionConfig, CropCoordinates, FlipOptions, OutputFormat, Preset, VideoTransforms,
+    AudioCodec, AudioSettings, AutoCrop, BurnSubtitles, ColorAdjust, CompressionConfig,
+    CropCoordinates, FlipOptions, OutputFormat, Preset, StreamKind, TrimRange, VideoFilters,
+    VideoTransforms,
 };
 use crate::error::Result;
 use crate::fs;
@@ -156,6 +159,174 @@ fn prompt_input_path() -> Result<String> {
     Ok(cleaned)
 }
 
+/// Audio prompts of the advanced settings; shared with the batch wizard.
+pub fn prompt_audio_settings(theme: &ColorfulTheme) -> AudioSettings {
+    // Audio codec
+    let audio_codecs = vec![
+        t("audio_codec_auto"),
+        "AAC".to_string(),
+        "Opus".to_string(),
+        "MP3".to_string(),
+        t("audio_codec_copy"),
+    ];
+    let audio_codec_idx = Select::with_theme(theme)
+        .with_prompt(t("audio_codec_prompt"))
+        .items(&audio_codecs)
+        .default(0)
+        .interact()
+        .unwrap_or(0);
+    let codec = match audio_codec_idx {
+        1 => Some(AudioCodec::Aac),
+        2 => Some(AudioCodec::Opus),
+        3 => Some(AudioCodec::Mp3),
+        4 => Some(AudioCodec::Copy),
+        _ => None,
+    };
+
+    let mut audio = AudioSettings {
+        codec,
+        ..AudioSettings::default()
+    };
+
+    // Bitrate, channels and sample rate only apply when re-encoding
+    if codec == Some(AudioCodec::Copy) {
+        return audio;
+    }
+
+    let bitrate_input: String = Input::with_theme(theme)
+        .with_prompt(t("audio_bitrate_prompt"))
+        .allow_empty(true)
+        .interact_text()
+        .unwrap_or_default();
+
+    if !bitrate_input.is_empty() {
+        // Same parser as --audio-bitrate
+        match crate::cli::parse_audio_bitrate(&bitrate_input) {
+            Ok(kbps) => audio.bitrate_kbps = Some(kbps),
+            Err(msg) => {
+                println!("{} {}", "⚠".bright_yellow(), msg.bright_yellow());
+            }
+        }
+    }
+
+    let mono_options = vec![t("no"), t("yes")];
+    let mono_idx = Select::with_theme(theme)
+        .with_prompt(t("audio_mono_prompt"))
+        .items(&mono_options)
+        .default(0)
+        .interact()
+        .unwrap_or(0);
+    if mono_idx == 1 {
+        audio.channels = Some(1);
+    }
+
+    let rate_input: String = Input::with_theme(theme)
+        .with_prompt(t("audio_rate_prompt"))
+        .allow_empty(true)
+        .interact_text()
+        .unwrap_or_default();
+
+    if !rate_input.is_empty() {
+        audio.sample_rate = rate_input.parse().ok();
+    }
+
+    audio
+}
+
+/// Ask for an optional `--start`/`--end` style timestamp.
+fn prompt_timestamp(theme: &ColorfulTheme, prompt_key: &str) -> Option<f64> {
+    let input: String = Input::with_theme(theme)
+        .with_prompt(t(prompt_key))
+        .allow_empty(true)
+        .interact_text()
+        .unwrap_or_default();
+
+    if input.is_empty() {
+        return None;
+    }
+    // Same parser as --start/--end
+    match crate::cli::parse_timestamp(&input) {
+        Ok(seconds) => Some(seconds),
+        Err(msg) => {
+            println!("{} {}", "⚠".bright_yellow(), msg.bright_yellow());
+            None
+        }
+    }
+}
+
+/// Offer to burn in one of the input's text subtitle tracks. Asks nothing
+/// when the file has none (or cannot be probed).
+fn prompt_burn_subtitles(theme: &ColorfulTheme, input_path: &str) -> Option<BurnSubtitles> {
+    let info = crate::ffmpeg::FFmpeg::new()
+        .and_then(|ffmpeg| ffmpeg.get_video_info(input_path))
+        .ok()?;
+    // Numbered among all subtitle tracks, like --burn-subtitle-track.
+    let tracks: Vec<(u32, String)> = info
+        .streams
+        .iter()
+        .filter(|s| s.kind == StreamKind::Subtitle)
+        .zip(1..)
+        .filter(|(s, _)| !s.is_image_subtitle())
+        .map(|(s, n)| (n, format!("{}. {}", n, crate::output::format_stream(s))))
+        .collect();
+    if tracks.is_empty() {
+        return None;
+    }
+
+    let mut options = vec![t("no")];
+    options.extend(tracks.iter().map(|(_, label)| label.clone()));
+    let idx = Select::with_theme(theme)
+        .with_prompt(t("burn_subtitles_prompt"))
+        .items(&options)
+        .default(0)
+        .interact()
+        .unwrap_or(0);
+    idx.checked_sub(1)
+        .map(|i| BurnSubtitles::Track(tracks[i].0))
+}
+
+/// Colour prompts of the advanced settings: each value starts at its neutral
+/// setting, then an optional LUT file.
+fn prompt_color_settings(theme: &ColorfulTheme) -> (ColorAdjust, Option<String>) {
+    let options = vec![t("no"), t("yes")];
+    let adjust_idx = Select::with_theme(theme)
+        .with_prompt(t("adjust_colors_prompt"))
+        .items(&options)
+        .default(0)
+        .interact()
+        .unwrap_or(0);
+    if adjust_idx == 0 {
+        return (ColorAdjust::default(), None);
+    }
+
+    // Same parsers and ranges as the CLI flags
+    let prompt_value =
+        |key: &str, neutral: f32, parse: fn(&str) -> std::result::Result<f32, String>| {
+            let input: String = Input::with_theme(theme)
+                .with_prompt(t(key))
+                .default(neutral.to_string())
+                .validate_with(|input: &String| parse(input).map(|_| ()))
+                .interact_text()
+                .unwrap_or_default();
+            parse(&input).ok().filter(|value| *value != neutral)
+        };
+    let color = ColorAdjust {
+        brightness: prompt_value("brightness_prompt", 0.0, crate::cli::parse_brightness),
+        contrast: prompt_value("contrast_prompt", 1.0, crate::cli::parse_contrast),
+        saturation: prompt_value("saturation_prompt", 1.0, crate::cli::parse_saturation),
+        gamma: prompt_value("gamma_prompt", 1.0, crate::cli::parse_gamma),
+    };
+
+    let lut: String = Input::with_theme(theme)
+        .with_prompt(t("lut_prompt"))
+        .allow_empty(true)
+        .interact_text()
+        .unwrap_or_default();
+    let lut = lut.trim().trim_matches('"').to_string();
+
+    (color, Some(lut).filter(|path| !path.is_empty()))
+}
+
 fn prompt_compression_settings(input_path: &str) -> Result<CompressionConfig> {
     let theme = ColorfulTheme::default();
 
@@ -203,6 +374,8 @@ fn prompt_compression_settings(input_path: &str) -> Result<CompressionConfig> {
         t("mkv_format"),
         t("avi_format"),
         t("mov_format"),
+        t("gif_format"),
+        t("webp_format"),
     ];
 
     let format_idx = Select::with_theme(&theme)
@@ -218,6 +391,8 @@ fn prompt_compression_settings(input_path: &str) -> Result<CompressionConfig> {
         3 => Some(OutputFormat::Mkv),
         4 => Some(OutputFormat::Avi),
         5 => Some(OutputFormat::Mov),
+        6 => Some(OutputFormat::Gif),
+        7 => Some(OutputFormat::Webp),
         _ => None,
     };
 
@@ -234,11 +409,17 @@ fn prompt_compression_settings(input_path: &str) -> Result<CompressionConfig> {
     let mut width: Option<u32> = None;
     let mut height: Option<u32> = None;
     let mut fps: Option<u32> = None;
+    let mut trim: Option<TrimRange> = None;
     let mut mute = false;
+    let mut audio = AudioSettings::default();
     let mut rotate: Option<i32> = None;
     let mut flip_horizontal = false;
     let mut flip_vertical = false;
     let mut crop: Option<CropCoordinates> = None;
+    let mut auto_crop: Option<AutoCrop> = None;
+    let mut burn_subtitles: Option<BurnSubtitles> = None;
+    let mut color = ColorAdjust::default();
+    let mut lut: Option<String> = None;
 
     if show_advanced {
         println!();
@@ -279,6 +460,16 @@ fn prompt_compression_settings(input_path: &str) -> Result<CompressionConfig> {
             fps = fps_input.parse().ok();
         }
 
+        // Trim
+        let start = prompt_timestamp(&theme, "trim_start_prompt");
+        let end = prompt_timestamp(&theme, "trim_end_prompt");
+        if start.is_some() || end.is_some() {
+            trim = Some(TrimRange {
+                start: start.unwrap_or(0.0),
+                end,
+            });
+        }
+
         // Mute
         let mute_options = vec![t("no"), t("yes")];
         let mute_idx = Select::with_theme(&theme)
@@ -289,6 +480,13 @@ fn prompt_compression_settings(input_path: &str) -> Result<CompressionConfig> {
             .unwrap_or(0);
         mute = mute_idx == 1;
 
+        if !mute {
+            audio = prompt_audio_settings(&theme);
+        }
+
+        burn_subtitles = prompt_burn_subtitles(&theme, input_path);
+        (color, lut) = prompt_color_settings(&theme);
+
         println!();
         println!("{}", t("transform_options").bright_white().bold());
         println!("{}", "─".repeat(30).dimmed());
@@ -352,8 +550,9 @@ fn prompt_compression_settings(input_path: &str) -> Result<CompressionConfig> {
             // parser checked `parts.len() == 2`, which never matched the
             // documented `1920x1080:0:0` (it splits into 3 parts), so crop was
             // silently ignored in interactive mode.
-            match crate::cli::parse_crop(&crop_input) {
-                Ok(c) => crop = Some(c),
+            match crate::cli::parse_crop_arg(&crop_input) {
+                Ok(CropArg::Manual(c)) => crop = Some(c),
+                Ok(CropArg::Auto) => auto_crop = Some(AutoCrop::Detect),
                 Err(msg) => {
                     println!("{} {}", "⚠".bright_yellow(), msg.bright_yellow());
                 }
@@ -421,20 +620,50 @@ fn prompt_compression_settings(input_path: &str) -> Result<CompressionConfig> {
         );
     }
 
-    if let (Some(w), Some(h)) = (width, height) {
-        println!("  {} {}x{}", t("dimensions").dimmed(), w, h);
+    if width.is_some() || height.is_some() {
+        // A missing side follows the aspect ratio.
+        let side = |v: Option<u32>| v.map_or("…".to_string(), |v| v.to_string());
+        println!(
+            "  {} {}x{}",
+            t("dimensions").dimmed(),
+            side(width),
+            side(height)
+        );
     }
 
     if let Some(f) = fps {
         println!("  {} {} fps", t("fps").dimmed(), f);
     }
 
+    if let Some(trim) = trim {
+        println!(
+            "  {} {}",
+            t("trim").dimmed(),
+            trim.to_string().bright_white()
+        );
+    }
+
+    if let Some(burn) = &burn_subtitles {
+        println!(
+            "  {} {}",
+            t("burn_subtitles").dimmed(),
+            burn.to_string().bright_white()
+        );
+    }
+
     if mute {
         println!("  {} {}", t("audio").dimmed(), t("muted").bright_red());
+    } else if !audio.is_default() {
+        println!(
+            "  {} {}",
+            t("audio").dimmed(),
+            audio.to_string().bright_white()
+        );
     }
 
     // Display transforms if any
-    if rotate.is_some() || flip_horizontal || flip_vertical || crop.is_some() {
+    if rotate.is_some() || flip_horizontal || flip_vertical || crop.is_some() || auto_crop.is_some()
+    {
         println!();
         if let Some(r) = rotate {
             println!(
@@ -463,6 +692,8 @@ fn prompt_compression_settings(input_path: &str) -> Result<CompressionConfig> {
                 c.x,
                 c.y
             );
+        } else if auto_crop.is_some() {
+            println!("  {} {}", t("crop").dimmed(), "auto".bright_cyan());
         }
     }
 
@@ -507,9 +738,19 @@ fn prompt_compression_settings(input_path: &str) -> Result<CompressionConfig> {
         height,
         fps,
         mute,
+        audio,
+        trim,
+        burn_subtitles,
         transforms,
+        auto_crop,
+        filters: VideoFilters {
+            color,
+            lut,
+            ..VideoFilters::default()
+        },
         overwrite: true,
         verbose: false,
         json: false,
+        ..CompressionConfig::default()
     })
 }
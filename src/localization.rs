@@ -104,6 +104,11 @@ fn english_translations() -> HashMap<String, String> {
     translations.insert("duration".to_string(), "Duration:".to_string());
     translations.insert("resolution".to_string(), "Resolution:".to_string());
     translations.insert("frame_rate".to_string(), "Frame rate:".to_string());
+    translations.insert("container".to_string(), "Container:".to_string());
+    translations.insert("bitrate".to_string(), "Bitrate:".to_string());
+    translations.insert("streams".to_string(), "Streams:".to_string());
+    translations.insert("default_track".to_string(), "default".to_string());
+    translations.insert("cover_art".to_string(), "cover art".to_string());
 
     // Compression settings
     translations.insert(
@@ -115,13 +120,90 @@ fn english_translations() -> HashMap<String, String> {
     translations.insert("preset".to_string(), "Preset:".to_string());
     translations.insert("quality".to_string(), "Quality:".to_string());
     translations.insert("dimensions".to_string(), "Dimensions:".to_string());
+    translations.insert("max_resolution".to_string(), "Max resolution:".to_string());
     translations.insert("fps".to_string(), "FPS:".to_string());
     translations.insert("audio".to_string(), "Audio:".to_string());
     translations.insert("muted".to_string(), "muted".to_string());
     translations.insert("format".to_string(), "Format:".to_string());
+    translations.insert("codec".to_string(), "Codec:".to_string());
+    translations.insert("target_size".to_string(), "Target size:".to_string());
+    translations.insert("target_met".to_string(), "met".to_string());
+    translations.insert("target_missed".to_string(), "not met".to_string());
+    translations.insert("quality_metrics".to_string(), "Quality:".to_string());
+    translations.insert(
+        "measuring_quality".to_string(),
+        "Measuring quality against the source...".to_string(),
+    );
+    translations.insert(
+        "quality_measurement_failed".to_string(),
+        "Could not measure quality".to_string(),
+    );
+    translations.insert("target_quality".to_string(), "Target quality:".to_string());
+    translations.insert(
+        "searching_crf".to_string(),
+        "Encoding samples to find the CRF for {target}...".to_string(),
+    );
+    translations.insert(
+        "crf_chosen".to_string(),
+        "Chosen CRF {crf} ({metric} {score})".to_string(),
+    );
+    translations.insert(
+        "target_quality_missed".to_string(),
+        "target not reached even at the best quality in range".to_string(),
+    );
     translations.insert("rotate".to_string(), "Rotate:".to_string());
     translations.insert("flip".to_string(), "Flip:".to_string());
     translations.insert("crop".to_string(), "Crop:".to_string());
+    translations.insert(
+        "detecting_crop".to_string(),
+        "Detecting black bars...".to_string(),
+    );
+    translations.insert(
+        "detecting_interlace".to_string(),
+        "Checking for interlacing...".to_string(),
+    );
+    translations.insert(
+        "measuring_loudness".to_string(),
+        "Measuring loudness...".to_string(),
+    );
+    translations.insert("filters".to_string(), "Filters:".to_string());
+    translations.insert("filter_deinterlace".to_string(), "deinterlace".to_string());
+    translations.insert("filter_denoise".to_string(), "denoise".to_string());
+    translations.insert("filter_sharpen".to_string(), "sharpen".to_string());
+    translations.insert("color".to_string(), "Color:".to_string());
+    translations.insert("lut".to_string(), "LUT:".to_string());
+    translations.insert("brightness".to_string(), "brightness".to_string());
+    translations.insert("contrast".to_string(), "contrast".to_string());
+    translations.insert("saturation".to_string(), "saturation".to_string());
+    translations.insert("gamma".to_string(), "gamma".to_string());
+    translations.insert(
+        "adjust_colors_prompt".to_string(),
+        "Adjust colors?".to_string(),
+    );
+    translations.insert(
+        "brightness_prompt".to_string(),
+        "Brightness (-1.0 to 1.0)".to_string(),
+    );
+    translations.insert(
+        "contrast_prompt".to_string(),
+        "Contrast (0.0 to 3.0)".to_string(),
+    );
+    translations.insert(
+        "saturation_prompt".to_string(),
+        "Saturation (0.0 to 3.0, 0 = greyscale)".to_string(),
+    );
+    translations.insert(
+        "gamma_prompt".to_string(),
+        "Gamma (0.1 to 10.0)".to_string(),
+    );
+    translations.insert(
+        "lut_prompt".to_string(),
+        "3D LUT file (.cube), empty for none".to_string(),
+    );
+    translations.insert(
+        "no_crop_needed".to_string(),
+        "Nothing to crop, keeping the full frame".to_string(),
+    );
 
     // Preset names
     translations.insert(
@@ -196,6 +278,61 @@ fn english_translations() -> HashMap<String, String> {
         "(Leave empty to keep original)".to_string(),
     );
     translations.insert("remove_audio".to_string(), "Remove audio?".to_string());
+    translations.insert("audio_codec_prompt".to_string(), "Audio codec".to_string());
+    translations.insert(
+        "audio_codec_auto".to_string(),
+        "Auto (container default)".to_string(),
+    );
+    translations.insert(
+        "audio_codec_copy".to_string(),
+        "Copy (keep original audio)".to_string(),
+    );
+    translations.insert(
+        "audio_bitrate_prompt".to_string(),
+        "Audio bitrate, e.g. 96k (empty = default)".to_string(),
+    );
+    translations.insert(
+        "audio_mono_prompt".to_string(),
+        "Downmix audio to mono?".to_string(),
+    );
+    translations.insert(
+        "audio_rate_prompt".to_string(),
+        "Audio sample rate in Hz (empty = keep)".to_string(),
+    );
+    translations.insert(
+        "stream_copy".to_string(),
+        "Stream copy (no re-encoding)".to_string(),
+    );
+    translations.insert("trim".to_string(), "Trim:".to_string());
+    translations.insert("speed".to_string(), "Speed:".to_string());
+    translations.insert("watermark".to_string(), "Watermark:".to_string());
+    translations.insert("text_overlay".to_string(), "Text:".to_string());
+    translations.insert("audio_tracks".to_string(), "Audio tracks:".to_string());
+    translations.insert("audio_filters".to_string(), "Audio filters:".to_string());
+    translations.insert("normalize_audio".to_string(), "normalize".to_string());
+    translations.insert("fade_in".to_string(), "fade in".to_string());
+    translations.insert("fade_out".to_string(), "fade out".to_string());
+    translations.insert("subtitles".to_string(), "Subtitles:".to_string());
+    translations.insert("metadata".to_string(), "Metadata:".to_string());
+    translations.insert("hdr".to_string(), "HDR:".to_string());
+    translations.insert("hdr_keep".to_string(), "kept (10-bit)".to_string());
+    translations.insert("hdr_tonemap".to_string(), "tone-mapped to SDR".to_string());
+    translations.insert(
+        "burn_subtitles".to_string(),
+        "Burned-in subtitles:".to_string(),
+    );
+    translations.insert(
+        "burn_subtitles_prompt".to_string(),
+        "Burn subtitles into the video?".to_string(),
+    );
+    translations.insert(
+        "trim_start_prompt".to_string(),
+        "Start time, HH:MM:SS or seconds (empty = beginning)".to_string(),
+    );
+    translations.insert(
+        "trim_end_prompt".to_string(),
+        "End time, HH:MM:SS or seconds (empty = end)".to_string(),
+    );
     translations.insert("rotate_video".to_string(), "Rotate video".to_string());
     translations.insert(
         "flip_horizontally".to_string(),
@@ -211,7 +348,7 @@ fn english_translations() -> HashMap<String, String> {
     );
     translations.insert(
         "crop_example".to_string(),
-        "Example: 1920x1080:0:0 (crop to 1920x1080 from top-left corner)".to_string(),
+        "Example: 1920x1080:0:0 (crop to 1920x1080 from top-left corner), or auto to remove black bars".to_string(),
     );
 
     // Rotation options
@@ -236,6 +373,32 @@ fn english_translations() -> HashMap<String, String> {
     translations.insert("mkv_format".to_string(), "MKV".to_string());
     translations.insert("avi_format".to_string(), "AVI".to_string());
     translations.insert("mov_format".to_string(), "MOV".to_string());
+    translations.insert(
+        "gif_format".to_string(),
+        "GIF (animated preview, no sound)".to_string(),
+    );
+    translations.insert(
+        "webp_format".to_string(),
+        "WebP (animated preview, no sound)".to_string(),
+    );
+    translations.insert("gif_dither".to_string(), "GIF dither:".to_string());
+    translations.insert("concat_then".to_string(), "Then:".to_string());
+    translations.insert("split_size".to_string(), "Part size:".to_string());
+    translations.insert("split_duration".to_string(), "Part length:".to_string());
+    translations.insert("parts".to_string(), "Parts:".to_string());
+    translations.insert(
+        "extracting_stills".to_string(),
+        "Extracting frames...".to_string(),
+    );
+    translations.insert("stills_complete".to_string(), "Images created".to_string());
+    translations.insert("still_thumbnail".to_string(), "Thumbnail:".to_string());
+    translations.insert("still_poster".to_string(), "Poster:".to_string());
+    translations.insert(
+        "still_contact_sheet".to_string(),
+        "Contact sheet:".to_string(),
+    );
+    translations.insert("still_sprite".to_string(), "Sprite:".to_string());
+    translations.insert("still_vtt".to_string(), "WebVTT track:".to_string());
 
     // Preset options
     translations.insert(
@@ -305,6 +468,18 @@ fn english_translations() -> HashMap<String, String> {
         "ffmpeg_not_found".to_string(),
         "FFmpeg not found. Please install FFmpeg or use bundled version.".to_string(),
     );
+    translations.insert(
+        "incompatible_options".to_string(),
+        "Incompatible options".to_string(),
+    );
+    translations.insert(
+        "target_size_unreachable".to_string(),
+        "Target size is too small for this video".to_string(),
+    );
+    translations.insert(
+        "crop_out_of_bounds".to_string(),
+        "Crop area does not fit in the video".to_string(),
+    );
     translations.insert("ffmpeg_error".to_string(), "FFmpeg error".to_string());
     translations.insert(
         "compression_cancelled_by_user".to_string(),
@@ -338,6 +513,18 @@ fn english_translations() -> HashMap<String, String> {
         "hint_invalid_output".to_string(),
         "💡 Suggestions:\n\n  • Check if the output directory exists: {path}\n  • Make sure you have write permissions to the directory\n  • Ensure the filename doesn't contain invalid characters: < > : \" / \\ | ? *\n  • Try using a different output location".to_string(),
     );
+    translations.insert(
+        "hint_incompatible_options".to_string(),
+        "💡 The requested settings cannot be combined:\n\n  • Check that the codec is supported by the output format\n    (h264: everything except webm; hevc: mp4, mov, mkv; vp9/av1: webm, mkv, mp4)\n  • Check that the audio codec is supported by the output format\n    (aac: everything except webm/wmv; opus: webm, mkv, mp4; mp3: everything except webm)\n  • Run with --help to see all available options".to_string(),
+    );
+    translations.insert(
+        "hint_target_size_unreachable".to_string(),
+        "💡 The video is too long for the requested size:\n\n  • Use a target of at least {min}\n  • Add --mute to free the audio budget for video\n  • Shorten the video before compressing it".to_string(),
+    );
+    translations.insert(
+        "hint_crop_out_of_bounds".to_string(),
+        "💡 The frame is {frame} (after --rotate):\n\n  • Keep X + width and Y + height within it\n  • Use --crop auto to remove black bars\n  • Run with --info to check the video size".to_string(),
+    );
     translations.insert(
         "hint_ffmpeg_error".to_string(),
         "💡 FFmpeg encountered an error:\n\n  Error: {msg}\n\n  Possible solutions:\n  • Try reducing quality or changing preset\n  • Check if there's enough disk space\n  • Verify the input video is not corrupted\n  • Try updating FFmpeg to the latest version".to_string(),
@@ -412,6 +599,11 @@ fn russian_translations() -> HashMap<String, String> {
     translations.insert("duration".to_string(), "Длительность:".to_string());
     translations.insert("resolution".to_string(), "Разрешение:".to_string());
     translations.insert("frame_rate".to_string(), "Частота кадров:".to_string());
+    translations.insert("container".to_string(), "Контейнер:".to_string());
+    translations.insert("bitrate".to_string(), "Битрейт:".to_string());
+    translations.insert("streams".to_string(), "Потоки:".to_string());
+    translations.insert("default_track".to_string(), "по умолчанию".to_string());
+    translations.insert("cover_art".to_string(), "обложка".to_string());
 
     // Compression settings
     translations.insert(
@@ -423,13 +615,99 @@ fn russian_translations() -> HashMap<String, String> {
     translations.insert("preset".to_string(), "Пресет:".to_string());
     translations.insert("quality".to_string(), "Качество:".to_string());
     translations.insert("dimensions".to_string(), "Размеры:".to_string());
+    translations.insert(
+        "max_resolution".to_string(),
+        "Макс. разрешение:".to_string(),
+    );
     translations.insert("fps".to_string(), "FPS:".to_string());
     translations.insert("audio".to_string(), "Аудио:".to_string());
     translations.insert("muted".to_string(), "без звука".to_string());
     translations.insert("format".to_string(), "Формат:".to_string());
+    translations.insert("codec".to_string(), "Кодек:".to_string());
+    translations.insert("target_size".to_string(), "Целевой размер:".to_string());
+    translations.insert("target_met".to_string(), "достигнут".to_string());
+    translations.insert("target_missed".to_string(), "не достигнут".to_string());
+    translations.insert("quality_metrics".to_string(), "Качество:".to_string());
+    translations.insert(
+        "measuring_quality".to_string(),
+        "Измерение качества относительно исходника...".to_string(),
+    );
+    translations.insert(
+        "quality_measurement_failed".to_string(),
+        "Не удалось измерить качество".to_string(),
+    );
+    translations.insert(
+        "target_quality".to_string(),
+        "Целевое качество:".to_string(),
+    );
+    translations.insert(
+        "searching_crf".to_string(),
+        "Кодирование фрагментов для подбора CRF под {target}...".to_string(),
+    );
+    translations.insert(
+        "crf_chosen".to_string(),
+        "Выбран CRF {crf} ({metric} {score})".to_string(),
+    );
+    translations.insert(
+        "target_quality_missed".to_string(),
+        "цель не достигнута даже при лучшем качестве в диапазоне".to_string(),
+    );
     translations.insert("rotate".to_string(), "Поворот:".to_string());
     translations.insert("flip".to_string(), "Отражение:".to_string());
     translations.insert("crop".to_string(), "Обрезка:".to_string());
+    translations.insert(
+        "detecting_crop".to_string(),
+        "Поиск чёрных полос...".to_string(),
+    );
+    translations.insert(
+        "detecting_interlace".to_string(),
+        "Проверка на чересстрочность...".to_string(),
+    );
+    translations.insert(
+        "measuring_loudness".to_string(),
+        "Измерение громкости...".to_string(),
+    );
+    translations.insert("filters".to_string(), "Фильтры:".to_string());
+    translations.insert(
+        "filter_deinterlace".to_string(),
+        "деинтерлейсинг".to_string(),
+    );
+    translations.insert("filter_denoise".to_string(), "шумоподавление".to_string());
+    translations.insert("filter_sharpen".to_string(), "резкость".to_string());
+    translations.insert("color".to_string(), "Цвет:".to_string());
+    translations.insert("lut".to_string(), "LUT:".to_string());
+    translations.insert("brightness".to_string(), "яркость".to_string());
+    translations.insert("contrast".to_string(), "контраст".to_string());
+    translations.insert("saturation".to_string(), "насыщенность".to_string());
+    translations.insert("gamma".to_string(), "гамма".to_string());
+    translations.insert(
+        "adjust_colors_prompt".to_string(),
+        "Настроить цвет?".to_string(),
+    );
+    translations.insert(
+        "brightness_prompt".to_string(),
+        "Яркость (от -1.0 до 1.0)".to_string(),
+    );
+    translations.insert(
+        "contrast_prompt".to_string(),
+        "Контраст (от 0.0 до 3.0)".to_string(),
+    );
+    translations.insert(
+        "saturation_prompt".to_string(),
+        "Насыщенность (от 0.0 до 3.0, 0 = оттенки серого)".to_string(),
+    );
+    translations.insert(
+        "gamma_prompt".to_string(),
+        "Гамма (от 0.1 до 10.0)".to_string(),
+    );
+    translations.insert(
+        "lut_prompt".to_string(),
+        "Файл 3D LUT (.cube), пусто — без него".to_string(),
+    );
+    translations.insert(
+        "no_crop_needed".to_string(),
+        "Обрезать нечего, кадр остаётся целым".to_string(),
+    );
 
     // Preset names
     translations.insert(
@@ -504,6 +782,58 @@ fn russian_translations() -> HashMap<String, String> {
         "(Оставьте пустым, чтобы сохранить оригинал)".to_string(),
     );
     translations.insert("remove_audio".to_string(), "Удалить аудио?".to_string());
+    translations.insert("audio_codec_prompt".to_string(), "Аудиокодек".to_string());
+    translations.insert(
+        "audio_codec_auto".to_string(),
+        "Авто (по умолчанию для контейнера)".to_string(),
+    );
+    translations.insert(
+        "audio_codec_copy".to_string(),
+        "Копировать (оставить исходное аудио)".to_string(),
+    );
+    translations.insert(
+        "audio_bitrate_prompt".to_string(),
+        "Битрейт аудио, например 96k (пусто = по умолчанию)".to_string(),
+    );
+    translations.insert(
+        "audio_mono_prompt".to_string(),
+        "Свести аудио в моно?".to_string(),
+    );
+    translations.insert(
+        "audio_rate_prompt".to_string(),
+        "Частота дискретизации аудио в Гц (пусто = оставить)".to_string(),
+    );
+    translations.insert(
+        "stream_copy".to_string(),
+        "Копирование потоков (без перекодирования)".to_string(),
+    );
+    translations.insert("trim".to_string(), "Обрезка:".to_string());
+    translations.insert("speed".to_string(), "Скорость:".to_string());
+    translations.insert("watermark".to_string(), "Водяной знак:".to_string());
+    translations.insert("text_overlay".to_string(), "Текст:".to_string());
+    translations.insert("audio_tracks".to_string(), "Аудиодорожки:".to_string());
+    translations.insert("audio_filters".to_string(), "Аудиофильтры:".to_string());
+    translations.insert("normalize_audio".to_string(), "нормализация".to_string());
+    translations.insert("fade_in".to_string(), "нарастание".to_string());
+    translations.insert("fade_out".to_string(), "затухание".to_string());
+    translations.insert("subtitles".to_string(), "Субтитры:".to_string());
+    translations.insert("burn_subtitles".to_string(), "Вшитые субтитры:".to_string());
+    translations.insert("metadata".to_string(), "Метаданные:".to_string());
+    translations.insert("hdr".to_string(), "HDR:".to_string());
+    translations.insert("hdr_keep".to_string(), "сохраняется (10 бит)".to_string());
+    translations.insert("hdr_tonemap".to_string(), "преобразуется в SDR".to_string());
+    translations.insert(
+        "burn_subtitles_prompt".to_string(),
+        "Вшить субтитры в видео?".to_string(),
+    );
+    translations.insert(
+        "trim_start_prompt".to_string(),
+        "Время начала, ЧЧ:ММ:СС или секунды (пусто = с начала)".to_string(),
+    );
+    translations.insert(
+        "trim_end_prompt".to_string(),
+        "Время конца, ЧЧ:ММ:СС или секунды (пусто = до конца)".to_string(),
+    );
     translations.insert("rotate_video".to_string(), "Повернуть видео".to_string());
     translations.insert(
         "flip_horizontally".to_string(),
@@ -519,7 +849,7 @@ fn russian_translations() -> HashMap<String, String> {
     );
     translations.insert(
         "crop_example".to_string(),
-        "Пример: 1920x1080:0:0 (обрезать до 1920x1080 от левого верхнего угла)".to_string(),
+        "Пример: 1920x1080:0:0 (обрезать до 1920x1080 от левого верхнего угла) или auto, чтобы убрать чёрные полосы".to_string(),
     );
 
     // Rotation options
@@ -547,6 +877,35 @@ fn russian_translations() -> HashMap<String, String> {
     translations.insert("mkv_format".to_string(), "MKV".to_string());
     translations.insert("avi_format".to_string(), "AVI".to_string());
     translations.insert("mov_format".to_string(), "MOV".to_string());
+    translations.insert(
+        "gif_format".to_string(),
+        "GIF (анимированное превью, без звука)".to_string(),
+    );
+    translations.insert(
+        "webp_format".to_string(),
+        "WebP (анимированное превью, без звука)".to_string(),
+    );
+    translations.insert("gif_dither".to_string(), "Дизеринг GIF:".to_string());
+    translations.insert("concat_then".to_string(), "Затем:".to_string());
+    translations.insert("split_size".to_string(), "Размер частей:".to_string());
+    translations.insert("split_duration".to_string(), "Длина частей:".to_string());
+    translations.insert("parts".to_string(), "Части:".to_string());
+    translations.insert(
+        "extracting_stills".to_string(),
+        "Извлечение кадров...".to_string(),
+    );
+    translations.insert(
+        "stills_complete".to_string(),
+        "Изображения созданы".to_string(),
+    );
+    translations.insert("still_thumbnail".to_string(), "Миниатюра:".to_string());
+    translations.insert("still_poster".to_string(), "Постер:".to_string());
+    translations.insert(
+        "still_contact_sheet".to_string(),
+        "Раскадровка:".to_string(),
+    );
+    translations.insert("still_sprite".to_string(), "Спрайт:".to_string());
+    translations.insert("still_vtt".to_string(), "Дорожка WebVTT:".to_string());
 
     // Preset options
     translations.insert(
@@ -623,6 +982,18 @@ fn russian_translations() -> HashMap<String, String> {
         "FFmpeg не найден. Пожалуйста, установите FFmpeg или используйте встроенную версию."
             .to_string(),
     );
+    translations.insert(
+        "incompatible_options".to_string(),
+        "Несовместимые параметры".to_string(),
+    );
+    translations.insert(
+        "target_size_unreachable".to_string(),
+        "Целевой размер слишком мал для этого видео".to_string(),
+    );
+    translations.insert(
+        "crop_out_of_bounds".to_string(),
+        "Область обрезки не помещается в кадр".to_string(),
+    );
     translations.insert("ffmpeg_error".to_string(), "Ошибка FFmpeg".to_string());
     translations.insert(
         "compression_cancelled_by_user".to_string(),
@@ -656,6 +1027,18 @@ fn russian_translations() -> HashMap<String, String> {
         "hint_invalid_output".to_string(),
         "💡 Подсказки:\n\n  • Проверьте, существует ли выходной каталог: {path}\n  • Убедитесь, что у вас есть права на запись в каталог\n  • Убедитесь, что имя файла не содержит недопустимых символов: < > : \" / \\ | ? *\n  • Попробуйте использовать другое место вывода".to_string(),
     );
+    translations.insert(
+        "hint_incompatible_options".to_string(),
+        "💡 Запрошенные параметры нельзя использовать вместе:\n\n  • Проверьте, что кодек поддерживается выходным форматом\n    (h264: всё, кроме webm; hevc: mp4, mov, mkv; vp9/av1: webm, mkv, mp4)\n  • Проверьте, что аудиокодек поддерживается выходным форматом\n    (aac: всё, кроме webm/wmv; opus: webm, mkv, mp4; mp3: всё, кроме webm)\n  • Запустите с --help, чтобы увидеть все доступные параметры".to_string(),
+    );
+    translations.insert(
+        "hint_target_size_unreachable".to_string(),
+        "💡 Видео слишком длинное для заданного размера:\n\n  • Укажите размер не меньше {min}\n  • Добавьте --mute, чтобы отдать бюджет аудио под видео\n  • Сократите видео перед сжатием".to_string(),
+    );
+    translations.insert(
+        "hint_crop_out_of_bounds".to_string(),
+        "💡 Размер кадра {frame} (с учётом --rotate):\n\n  • X + ширина и Y + высота должны помещаться в кадр\n  • Используйте --crop auto, чтобы убрать чёрные полосы\n  • Запустите с --info, чтобы узнать размер видео".to_string(),
+    );
     translations.insert(
         "hint_ffmpeg_error".to_string(),
         "💡 FFmpeg столкнулся с ошибкой:\n\n  Ошибка: {msg}\n\n  Возможные решения:\n  • Попробуйте снизить качество или изменить пресет\n  • Проверьте, достаточно ли места на диске\n  • Убедитесь, что входное видео не повреждено\n  • Попробуйте обновить FFmpeg до последней версии".to_string(),
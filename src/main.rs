@@ -6,6 +6,7 @@ mod fs;
 mod interactive;
 mod localization;
 mod output;
+mod probe;
 mod progress;
 
 use clap::Parser;
@@ -19,7 +20,7 @@ use std::sync::{
 
 use cli::Cli;
 use cli::LanguageArg;
-use domain::{CompressionConfig, CompressionResult};
+use domain::{AutoCrop, CompressionConfig, CompressionResult, Deinterlace, ThumbnailRequest};
 use error::CompressoError;
 use ffmpeg::FFmpeg;
 use localization::{set_language, t};
@@ -184,6 +185,20 @@ fn main() {
             return;
         }
 
+        // --thumbnails, --poster and --contact-sheet extract images instead
+        // of compressing
+        if let Some(request) = cli.thumbnail_request() {
+            if let Err(e) = request.and_then(|request| run_thumbnail_mode(&request, cli.json)) {
+                if cli.json {
+                    eprintln!("{}", serde_json::json!({ "error": e.to_string() }));
+                } else {
+                    print_error_with_hint(&e);
+                }
+                std::process::exit(1);
+            }
+            return;
+        }
+
         // Check if this is batch processing (multiple inputs or directory)
         let input_files = get_input_files(&cli);
 
@@ -194,8 +209,8 @@ fn main() {
             std::process::exit(1);
         }
 
-        // If multiple files, run batch processing
-        if input_files.len() > 1 {
+        // If multiple files, run batch processing (unless they are joined)
+        if input_files.len() > 1 && !cli.concat {
             let outcome = run_batch_mode(&cli, input_files, cancelled.clone());
             // Non-zero exit code when any file failed, so CI can detect it.
             if outcome.failed > 0 && !outcome.cancelled {
@@ -208,7 +223,19 @@ fn main() {
         }
 
         // Single file mode - use existing logic
-        cli.to_config()
+        let mut config = cli.to_config();
+        // --concat joins every input into this one output
+        if cli.concat {
+            if input_files.len() < 2 {
+                print_error_with_hint(&CompressoError::IncompatibleOptions(
+                    "--concat needs at least two inputs".to_string(),
+                ));
+                std::process::exit(1);
+            }
+            config.input_path = input_files[0].clone();
+            config.concat = input_files[1..].to_vec();
+        }
+        config
     };
 
     // Run the application (cancelled handler was installed at the top of main)
@@ -296,7 +323,39 @@ fn run_info_mode(cli: &Cli) {
     }
 }
 
-fn run(config: CompressionConfig, cancelled: Arc<AtomicBool>) -> error::Result<CompressionResult> {
+fn run_thumbnail_mode(request: &ThumbnailRequest, json: bool) -> error::Result<()> {
+    if !json {
+        print_header();
+    }
+
+    if !fs::file_exists(&request.input_path) {
+        return Err(CompressoError::FileNotFound(request.input_path.clone()));
+    }
+
+    let ffmpeg = FFmpeg::new()?;
+    let video_info = ffmpeg.get_video_info(&request.input_path)?;
+    let start_time = std::time::Instant::now();
+
+    if !json {
+        let file_metadata = fs::get_file_metadata(&request.input_path)?;
+        print_video_info(&request.input_path, &video_info, file_metadata.size);
+        print_info(&t("extracting_stills"));
+    }
+
+    let files = ffmpeg.generate_stills(request, &video_info)?;
+
+    if json {
+        print_stills_json(&request.input_path, &files, start_time.elapsed());
+    } else {
+        print_stills(&files, start_time.elapsed());
+    }
+    Ok(())
+}
+
+fn run(
+    mut config: CompressionConfig,
+    cancelled: Arc<AtomicBool>,
+) -> error::Result<CompressionResult> {
     // Print header (skip in JSON mode)
     if !config.json {
         print_header();
@@ -307,18 +366,20 @@ fn run(config: CompressionConfig, cancelled: Arc<AtomicBool>) -> error::Result<C
         return Err(CompressoError::FileNotFound(config.input_path.clone()));
     }
 
-    if !fs::is_video_file(&config.input_path) {
-        return Err(CompressoError::InvalidInput(format!(
-            "{} is not a valid video file",
-            config.input_path
-        )));
+    for path in std::iter::once(&config.input_path).chain(&config.concat) {
+        if !fs::is_video_file(path) {
+            return Err(CompressoError::InvalidInput(format!(
+                "{} is not a valid video file",
+                path
+            )));
+        }
     }
 
     // Initialize FFmpeg
     let ffmpeg = FFmpeg::new()?;
 
-    // Get video info
-    let video_info = ffmpeg.get_video_info(&config.input_path)?;
+    // Get video info (with --concat, of all inputs joined)
+    let video_info = ffmpeg.concat_info(&config, ffmpeg.get_video_info(&config.input_path)?)?;
     let file_metadata = fs::get_file_metadata(&config.input_path)?;
 
     // Determine output path
@@ -330,24 +391,89 @@ fn run(config: CompressionConfig, cancelled: Arc<AtomicBool>) -> error::Result<C
         }
     });
 
+    // Check for overwrite before any analysis pass reads the input
+    // A split output is written as <output>_part01, _part02, ...
+    let first_output = match config.split {
+        Some(_) => fs::part_path(&output_path, 1),
+        None => output_path.clone(),
+    };
+    if !config.overwrite && fs::file_exists(&first_output) {
+        if !config.json {
+            print_warning(&format!("Output file already exists: {}", first_output));
+            print_info("Use -y flag to overwrite.");
+        }
+        return Err(CompressoError::InvalidOutput(format!(
+            "File already exists: {}",
+            first_output
+        )));
+    }
+
+    // Settle the HDR handling up front so it is shown with the settings.
+    let output_format = FFmpeg::output_format(&config, &config.input_path);
+    FFmpeg::apply_animation_defaults(&mut config, &video_info, &output_format);
+    config.hdr = ffmpeg.hdr_mode(&config, &video_info, &output_format)?;
+
     // Print video info and config (skip in JSON mode)
     if !config.json {
         print_video_info(&config.input_path, &video_info, file_metadata.size);
-        print_config(&config, &output_path);
     }
 
-    // Check for overwrite
-    if !config.overwrite && fs::file_exists(&output_path) {
+    // --crop auto / --crop-aspect become a plain crop for this file
+    if let Some(auto) = config.auto_crop.take() {
+        if !config.json && auto == AutoCrop::Detect {
+            print_info(&t("detecting_crop"));
+        }
+        config.transforms.crop = ffmpeg.resolve_auto_crop(auto, &config, &video_info)?;
+        if !config.json && config.transforms.crop.is_none() {
+            print_info(&t("no_crop_needed"));
+        }
+    }
+
+    if config.filters.deinterlace == Deinterlace::Auto {
         if !config.json {
-            print_warning(&format!("Output file already exists: {}", output_path));
-            print_info("Use -y flag to overwrite.");
+            print_info(&t("detecting_interlace"));
         }
-        return Err(CompressoError::InvalidOutput(format!(
-            "File already exists: {}",
-            output_path
-        )));
+        config.filters.deinterlace =
+            ffmpeg.resolve_deinterlace(&config, &video_info, &output_format)?;
     }
 
+    // First pass of --normalize-audio; the encode is the second.
+    if config.audio_filters.normalize && !config.mute {
+        if !config.json {
+            print_info(&t("measuring_loudness"));
+        }
+        config.audio_filters.loudness = ffmpeg.measure_loudness(&config, &video_info)?;
+    }
+
+    if !config.json {
+        print_config(&config, &output_path);
+    }
+
+    // --target-quality: pick the CRF from sample encodes before the real run
+    let crf_search = match config.target_quality {
+        Some(target) => {
+            if !config.json {
+                print_info(&t("searching_crf").replace("{target}", &target.to_string()));
+            }
+            let search = ffmpeg.search_crf(&config, &video_info, cancelled.clone())?;
+            if !config.json {
+                let msg = t("crf_chosen")
+                    .replace("{crf}", &search.crf.to_string())
+                    .replace("{metric}", search.metric.name())
+                    .replace("{score}", &format!("{:.4}", search.score));
+                if search.target_met {
+                    print_info(&msg);
+                } else {
+                    print_warning(&format!("{} ({})", msg, t("target_quality_missed")));
+                }
+                println!();
+            }
+            config.crf = Some(search.crf);
+            Some(search)
+        }
+        None => None,
+    };
+
     // Create progress bar (skip in JSON mode)
     let json_mode = config.json;
     let progress_bar = if !json_mode {
@@ -360,7 +486,7 @@ fn run(config: CompressionConfig, cancelled: Arc<AtomicBool>) -> error::Result<C
     // Start compression
     let start_time = std::time::Instant::now();
 
-    let result = ffmpeg.compress_video(
+    let mut result = ffmpeg.compress_video(
         &config,
         Some(&video_info),
         cancelled.clone(),
@@ -379,12 +505,29 @@ fn run(config: CompressionConfig, cancelled: Arc<AtomicBool>) -> error::Result<C
     )?;
 
     let elapsed = start_time.elapsed();
+    result.crf_search = crf_search;
 
     // Finish progress bar (skip in JSON mode)
     if !config.json {
         finish_progress(&progress_bar);
     }
 
+    // Quality scores are informational: a failed measurement must not throw
+    // away a finished encode, so it only produces a warning.
+    if config.measure_quality {
+        if !config.json {
+            print_info(&t("measuring_quality"));
+        }
+        match ffmpeg.measure_quality(&config, &video_info, &result.file_path) {
+            Ok(metrics) => result.quality_metrics = Some(metrics),
+            Err(e) => {
+                if !config.json {
+                    print_warning(&format!("{}: {}", t("quality_measurement_failed"), e));
+                }
+            }
+        }
+    }
+
     // Print result (only in non-batch mode - batch mode handles its own output)
     if !config.json {
         print_result(&result, elapsed);
@@ -653,6 +796,7 @@ fn run_interactive_batch(files: Vec<String>, cancelled: Arc<AtomicBool>) -> Batc
     let mut height: Option<u32> = None;
     let mut fps: Option<u32> = None;
     let mut mute = false;
+    let mut audio = domain::AudioSettings::default();
 
     if show_advanced {
         println!();
@@ -702,6 +846,10 @@ fn run_interactive_batch(files: Vec<String>, cancelled: Arc<AtomicBool>) -> Batc
             .interact()
             .unwrap_or(0);
         mute = mute_idx == 1;
+
+        if !mute {
+            audio = interactive::prompt_audio_settings(&theme);
+        }
     }
 
     // Confirm and start
@@ -764,10 +912,12 @@ fn run_interactive_batch(files: Vec<String>, cancelled: Arc<AtomicBool>) -> Batc
             height,
             fps,
             mute,
+            audio,
             transforms: domain::VideoTransforms::default(),
             overwrite: true,
             verbose: false,
             json: false,
+            ..CompressionConfig::default()
         };
 
         // Process the file
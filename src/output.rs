@@ -4,7 +4,11 @@ use serde::Serialize;
 use std::io::IsTerminal;
 use std::sync::{Arc, Mutex};
 
-use crate::domain::{CompressionConfig, CompressionResult, Preset, VideoInfo};
+use crate::domain::{
+    format_timestamp, CompressionConfig, CompressionResult, CrfSearch, Deinterlace, GeneratedFile,
+    HdrMode, MetadataMode, OutputFormat, OutputPart, Preset, QualityMetrics, RemuxMode, SplitMode,
+    StillKind, StreamInfo, TrackSelection, VideoInfo,
+};
 use crate::fs::format_size;
 use crate::localization::t;
 
@@ -54,9 +58,100 @@ pub fn print_video_info(path: &str, info: &VideoInfo, size: u64) {
         );
     }
 
+    if let Some(video_codec) = &info.video_codec {
+        let codecs = match &info.audio_codec {
+            Some(audio_codec) => format!("{} / {}", video_codec, audio_codec),
+            None => video_codec.clone(),
+        };
+        println!("  {} {}", t("codec").dimmed(), codecs.bright_white());
+    }
+
+    if let Some(container) = &info.container {
+        println!("  {} {}", t("container").dimmed(), container.bright_white());
+    }
+
+    if let Some(bitrate) = info.bitrate {
+        println!(
+            "  {} {}",
+            t("bitrate").dimmed(),
+            format_bitrate(bitrate).bright_white()
+        );
+    }
+
+    if !info.streams.is_empty() {
+        println!();
+        println!("  {}", t("streams").dimmed());
+        for stream in &info.streams {
+            println!(
+                "    {} {:<8} {}",
+                format!("#{}", stream.index).bright_cyan(),
+                stream.kind.name(),
+                format_stream(stream)
+            );
+        }
+    }
+
     println!();
 }
 
+/// `4.8 Mb/s`, `192 kb/s`
+fn format_bitrate(bits_per_second: u64) -> String {
+    if bits_per_second >= 1_000_000 {
+        format!("{:.1} Mb/s", bits_per_second as f64 / 1_000_000.0)
+    } else {
+        format!("{} kb/s", bits_per_second / 1000)
+    }
+}
+
+/// One-line stream description for `--info`, e.g.
+/// `hevc (Main 10) · 3840x2160 · 29.97 fps · yuv420p10le · bt2020nc/arib-std-b67`
+pub fn format_stream(stream: &StreamInfo) -> String {
+    let mut parts = Vec::new();
+    let codec = stream.codec.as_deref().unwrap_or("?");
+    parts.push(match &stream.profile {
+        Some(profile) => format!("{} ({})", codec, profile),
+        None => codec.to_string(),
+    });
+    if let (Some(w), Some(h)) = (stream.width, stream.height) {
+        parts.push(format!("{}x{}", w, h));
+    }
+    if let Some(fps) = stream.fps.filter(|_| !stream.attached_pic) {
+        parts.push(format!("{:.2} fps", fps));
+    }
+    if let Some(pix_fmt) = &stream.pix_fmt {
+        parts.push(pix_fmt.clone());
+    }
+    if let (Some(space), Some(transfer)) = (&stream.color_space, &stream.color_transfer) {
+        parts.push(format!("{}/{}", space, transfer));
+    }
+    if let Some(rotation) = stream.rotation {
+        parts.push(format!("{}°", rotation));
+    }
+    if let Some(rate) = stream.sample_rate {
+        parts.push(format!("{} Hz", rate));
+    }
+    if let Some(layout) = &stream.channel_layout {
+        parts.push(layout.clone());
+    } else if let Some(channels) = stream.channels {
+        parts.push(format!("{}ch", channels));
+    }
+    if let Some(bitrate) = stream.bitrate {
+        parts.push(format_bitrate(bitrate));
+    }
+    if let Some(language) = &stream.language {
+        parts.push(format!("[{}]", language));
+    }
+    if let Some(title) = &stream.title {
+        parts.push(format!("\"{}\"", title));
+    }
+    if stream.attached_pic {
+        parts.push(t("cover_art"));
+    } else if stream.default {
+        parts.push(t("default_track"));
+    }
+    parts.join(" · ")
+}
+
 /// Print compression configuration
 pub fn print_config(config: &CompressionConfig, output_path: &str) {
     println!("{}", t("compression_settings").bright_white().bold());
@@ -67,6 +162,9 @@ pub fn print_config(config: &CompressionConfig, output_path: &str) {
         t("input").dimmed(),
         config.input_path.bright_white()
     );
+    for path in &config.concat {
+        println!("  {} {}", t("concat_then").dimmed(), path.bright_white());
+    }
     println!("  {} {}", t("output").dimmed(), output_path.bright_white());
     println!(
         "  {} {}",
@@ -76,18 +174,79 @@ pub fn print_config(config: &CompressionConfig, output_path: &str) {
             Preset::Ironclad => t("ironclad_preset").bright_blue(),
         }
     );
-    println!(
-        "  {} {}%",
-        t("quality").dimmed(),
-        config.quality.to_string().bright_yellow()
-    );
+    if config.remux == RemuxMode::Always {
+        println!("  {}", t("stream_copy").bright_cyan());
+    } else if let Some(target) = config.target_size {
+        println!(
+            "  {} {}",
+            t("target_size").dimmed(),
+            format_size(target).bright_yellow()
+        );
+    } else if let Some(target) = config.target_quality {
+        println!(
+            "  {} {}",
+            t("target_quality").dimmed(),
+            target.to_string().bright_yellow()
+        );
+    } else {
+        println!(
+            "  {} {}%",
+            t("quality").dimmed(),
+            config.quality.to_string().bright_yellow()
+        );
+    }
+
+    if let Some(codec) = config.codec {
+        println!(
+            "  {} {}",
+            t("codec").dimmed(),
+            codec.to_string().bright_white()
+        );
+    }
+
+    match config.split {
+        Some(SplitMode::Size(bytes)) => println!(
+            "  {} {}",
+            t("split_size").dimmed(),
+            format_size(bytes).bright_white()
+        ),
+        Some(SplitMode::Duration(seconds)) => println!(
+            "  {} {}",
+            t("split_duration").dimmed(),
+            format_timestamp(seconds).bright_white()
+        ),
+        None => {}
+    }
 
-    if let (Some(w), Some(h)) = (config.width, config.height) {
+    let is_gif = std::path::Path::new(output_path)
+        .extension()
+        .and_then(|e| e.to_str())
+        .and_then(OutputFormat::from_extension)
+        == Some(OutputFormat::Gif);
+    if is_gif {
+        println!(
+            "  {} {}",
+            t("gif_dither").dimmed(),
+            config.gif_dither.to_string().bright_white()
+        );
+    }
+
+    if config.width.is_some() || config.height.is_some() {
+        // A missing side follows the aspect ratio.
+        let side = |v: Option<u32>| v.map_or("…".to_string(), |v| v.to_string());
         println!(
             "  {} {}x{}",
             t("dimensions").dimmed(),
-            w.to_string().bright_white(),
-            h.to_string().bright_white()
+            side(config.width).bright_white(),
+            side(config.height).bright_white()
+        );
+    }
+
+    if let Some(max) = config.max_resolution {
+        println!(
+            "  {} {}",
+            t("max_resolution").dimmed(),
+            max.to_string().bright_white()
         );
     }
 
@@ -99,8 +258,158 @@ pub fn print_config(config: &CompressionConfig, output_path: &str) {
         );
     }
 
+    if let Some(crop) = &config.transforms.crop {
+        println!(
+            "  {} {}x{} at ({}, {})",
+            t("crop").dimmed(),
+            crop.width.to_string().bright_white(),
+            crop.height.to_string().bright_white(),
+            crop.x,
+            crop.y
+        );
+    }
+
+    if config.filters.is_active() {
+        let mut filters = Vec::new();
+        if config.filters.deinterlace == Deinterlace::On {
+            filters.push(t("filter_deinterlace"));
+        }
+        if let Some(level) = config.filters.denoise {
+            filters.push(format!("{} ({})", t("filter_denoise"), level));
+        }
+        if config.filters.sharpen {
+            filters.push(t("filter_sharpen"));
+        }
+        println!(
+            "  {} {}",
+            t("filters").dimmed(),
+            filters.join(", ").bright_white()
+        );
+    }
+
+    let color = &config.filters.color;
+    if color.is_active() {
+        let values: Vec<String> = [
+            ("brightness", color.brightness),
+            ("contrast", color.contrast),
+            ("saturation", color.saturation),
+            ("gamma", color.gamma),
+        ]
+        .into_iter()
+        .filter_map(|(key, value)| value.map(|v| format!("{} {}", t(key), v)))
+        .collect();
+        println!(
+            "  {} {}",
+            t("color").dimmed(),
+            values.join(", ").bright_white()
+        );
+    }
+
+    if let Some(lut) = &config.filters.lut {
+        println!("  {} {}", t("lut").dimmed(), lut.bright_white());
+    }
+
+    let overlay = &config.overlay;
+    for (key, value) in [
+        ("watermark", &overlay.image),
+        ("text_overlay", &overlay.text),
+    ] {
+        if let Some(value) = value {
+            println!(
+                "  {} {} ({})",
+                t(key).dimmed(),
+                value.bright_white(),
+                overlay.position
+            );
+        }
+    }
+
+    if let Some(trim) = config.trim {
+        println!(
+            "  {} {}",
+            t("trim").dimmed(),
+            trim.to_string().bright_white()
+        );
+    }
+
+    if let Some(speed) = config.speed {
+        println!(
+            "  {} {}x",
+            t("speed").dimmed(),
+            speed.to_string().bright_white()
+        );
+    }
+
     if config.mute {
         println!("  {} {}", t("audio").dimmed(), t("muted").bright_red());
+    } else if !config.audio.is_default() {
+        println!(
+            "  {} {}",
+            t("audio").dimmed(),
+            config.audio.to_string().bright_white()
+        );
+    }
+
+    let audio_filters = &config.audio_filters;
+    if !config.mute && audio_filters.is_active() {
+        let mut filters = Vec::new();
+        if audio_filters.normalize {
+            filters.push(format!("{} (-16 LUFS)", t("normalize_audio")));
+        }
+        if let Some(db) = audio_filters.volume_db {
+            filters.push(format!("{:+} dB", db));
+        }
+        if let Some(length) = audio_filters.fade_in {
+            filters.push(format!("{} {}s", t("fade_in"), length));
+        }
+        if let Some(length) = audio_filters.fade_out {
+            filters.push(format!("{} {}s", t("fade_out"), length));
+        }
+        println!(
+            "  {} {}",
+            t("audio_filters").dimmed(),
+            filters.join(", ").bright_white()
+        );
+    }
+
+    if !config.mute && config.audio_tracks != TrackSelection::Default {
+        println!(
+            "  {} {}",
+            t("audio_tracks").dimmed(),
+            config.audio_tracks.to_string().bright_white()
+        );
+    }
+
+    if let Some(hdr) = config.hdr {
+        let action = match hdr {
+            HdrMode::Keep => t("hdr_keep"),
+            HdrMode::Tonemap => t("hdr_tonemap"),
+        };
+        println!("  {} {}", t("hdr").dimmed(), action.bright_white());
+    }
+
+    if config.metadata != MetadataMode::Keep {
+        println!(
+            "  {} {}",
+            t("metadata").dimmed(),
+            config.metadata.to_string().bright_white()
+        );
+    }
+
+    if let Some(burn) = &config.burn_subtitles {
+        println!(
+            "  {} {}",
+            t("burn_subtitles").dimmed(),
+            burn.to_string().bright_white()
+        );
+    }
+
+    if config.subtitles != TrackSelection::Default {
+        println!(
+            "  {} {}",
+            t("subtitles").dimmed(),
+            config.subtitles.to_string().bright_white()
+        );
     }
 
     println!();
@@ -239,13 +548,111 @@ pub fn print_result(result: &CompressionResult, elapsed: std::time::Duration) {
         ratio
     );
     println!("  {} {:.2}s", t("time").dimmed(), elapsed.as_secs_f64());
+    if result.remuxed {
+        println!("  {}", t("stream_copy").bright_cyan());
+    }
+    if let (Some(target), Some(met)) = (result.target_size, result.target_met) {
+        let verdict = if met {
+            t("target_met").bright_green()
+        } else {
+            t("target_missed").bright_red()
+        };
+        println!(
+            "  {} {} ({})",
+            t("target_size").dimmed(),
+            format_size(target).bright_white(),
+            verdict
+        );
+    }
+    if let Some(ref search) = result.crf_search {
+        let verdict = if search.target_met {
+            t("target_met").bright_green()
+        } else {
+            t("target_missed").bright_red()
+        };
+        println!(
+            "  {} CRF {} · {} {:.4} / {} ({})",
+            t("target_quality").dimmed(),
+            search.crf.to_string().bright_white(),
+            search.metric,
+            search.score,
+            search.target,
+            verdict
+        );
+    }
+    if let Some(ref metrics) = result.quality_metrics {
+        println!(
+            "  {} {}",
+            t("quality_metrics").dimmed(),
+            format_quality_metrics(metrics).bright_white()
+        );
+    }
     println!();
+    if result.parts.is_empty() {
+        println!(
+            "  {} {}",
+            t("output").dimmed(),
+            result.file_path.bright_cyan()
+        );
+    } else {
+        println!("  {}", t("parts").dimmed());
+        for part in &result.parts {
+            println!(
+                "    {} {}",
+                part.path.bright_cyan(),
+                format_size(part.size).dimmed()
+            );
+        }
+    }
+    println!();
+}
+
+/// Print the files written by thumbnail mode
+pub fn print_stills(files: &[GeneratedFile], elapsed: std::time::Duration) {
+    println!();
+    println!("{}", t("header_separator").dimmed());
     println!(
-        "  {} {}",
-        t("output").dimmed(),
-        result.file_path.bright_cyan()
+        "{} {}",
+        "✓".bright_green().bold(),
+        t("stills_complete").bright_green().bold()
     );
     println!();
+    for file in files {
+        let kind = match file.kind {
+            StillKind::Thumbnail => t("still_thumbnail"),
+            StillKind::Poster => t("still_poster"),
+            StillKind::ContactSheet => t("still_contact_sheet"),
+            StillKind::Sprite => t("still_sprite"),
+            StillKind::Vtt => t("still_vtt"),
+        };
+        match file.timestamp {
+            Some(ts) => println!(
+                "  {} {} ({})",
+                kind.dimmed(),
+                file.path.bright_cyan(),
+                format_timestamp(ts)
+            ),
+            None => println!("  {} {}", kind.dimmed(), file.path.bright_cyan()),
+        }
+    }
+    println!();
+    println!("  {} {:.2}s", t("time").dimmed(), elapsed.as_secs_f64());
+    println!();
+}
+
+/// One-line summary of the measured scores, e.g. `SSIM 0.9812 · PSNR 41.20 dB · VMAF 94.3`
+fn format_quality_metrics(metrics: &QualityMetrics) -> String {
+    let mut parts = Vec::new();
+    if let Some(ssim) = metrics.ssim {
+        parts.push(format!("SSIM {:.4}", ssim));
+    }
+    if let Some(psnr) = metrics.psnr {
+        parts.push(format!("PSNR {:.2} dB", psnr));
+    }
+    if let Some(vmaf) = metrics.vmaf {
+        parts.push(format!("VMAF {:.1}", vmaf));
+    }
+    parts.join(" · ")
 }
 
 /// Print error message (simple version without hints)
@@ -278,6 +685,13 @@ pub fn print_error_with_hint(error: &crate::error::CompressoError) {
         CompressoError::InvalidInput(_) => t("hint_invalid_input"),
         CompressoError::CorruptedVideo => t("hint_corrupted_video"),
         CompressoError::InvalidOutput(path) => t("hint_invalid_output").replace("{path}", path),
+        CompressoError::IncompatibleOptions(_) => t("hint_incompatible_options"),
+        CompressoError::TargetSizeUnreachable { minimum, .. } => {
+            t("hint_target_size_unreachable").replace("{min}", &format_size(*minimum))
+        }
+        CompressoError::CropOutOfBounds { frame, .. } => {
+            t("hint_crop_out_of_bounds").replace("{frame}", &format!("{}x{}", frame.0, frame.1))
+        }
         CompressoError::FfmpegError(msg) => t("hint_ffmpeg_error").replace("{msg}", msg),
         CompressoError::Io(io_error) => t("hint_io_error").replace("{err}", &io_error.to_string()),
         CompressoError::Cancelled => t("hint_cancelled"),
@@ -379,6 +793,20 @@ pub struct CompressionResultJson {
     pub compressed_size: u64,
     pub saved: u64,
     pub compression_ratio: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub target_size: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub target_met: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub quality_metrics: Option<QualityMetrics>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub crf_search: Option<CrfSearch>,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub remuxed: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hdr: Option<HdrMode>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub parts: Vec<OutputPart>,
     pub elapsed_secs: f64,
 }
 
@@ -419,6 +847,36 @@ pub fn print_single_file_json(
         compressed_size: result.compressed_size,
         saved,
         compression_ratio: ratio,
+        target_size: result.target_size,
+        target_met: result.target_met,
+        quality_metrics: result.quality_metrics,
+        crf_search: result.crf_search,
+        remuxed: result.remuxed,
+        hdr: result.hdr,
+        parts: result.parts.clone(),
+        elapsed_secs: elapsed.as_secs_f64(),
+    };
+    match serde_json::to_string_pretty(&output) {
+        Ok(json) => println!("{}", json),
+        Err(e) => eprintln!("Error serializing to JSON: {}", e),
+    }
+}
+
+/// JSON output for thumbnail mode
+#[derive(Serialize)]
+pub struct StillsJson<'a> {
+    pub success: bool,
+    pub input: &'a str,
+    pub files: &'a [GeneratedFile],
+    pub elapsed_secs: f64,
+}
+
+/// Print the files written by thumbnail mode as JSON
+pub fn print_stills_json(input: &str, files: &[GeneratedFile], elapsed: std::time::Duration) {
+    let output = StillsJson {
+        success: true,
+        input,
+        files,
         elapsed_secs: elapsed.as_secs_f64(),
     };
     match serde_json::to_string_pretty(&output) {
@@ -476,6 +934,20 @@ pub struct BatchFileResultJson {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub compression_ratio: Option<f64>,
     #[serde(skip_serializing_if = "Option::is_none")]
+    pub target_size: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub target_met: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub quality_metrics: Option<QualityMetrics>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub crf_search: Option<CrfSearch>,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub remuxed: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hdr: Option<HdrMode>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub parts: Vec<OutputPart>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub error: Option<String>,
     pub elapsed_secs: f64,
 }
@@ -637,6 +1109,13 @@ pub fn print_batch_summary_json(results: &[BatchFileResult], total_elapsed: std:
                     compressed_size: Some(res.compressed_size),
                     saved: Some(saved),
                     compression_ratio: Some(ratio),
+                    target_size: res.target_size,
+                    target_met: res.target_met,
+                    quality_metrics: res.quality_metrics,
+                    crf_search: res.crf_search,
+                    remuxed: res.remuxed,
+                    hdr: res.hdr,
+                    parts: res.parts.clone(),
                     error: None,
                     elapsed_secs: r.elapsed.as_secs_f64(),
                 }
@@ -649,6 +1128,13 @@ pub fn print_batch_summary_json(results: &[BatchFileResult], total_elapsed: std:
                     compressed_size: None,
                     saved: None,
                     compression_ratio: None,
+                    target_size: None,
+                    target_met: None,
+                    quality_metrics: None,
+                    crf_search: None,
+                    remuxed: false,
+                    hdr: None,
+                    parts: Vec::new(),
                     error: r.error.clone(),
                     elapsed_secs: r.elapsed.as_secs_f64(),
                 }
@@ -688,6 +1174,7 @@ mod tests {
             file_path: "out.mp4".to_string(),
             original_size: 67108864,
             compressed_size: 21102387,
+            ..Default::default()
         };
         let batch = vec![BatchFileResult {
             input_path: "video1.mp4".to_string(),
@@ -768,6 +1255,7 @@ mod tests {
             file_path: "out.mp4".to_string(),
             original_size: 1000,
             compressed_size: 400,
+            ..Default::default()
         };
         let saved = result.original_size - result.compressed_size;
         let ratio = (saved as f64 / result.original_size as f64) * 100.0;
@@ -0,0 +1,480 @@
+use std::collections::HashMap;
+
+use serde::Deserialize;
+
+use crate::domain::{StreamInfo, StreamKind, VideoInfo};
+
+/// Top level of the ffprobe JSON document
+#[derive(Debug, Deserialize)]
+struct ProbeOutput {
+    #[serde(default)]
+    streams: Vec<ProbeStream>,
+    format: Option<ProbeFormat>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ProbeFormat {
+    format_name: Option<String>,
+    /// Seconds, as a decimal string
+    duration: Option<String>,
+    /// bit/s, as a decimal string
+    bit_rate: Option<String>,
+    #[serde(default)]
+    tags: HashMap<String, String>,
+}
+
+/// One entry of `streams`. ffprobe prints most numbers as strings, so they
+/// are parsed after deserialization.
+#[derive(Debug, Deserialize)]
+struct ProbeStream {
+    index: u32,
+    codec_type: Option<String>,
+    codec_name: Option<String>,
+    profile: Option<String>,
+    width: Option<u32>,
+    height: Option<u32>,
+    pix_fmt: Option<String>,
+    sample_aspect_ratio: Option<String>,
+    color_range: Option<String>,
+    color_space: Option<String>,
+    color_transfer: Option<String>,
+    color_primaries: Option<String>,
+    avg_frame_rate: Option<String>,
+    r_frame_rate: Option<String>,
+    bit_rate: Option<String>,
+    duration: Option<String>,
+    channels: Option<u32>,
+    channel_layout: Option<String>,
+    sample_rate: Option<String>,
+    #[serde(default)]
+    disposition: HashMap<String, i64>,
+    #[serde(default)]
+    tags: HashMap<String, String>,
+    #[serde(default)]
+    side_data_list: Vec<ProbeSideData>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ProbeSideData {
+    side_data_type: Option<String>,
+    /// Counter-clockwise degrees of the display matrix
+    rotation: Option<f64>,
+    // Mastering display metadata: CIE 1931 chromaticities and luminance in
+    // cd/m², each a fraction such as `34000/50000`
+    red_x: Option<String>,
+    red_y: Option<String>,
+    green_x: Option<String>,
+    green_y: Option<String>,
+    blue_x: Option<String>,
+    blue_y: Option<String>,
+    white_point_x: Option<String>,
+    white_point_y: Option<String>,
+    min_luminance: Option<String>,
+    max_luminance: Option<String>,
+    // Content light level metadata, in cd/m²
+    max_content: Option<u32>,
+    max_average: Option<u32>,
+}
+
+/// Top level of `ffprobe -show_entries packet=...` JSON
+#[derive(Debug, Deserialize)]
+struct ProbePackets {
+    #[serde(default)]
+    packets: Vec<ProbePacket>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ProbePacket {
+    codec_type: Option<String>,
+    pts_time: Option<String>,
+    dts_time: Option<String>,
+    size: Option<String>,
+    /// `K__` for a keyframe
+    flags: Option<String>,
+}
+
+/// One packet of a file, as far as `--split-size` needs it
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PacketInfo {
+    /// Presentation time in seconds
+    pub time: f64,
+    pub size: u64,
+    /// A video keyframe, where a part can begin
+    pub keyframe: bool,
+}
+
+/// Build a [`VideoInfo`] from the output of
+/// `ffprobe -print_format json -show_streams -show_format`.
+pub fn parse_ffprobe_json(json: &str) -> serde_json::Result<VideoInfo> {
+    let probe: ProbeOutput = serde_json::from_str(json)?;
+    let streams: Vec<StreamInfo> = probe.streams.iter().filter_map(stream_info).collect();
+
+    // Cover art shows up as a one-frame video stream; skip it.
+    let main_video = streams
+        .iter()
+        .find(|s| s.kind == StreamKind::Video && !s.attached_pic);
+    let main_audio = streams.iter().find(|s| s.kind == StreamKind::Audio);
+
+    let format = probe.format.as_ref();
+    let duration_seconds = format
+        .and_then(|f| parse_number::<f64>(f.duration.as_deref()))
+        .or_else(|| {
+            let index = main_video?.index;
+            let stream = probe.streams.iter().find(|s| s.index == index)?;
+            parse_number(stream.duration.as_deref())
+        })
+        .filter(|d| *d > 0.0);
+
+    // Players (and FFmpeg's autorotate) show a 90°/270° video on its side,
+    // so report the size as displayed.
+    let dimensions = main_video.and_then(|v| {
+        let (w, h) = (v.width?, v.height?);
+        Some(match v.rotation {
+            Some(90 | 270) => (h, w),
+            _ => (w, h),
+        })
+    });
+
+    Ok(VideoInfo {
+        duration: duration_seconds.map(format_duration),
+        duration_seconds,
+        dimensions,
+        fps: main_video.and_then(|v| v.fps),
+        video_codec: main_video.and_then(|v| v.codec.clone()),
+        audio_codec: main_audio.and_then(|a| a.codec.clone()),
+        container: format.and_then(|f| f.format_name.clone()),
+        bitrate: format.and_then(|f| parse_number(f.bit_rate.as_deref())),
+        creation_time: format.and_then(|f| f.tags.get("creation_time").cloned()),
+        streams,
+        parts: Vec::new(),
+    })
+}
+
+/// Packets in presentation order from the output of `ffprobe -print_format
+/// json -show_entries packet=codec_type,pts_time,dts_time,size,flags`. A
+/// packet without timestamps is counted at the time of the one before.
+pub fn parse_packets_json(json: &str) -> serde_json::Result<Vec<PacketInfo>> {
+    let probe: ProbePackets = serde_json::from_str(json)?;
+    let mut time = 0.0;
+    let mut packets: Vec<PacketInfo> = probe
+        .packets
+        .iter()
+        .map(|packet| {
+            if let Some(t) = parse_number(packet.pts_time.as_deref())
+                .or_else(|| parse_number(packet.dts_time.as_deref()))
+            {
+                time = t;
+            }
+            PacketInfo {
+                time,
+                size: parse_number(packet.size.as_deref()).unwrap_or(0),
+                keyframe: packet.codec_type.as_deref() == Some("video")
+                    && packet.flags.as_deref().is_some_and(|f| f.starts_with('K')),
+            }
+        })
+        .collect();
+    // B-frames are stored ahead of the frames they come after.
+    packets.sort_by(|a, b| a.time.total_cmp(&b.time));
+    Ok(packets)
+}
+
+fn stream_info(stream: &ProbeStream) -> Option<StreamInfo> {
+    let kind = match stream.codec_type.as_deref()? {
+        "video" => StreamKind::Video,
+        "audio" => StreamKind::Audio,
+        "subtitle" => StreamKind::Subtitle,
+        "data" => StreamKind::Data,
+        "attachment" => StreamKind::Attachment,
+        _ => return None,
+    };
+    let flag = |name: &str| stream.disposition.get(name).copied().unwrap_or(0) != 0;
+    let tag = |name: &str| {
+        stream
+            .tags
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.clone())
+    };
+
+    // Display matrix rotation is counter-clockwise; the legacy `rotate` tag
+    // is clockwise. Normalise both to clockwise 0-359.
+    let rotation = stream
+        .side_data_list
+        .iter()
+        .find_map(|side| side.rotation)
+        .map(|ccw| (-ccw.round() as i32).rem_euclid(360))
+        .or_else(|| {
+            tag("rotate")
+                .and_then(|r| r.parse::<i32>().ok())
+                .map(|cw| cw.rem_euclid(360))
+        })
+        .filter(|r| *r != 0);
+
+    let fps = parse_rate(stream.avg_frame_rate.as_deref())
+        .or_else(|| parse_rate(stream.r_frame_rate.as_deref()));
+
+    let side_data = |kind: &str| {
+        stream
+            .side_data_list
+            .iter()
+            .find(|side| side.side_data_type.as_deref() == Some(kind))
+    };
+    let mastering_display = side_data("Mastering display metadata").and_then(mastering_display);
+    let max_cll = side_data("Content light level metadata")
+        .and_then(|side| Some(format!("{},{}", side.max_content?, side.max_average?)));
+
+    Some(StreamInfo {
+        index: stream.index,
+        kind,
+        codec: stream.codec_name.clone(),
+        profile: stream.profile.clone(),
+        bitrate: parse_number(stream.bit_rate.as_deref()),
+        language: tag("language").filter(|l| l != "und"),
+        title: tag("title"),
+        default: flag("default"),
+        width: stream.width.filter(|_| kind == StreamKind::Video),
+        height: stream.height.filter(|_| kind == StreamKind::Video),
+        fps: fps.filter(|_| kind == StreamKind::Video),
+        pix_fmt: stream.pix_fmt.clone(),
+        // `0:1` and `N/A` mean the file does not say.
+        sample_aspect_ratio: stream
+            .sample_aspect_ratio
+            .clone()
+            .filter(|sar| sar.as_str() != "0:1" && sar.as_str() != "N/A"),
+        color_range: stream.color_range.clone(),
+        color_space: stream.color_space.clone(),
+        color_transfer: stream.color_transfer.clone(),
+        color_primaries: stream.color_primaries.clone(),
+        rotation,
+        mastering_display,
+        max_cll,
+        attached_pic: flag("attached_pic"),
+        channels: stream.channels,
+        channel_layout: stream.channel_layout.clone(),
+        sample_rate: parse_number(stream.sample_rate.as_deref()),
+    })
+}
+
+/// Mastering display side data in x265's `master-display` notation, which
+/// counts chromaticities in 0.00002 and luminance in 0.0001 cd/m² steps.
+fn mastering_display(side: &ProbeSideData) -> Option<String> {
+    let chroma = |value: &Option<String>| {
+        parse_ratio(value.as_deref()).map(|v| (v * 50_000.0).round() as u64)
+    };
+    let luma = |value: &Option<String>| {
+        parse_ratio(value.as_deref()).map(|v| (v * 10_000.0).round() as u64)
+    };
+    Some(format!(
+        "G({},{})B({},{})R({},{})WP({},{})L({},{})",
+        chroma(&side.green_x)?,
+        chroma(&side.green_y)?,
+        chroma(&side.blue_x)?,
+        chroma(&side.blue_y)?,
+        chroma(&side.red_x)?,
+        chroma(&side.red_y)?,
+        chroma(&side.white_point_x)?,
+        chroma(&side.white_point_y)?,
+        luma(&side.max_luminance)?,
+        luma(&side.min_luminance)?,
+    ))
+}
+
+fn parse_number<T: std::str::FromStr>(value: Option<&str>) -> Option<T> {
+    value?.trim().parse().ok()
+}
+
+/// Parse a frame rate such as `30000/1001`; `0/0` means unknown.
+fn parse_rate(value: Option<&str>) -> Option<f32> {
+    parse_ratio(value)
+        .filter(|rate| *rate > 0.0)
+        .map(|rate| rate as f32)
+}
+
+/// Parse a fraction such as `50/10000`, or a plain number.
+fn parse_ratio(value: Option<&str>) -> Option<f64> {
+    let value = value?.trim();
+    let Some((num, den)) = value.split_once('/') else {
+        return value.parse().ok().filter(|v: &f64| *v >= 0.0);
+    };
+    let num: f64 = num.parse().ok()?;
+    let den: f64 = den.parse().ok()?;
+    if num < 0.0 || den <= 0.0 {
+        return None;
+    }
+    Some(num / den)
+}
+
+/// `HH:MM:SS.ss`, the form FFmpeg prints in its own banner
+pub fn format_duration(seconds: f64) -> String {
+    let centis = (seconds * 100.0).round() as u64;
+    format!(
+        "{:02}:{:02}:{:02}.{:02}",
+        centis / 360_000,
+        centis / 6000 % 60,
+        centis / 100 % 60,
+        centis % 100
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Trimmed `ffprobe` output for a phone recording with cover art and a
+    /// subtitle track.
+    const SAMPLE: &str = r#"{
+        "streams": [
+            {
+                "index": 0, "codec_name": "hevc", "profile": "Main 10",
+                "codec_type": "video", "width": 3840, "height": 2160,
+                "pix_fmt": "yuv420p10le", "sample_aspect_ratio": "1:1", "color_range": "tv",
+                "color_space": "bt2020nc", "color_transfer": "arib-std-b67",
+                "color_primaries": "bt2020", "r_frame_rate": "30/1",
+                "avg_frame_rate": "30000/1001", "bit_rate": "48000000",
+                "disposition": { "default": 1, "attached_pic": 0 },
+                "tags": { "language": "und" },
+                "side_data_list": [ { "side_data_type": "Display Matrix", "rotation": -90 } ]
+            },
+            {
+                "index": 1, "codec_name": "aac", "profile": "LC",
+                "codec_type": "audio", "sample_rate": "48000", "channels": 2,
+                "channel_layout": "stereo", "bit_rate": "192000",
+                "disposition": { "default": 1, "attached_pic": 0 },
+                "tags": { "language": "eng", "title": "Main" }
+            },
+            {
+                "index": 2, "codec_name": "mov_text", "codec_type": "subtitle",
+                "disposition": { "default": 0 },
+                "tags": { "LANGUAGE": "rus" }
+            },
+            {
+                "index": 3, "codec_name": "mjpeg", "codec_type": "video",
+                "width": 600, "height": 600, "avg_frame_rate": "0/0",
+                "disposition": { "attached_pic": 1 }
+            }
+        ],
+        "format": {
+            "format_name": "mov,mp4,m4a,3gp,3g2,mj2",
+            "duration": "75.250000",
+            "bit_rate": "48350000",
+            "tags": {
+                "creation_time": "2024-06-01T18:22:05.000000Z",
+                "com.apple.quicktime.location.ISO6709": "+55.7558+037.6173+150.000/"
+            }
+        }
+    }"#;
+
+    #[test]
+    fn test_parse_ffprobe_json_main_streams() {
+        let info = parse_ffprobe_json(SAMPLE).unwrap();
+        assert_eq!(info.duration_seconds, Some(75.25));
+        assert_eq!(info.duration.as_deref(), Some("00:01:15.25"));
+        // Rotated 90°: displayed as portrait.
+        assert_eq!(info.dimensions, Some((2160, 3840)));
+        assert!((info.fps.unwrap() - 29.97).abs() < 0.01);
+        assert_eq!(info.video_codec.as_deref(), Some("hevc"));
+        assert_eq!(info.audio_codec.as_deref(), Some("aac"));
+        assert_eq!(info.container.as_deref(), Some("mov,mp4,m4a,3gp,3g2,mj2"));
+        assert_eq!(info.bitrate, Some(48_350_000));
+        assert_eq!(
+            info.creation_time.as_deref(),
+            Some("2024-06-01T18:22:05.000000Z")
+        );
+    }
+
+    #[test]
+    fn test_parse_ffprobe_json_stream_details() {
+        let info = parse_ffprobe_json(SAMPLE).unwrap();
+        assert_eq!(info.streams.len(), 4);
+
+        let video = &info.streams[0];
+        assert_eq!(video.rotation, Some(90));
+        assert_eq!(video.sample_aspect_ratio.as_deref(), Some("1:1"));
+        assert_eq!(video.color_transfer.as_deref(), Some("arib-std-b67"));
+        assert_eq!(video.language, None, "`und` is not a language");
+
+        let audio = &info.streams[1];
+        assert_eq!(audio.sample_rate, Some(48000));
+        assert_eq!(audio.language.as_deref(), Some("eng"));
+        assert_eq!(audio.title.as_deref(), Some("Main"));
+        assert!(audio.default);
+
+        let subtitle = &info.streams[2];
+        assert_eq!(subtitle.kind, StreamKind::Subtitle);
+        assert_eq!(subtitle.language.as_deref(), Some("rus"));
+
+        assert!(info.streams[3].attached_pic);
+        assert_eq!(info.streams[3].fps, None);
+
+        assert_eq!(info.main_video().map(|v| v.index), Some(0));
+        assert_eq!(info.hdr_format(), Some("HLG"));
+    }
+
+    #[test]
+    fn test_parse_ffprobe_json_hdr10_metadata() {
+        let json = r#"{
+            "streams": [
+                {
+                    "index": 0, "codec_name": "hevc", "codec_type": "video",
+                    "width": 3840, "height": 2160, "color_transfer": "smpte2084",
+                    "side_data_list": [
+                        {
+                            "side_data_type": "Mastering display metadata",
+                            "red_x": "34000/50000", "red_y": "16000/50000",
+                            "green_x": "13250/50000", "green_y": "34500/50000",
+                            "blue_x": "7500/50000", "blue_y": "3000/50000",
+                            "white_point_x": "15635/50000", "white_point_y": "16450/50000",
+                            "min_luminance": "50/10000", "max_luminance": "10000000/10000"
+                        },
+                        {
+                            "side_data_type": "Content light level metadata",
+                            "max_content": 1000, "max_average": 400
+                        }
+                    ]
+                }
+            ]
+        }"#;
+        let info = parse_ffprobe_json(json).unwrap();
+        let video = info.main_video().unwrap();
+        assert_eq!(
+            video.mastering_display.as_deref(),
+            Some("G(13250,34500)B(7500,3000)R(34000,16000)WP(15635,16450)L(10000000,50)")
+        );
+        assert_eq!(video.max_cll.as_deref(), Some("1000,400"));
+        assert_eq!(video.rotation, None);
+
+        // The phone recording has neither.
+        let info = parse_ffprobe_json(SAMPLE).unwrap();
+        assert_eq!(info.streams[0].mastering_display, None);
+        assert_eq!(info.streams[0].max_cll, None);
+    }
+
+    #[test]
+    fn test_parse_packets_json() {
+        let json = r#"{
+            "packets": [
+                { "codec_type": "video", "pts_time": "0.000000", "dts_time": "-0.066667", "size": "40000", "flags": "K__" },
+                { "codec_type": "audio", "pts_time": "0.000000", "dts_time": "0.000000", "size": "400", "flags": "K__" },
+                { "codec_type": "video", "pts_time": "0.133333", "dts_time": "-0.033333", "size": "9000", "flags": "___" },
+                { "codec_type": "video", "pts_time": "0.066667", "dts_time": "0.000000", "size": "3000", "flags": "___" },
+                { "codec_type": "audio", "pts_time": "N/A", "dts_time": "N/A", "size": "380", "flags": "K__" }
+            ]
+        }"#;
+        let packets = parse_packets_json(json).unwrap();
+        let times: Vec<f64> = packets.iter().map(|p| p.time).collect();
+        // The untimed audio packet stays with the one stored before it.
+        assert_eq!(times, [0.0, 0.0, 0.066667, 0.066667, 0.133333]);
+        assert!(packets[0].keyframe);
+        assert!(!packets[1].keyframe, "only video keyframes start a part");
+        assert_eq!(packets[2].size, 3000);
+        assert_eq!(packets[3].size, 380);
+    }
+
+    #[test]
+    fn test_parse_ffprobe_json_without_format() {
+        let info = parse_ffprobe_json(r#"{ "streams": [] }"#).unwrap();
+        assert_eq!(info.duration_seconds, None);
+        assert_eq!(info.dimensions, None);
+        assert!(parse_ffprobe_json("not json").is_err());
+    }
+}